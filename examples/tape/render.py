@@ -38,7 +38,6 @@ def record(output_cast, scenario_file, common_file="common.exp", width=80, heigh
             "KUBECONFIG": host_kubeconfig,
             "VAULT_ADDR": "http://127.0.0.1:8200",
             "VAULT_TOKEN": "token",
-            "LADE_VAULT_HTTP": "1",
             "LADE_CONFIG_PATH": os.path.join(home_dir, ".lade-test-config.json"),
             "LADE_SHELL": "zsh",
             "USER": "bob",
@@ -6,70 +6,193 @@ use std::{
 use anyhow::{Ok, Result};
 use rustc_hash::{FxHashMap, FxHashSet};
 
+mod error;
 pub mod mcp;
 mod providers;
 mod resolve;
 
+pub use error::{Details, Error};
 pub use providers::Providers;
 pub use providers::Warnings;
 pub use providers::compat;
+pub use providers::keepass;
+pub use providers::limits::{Limits, ProviderLimits};
 pub use providers::network;
-pub use resolve::{Dag, Template, resolve, resolve_one};
+pub use providers::vault;
+pub use resolve::{
+    Dag, Template, Transform, apply_transforms, resolve, resolve_one, split_template_transforms,
+    split_transforms,
+};
 
 type Hydration = FxHashMap<String, String>;
 
+/// Hydrated values and the leases issued for them, which the caller revokes
+/// once the values are no longer needed.
 pub async fn hydrate(
     env: HashMap<String, String>,
     cwd: PathBuf,
     extra_env: HashMap<String, String>,
-) -> Result<HashMap<String, String>> {
-    Ok(hydrate_with_maskable(env, cwd, extra_env).await?.0)
+) -> Result<(HashMap<String, String>, Vec<vault::Lease>)> {
+    let (values, _, _, leases) = hydrate_with_maskable(env, cwd, extra_env).await?;
+    Ok((values, leases))
 }
 
+/// Hydrated values, the sources to mask, warnings, and the leases issued
+/// while resolving.
+pub type Hydrated = (
+    HashMap<String, String>,
+    FxHashSet<String>,
+    Vec<String>,
+    Vec<vault::Lease>,
+);
+
 pub async fn hydrate_with_maskable(
     env: HashMap<String, String>,
     cwd: PathBuf,
     extra_env: HashMap<String, String>,
-) -> Result<(HashMap<String, String>, FxHashSet<String>, Vec<String>)> {
-    let mut providers = Providers::new();
-    for value_or_uri in env.values() {
-        providers.add(value_or_uri.clone())?;
+) -> Result<Hydrated> {
+    hydrate_with_limits(env, cwd, extra_env, &ProviderLimits::default()).await
+}
+
+/// [`hydrate_with_maskable`] with timeouts, retries and concurrency limits.
+pub async fn hydrate_with_limits(
+    env: HashMap<String, String>,
+    cwd: PathBuf,
+    extra_env: HashMap<String, String>,
+    limits: &ProviderLimits,
+) -> Result<Hydrated> {
+    let mut providers = Providers::with_limits(limits.clone());
+    let mut pipelines = HashMap::new();
+    for (key, value_or_uri) in &env {
+        let (source, transforms) = split_transforms(value_or_uri)
+            .map_err(|e| Error::invalid_uri(value_or_uri, e.to_string()).with_binding(key))?;
+        providers.add(source.to_string())?;
+        pipelines.insert(value_or_uri.clone(), (source.to_string(), transforms));
     }
     let warnings = Warnings::default();
-    let (hydration, maskable) = providers.resolve(&cwd, &extra_env, &warnings).await?;
-
-    let values = env
-        .into_iter()
-        .map(|(key, value_or_uri)| {
-            let value = hydration.get(&value_or_uri).cloned().unwrap_or_else(|| {
-                panic!(
-                    "Cannot find {} in {}",
-                    value_or_uri,
-                    hydration
-                        .keys()
-                        .cloned()
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
-            });
-            (key, value)
+    let (hydration, mut maskable) = match providers.resolve(&cwd, &extra_env, &warnings).await {
+        std::result::Result::Ok(resolved) => resolved,
+        Err(e) => {
+            revoke_all(&providers).await;
+            return Err(with_bindings(e, &env, &pipelines));
+        }
+    };
+
+    let mut values = HashMap::new();
+    for (key, value_or_uri) in env {
+        let (source, transforms) = &pipelines[&value_or_uri];
+        let Some(value) = hydration.get(source).cloned() else {
+            revoke_all(&providers).await;
+            let uri = error::redact(source);
+            return Err(
+                Error::not_found(source, format!("no value resolved for {uri}"))
+                    .with_binding(key)
+                    .into(),
+            );
+        };
+        // A transformed value is masked like the value it comes from.
+        if maskable.contains(source) {
+            maskable.insert(value_or_uri.clone());
+        }
+        match apply_transforms(source, value, transforms) {
+            std::result::Result::Ok(value) => values.insert(key, value),
+            Err(e) => {
+                revoke_all(&providers).await;
+                return Err(Error::transform(source, e.to_string())
+                    .with_binding(key)
+                    .into());
+            }
+        };
+    }
+
+    Ok((values, maskable, warnings.take(), providers.leases()))
+}
+
+/// Names the bindings behind a provider error: the ones using its URI, or
+/// every binding of its provider when the failure is not specific to one.
+fn with_bindings(
+    error: anyhow::Error,
+    env: &HashMap<String, String>,
+    pipelines: &HashMap<String, (String, Vec<Transform>)>,
+) -> anyhow::Error {
+    let error = match error.downcast::<Error>() {
+        std::result::Result::Ok(error) => error,
+        Err(error) => return error,
+    };
+    let mut bindings = env
+        .iter()
+        .filter(|(_, value_or_uri)| {
+            let source = &pipelines[*value_or_uri].0;
+            match error.uri() {
+                Some(uri) => error::redact(source) == uri,
+                None => source.split_once("://").map(|(scheme, _)| scheme) == Some(error.scheme()),
+            }
         })
-        .collect();
+        .map(|(key, _)| key.as_str())
+        .collect::<Vec<_>>();
+    if bindings.is_empty() {
+        return error.into();
+    }
+    bindings.sort_unstable();
+    error.with_binding(bindings.join(", ")).into()
+}
 
-    Ok((values, maskable, warnings.take()))
+/// Something failed after Vault issued its leases; nothing will use them, so
+/// do not leave them valid until they expire.
+async fn revoke_all(providers: &Providers) {
+    for lease in providers.leases() {
+        let _ = lease.revoke().await;
+    }
 }
 
+/// One value and, like [`hydrate`], the leases issued for it.
 pub async fn hydrate_one(
     value: String,
     cwd: &Path,
     extra_env: &HashMap<String, String>,
-) -> Result<String> {
+) -> Result<(String, Vec<vault::Lease>)> {
+    let (source, transforms) =
+        split_transforms(&value).map_err(|e| Error::invalid_uri(&value, e.to_string()))?;
     let mut providers = Providers::new();
-    providers.add(value.clone())?;
-    let (hydration, _) = providers
+    providers.add(source.to_string())?;
+    let resolved = providers
         .resolve(cwd, extra_env, &Warnings::default())
-        .await?;
-    Ok(hydration.get(&value).unwrap().to_owned())
+        .await
+        .and_then(|(mut hydration, _)| {
+            hydration.remove(source).ok_or_else(|| {
+                Error::not_found(
+                    source,
+                    format!("no value resolved for {}", error::redact(source)),
+                )
+                .into()
+            })
+        })
+        .and_then(|value| {
+            apply_transforms(source, value, &transforms)
+                .map_err(|e| Error::transform(source, e.to_string()).into())
+        });
+    match resolved {
+        std::result::Result::Ok(value) => Ok((value, providers.leases())),
+        Err(e) => {
+            revoke_all(&providers).await;
+            Err(e)
+        }
+    }
+}
+
+/// Stores `value` at a secret URI, for the providers that support writes.
+pub async fn write(
+    uri: &str,
+    value: &str,
+    cwd: &Path,
+    extra_env: &HashMap<String, String>,
+) -> Result<()> {
+    let (source, transforms) =
+        split_transforms(uri).map_err(|e| Error::invalid_uri(uri, e.to_string()))?;
+    if !transforms.is_empty() {
+        return Err(Error::invalid_uri(uri, "cannot write through | transforms").into());
+    }
+    Providers::new().write(source, value, cwd, extra_env).await
 }
 
 #[cfg(test)]
@@ -83,7 +206,7 @@ mod tests {
             ("KEY1".to_string(), "value1".to_string()),
             ("KEY2".to_string(), "!value2".to_string()),
         ]);
-        let (values, maskable, _warnings) =
+        let (values, maskable, _warnings, _leases) =
             hydrate_with_maskable(env, PathBuf::from("."), HashMap::new())
                 .await
                 .unwrap();
@@ -96,19 +219,22 @@ mod tests {
     async fn test_hydrate_raw_values_with_extra_env_ignored_by_raw_provider() {
         let env = HashMap::from([("KEY".to_string(), "rawval".to_string())]);
         let extra = HashMap::from([("INJECTED".to_string(), "token123".to_string())]);
-        let (values, maskable, _warnings) = hydrate_with_maskable(env, PathBuf::from("."), extra)
-            .await
-            .unwrap();
+        let (values, maskable, _warnings, _leases) =
+            hydrate_with_maskable(env, PathBuf::from("."), extra)
+                .await
+                .unwrap();
         assert_eq!(values.get("KEY").unwrap(), "rawval");
         assert!(maskable.is_empty());
     }
 
     #[tokio::test]
     async fn test_hydrate_one_raw_with_empty_extra_env() {
-        let result = hydrate_one("mytoken".to_string(), &PathBuf::from("."), &HashMap::new())
-            .await
-            .unwrap();
+        let (result, leases) =
+            hydrate_one("mytoken".to_string(), &PathBuf::from("."), &HashMap::new())
+                .await
+                .unwrap();
         assert_eq!(result, "mytoken");
+        assert!(leases.is_empty());
     }
 
     #[tokio::test]
@@ -117,18 +243,108 @@ mod tests {
             ("KEY1".to_string(), "a".to_string()),
             ("KEY2".to_string(), "a".to_string()),
         ]);
-        let (values, _, _warnings) = hydrate_with_maskable(env, PathBuf::from("."), HashMap::new())
-            .await
-            .unwrap();
+        let (values, _, _warnings, _) =
+            hydrate_with_maskable(env, PathBuf::from("."), HashMap::new())
+                .await
+                .unwrap();
         assert_eq!(values.get("KEY1").unwrap(), "a");
         assert_eq!(values.get("KEY2").unwrap(), "a");
     }
 
     #[tokio::test]
     async fn test_hydrate_one_raw_bang_escape() {
-        let result = hydrate_one("!escaped".to_string(), &PathBuf::from("."), &HashMap::new())
+        let (result, leases) =
+            hydrate_one("!escaped".to_string(), &PathBuf::from("."), &HashMap::new())
+                .await
+                .unwrap();
+        assert_eq!(result, "escaped");
+        assert!(leases.is_empty());
+    }
+
+    async fn hydrate_error(env: &[(&str, &str)]) -> Error {
+        let env = env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        hydrate_with_maskable(env, PathBuf::from("."), HashMap::new())
+            .await
+            .unwrap_err()
+            .downcast::<Error>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_missing_file_is_a_typed_error() {
+        let error = hydrate_error(&[("DB", "file://missing.json?query=.db"), ("KEY", "raw")]).await;
+        assert_eq!(error.kind(), "not_found");
+        assert_eq!(error.scheme(), "file");
+        assert_eq!(error.uri(), Some("file://missing.json?query=***"));
+        assert_eq!(error.binding(), Some("DB"));
+        assert!(error.to_string().contains("cannot read file"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_host_is_a_typed_error() {
+        let error = hydrate_error(&[("TOKEN", "doppler:///project/config/TOKEN")]).await;
+        assert_eq!(error.kind(), "invalid_uri");
+        assert_eq!(error.scheme(), "doppler");
+        assert_eq!(error.binding(), Some("TOKEN"));
+    }
+
+    #[tokio::test]
+    async fn test_provider_failure_names_its_bindings() {
+        let error = hydrate_error(&[
+            ("B", "vault://127.0.0.1:1/secret/a/b?tls=false"),
+            ("A", "vault://127.0.0.1:1/secret/a/token?tls=false"),
+            ("KEY", "raw"),
+        ])
+        .await;
+        assert_eq!(error.kind(), "provider");
+        assert_eq!(error.scheme(), "vault");
+        assert_eq!(error.uri(), None);
+        assert_eq!(error.binding(), Some("A, B"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_transform_is_a_typed_error() {
+        let error = hydrate_error(&[("KEY", "sh://printf raw | lines(2)")]).await;
+        assert_eq!(error.kind(), "transform");
+        assert_eq!(error.binding(), Some("KEY"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_hydrate_one_returns_or_revokes_its_leases() {
+        let fake_bin = tempfile::tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        providers::fake_cli(
+            &fake_bin,
+            "vault",
+            &format!(
+                "echo \"$@\" >> {}\necho '{}'",
+                calls.display(),
+                r#"{"lease_id":"database/creds/app/abc","lease_duration":3600,"data":{"username":"v-app-1"}}"#
+            ),
+        );
+        let env = HashMap::from([(
+            "PATH".to_string(),
+            fake_bin.path().to_string_lossy().into_owned(),
+        )]);
+        let uri = "vault://localhost/database/creds/app/username";
+        let (value, leases) = hydrate_one(uri.to_string(), Path::new("."), &env)
             .await
             .unwrap();
-        assert_eq!(result, "escaped");
+        assert_eq!(value, "v-app-1");
+        assert_eq!(leases.len(), 1);
+
+        std::fs::remove_file(&calls).unwrap();
+        hydrate_one(format!("{uri} | lines(2)"), Path::new("."), &env)
+            .await
+            .unwrap_err();
+        assert!(
+            std::fs::read_to_string(calls)
+                .unwrap()
+                .contains("lease revoke -address=https://localhost database/creds/app/abc")
+        );
     }
 }
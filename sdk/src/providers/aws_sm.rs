@@ -0,0 +1,319 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::{Ok, Result, anyhow, bail};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use itertools::Itertools;
+use log::debug;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, add_url, deserialize_output, run_cli};
+
+/// `aws-sm://<region>/<secret-id>[/<json-key>]`. Secret ids containing `/`
+/// must percent-encode it (`prod%2Fdb`) so the optional JSON key stays
+/// unambiguous.
+#[derive(Default)]
+pub struct AwsSecretsManager {
+    urls: FxHashMap<Url, String>,
+}
+
+impl AwsSecretsManager {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AwsSecretValue {
+    secret_string: Option<String>,
+}
+
+/// A malformed AWS Secrets Manager URI, named without its query values.
+fn invalid_uri(url: &Url, message: &str) -> anyhow::Error {
+    Error::invalid_uri(url.as_str(), format!("{message}: {}", redact(url.as_str()))).into()
+}
+
+fn secret_id(url: &Url) -> Result<String> {
+    let id = url.path().split('/').nth(1).unwrap_or_default();
+    if id.is_empty() {
+        return Err(invalid_uri(
+            url,
+            "AWS Secrets Manager URI is missing a secret id",
+        ));
+    }
+    Ok(urlencoding::decode(id)
+        .map_err(|_| invalid_uri(url, "AWS Secrets Manager secret id is not valid UTF-8"))?
+        .into_owned())
+}
+
+fn json_key(url: &Url) -> Result<Option<String>> {
+    match url.path().split('/').nth(2) {
+        Some(key) if !key.is_empty() => Ok(Some(
+            urlencoding::decode(key)
+                .map_err(|_| invalid_uri(url, "AWS Secrets Manager key is not valid UTF-8"))?
+                .into_owned(),
+        )),
+        _ => Ok(None),
+    }
+}
+
+fn extract(secret: &str, key: Option<&str>, id: &str, value: &str) -> Result<String> {
+    let Some(key) = key else {
+        return Ok(secret.to_string());
+    };
+    let json = serde_json::from_str::<Value>(secret).map_err(|_| {
+        Error::not_found(
+            value,
+            format!("AWS Secrets Manager secret {id} is not JSON, cannot read key {key}"),
+        )
+    })?;
+    match json.get(key) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(Error::not_found(
+            value,
+            format!("Key {key} not found in AWS Secrets Manager secret {id}"),
+        )
+        .into()),
+    }
+}
+
+#[async_trait]
+impl Provider for AwsSecretsManager {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "aws-sm")
+    }
+
+    fn name(&self) -> &'static str {
+        "AWS Secrets Manager"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://docs.aws.amazon.com/cli/latest/userguide/getting-started-install.html"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        let fetches = self
+            .urls
+            .iter()
+            .into_group_map_by(|(url, _)| url.host_str().unwrap_or_default().to_string())
+            .into_iter()
+            .flat_map(|(region, group)| {
+                group
+                    .into_iter()
+                    .into_group_map_by(|(url, _)| url.path().split('/').nth(1).unwrap_or_default())
+                    .into_values()
+                    .map(|group| {
+                        let region = region.clone();
+                        let extra_env = Arc::clone(&extra_env);
+                        async move {
+                            let (first, value) = group[0];
+                            if region.is_empty() {
+                                return Err(invalid_uri(
+                                    first,
+                                    "AWS Secrets Manager URI is missing a region",
+                                ));
+                            }
+                            let id = secret_id(first)?;
+                            let cmd = [
+                                "aws",
+                                "secretsmanager",
+                                "get-secret-value",
+                                "--region",
+                                &region,
+                                "--secret-id",
+                                &id,
+                                "--output",
+                                "json",
+                            ];
+                            debug!("Lade run: {}", cmd.join(" "));
+                            let child = run_cli(&cmd, &extra_env, name, install_url, None).await?;
+                            if !child.status.success() {
+                                let stderr = String::from_utf8_lossy(&child.stderr);
+                                let message = format!(
+                                    "{name} error for secret {id} in {region}: {}",
+                                    stderr.trim()
+                                );
+                                if stderr.contains("ResourceNotFoundException") {
+                                    return Err(Error::not_found(value, message).into());
+                                }
+                                bail!(message);
+                            }
+                            let loaded: AwsSecretValue = deserialize_output(&child, name)?;
+                            let secret = loaded.secret_string.ok_or_else(|| {
+                                anyhow!("AWS Secrets Manager secret {id} is binary, only SecretString is supported")
+                            })?;
+                            let hydration = group
+                                .into_iter()
+                                .map(|(url, value)| {
+                                    let key = json_key(url)?;
+                                    Ok((value.clone(), extract(&secret, key.as_deref(), &id, value)?))
+                                })
+                                .collect::<Result<Hydration>>()?;
+                            debug!("hydration: {:?}", hydration);
+                            Ok(hydration)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    #[test]
+    fn test_add_routing() {
+        let mut p = AwsSecretsManager::new();
+        assert!(p.add("aws-sm://us-east-1/my-secret".to_string()).is_ok());
+        assert!(
+            p.add("aws-sm://us-east-1/my-secret/password".to_string())
+                .is_ok()
+        );
+        assert!(p.add("vault://host/mount/key/field".to_string()).is_err());
+        assert!(p.add("plainvalue".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_extract_json_key() {
+        let secret = r#"{"username":"app","port":5432}"#;
+        let uri = "aws-sm://us-east-1/db/missing";
+        assert_eq!(extract(secret, Some("username"), "db", uri).unwrap(), "app");
+        assert_eq!(extract(secret, Some("port"), "db", uri).unwrap(), "5432");
+        assert_eq!(extract(secret, None, "db", uri).unwrap(), secret);
+        let error = extract(secret, Some("missing"), "db", uri).unwrap_err();
+        assert!(error.to_string().contains("Key missing not found"));
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "not_found");
+        assert!(extract("plain", Some("key"), "db", uri).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_whole_secret_and_json_keys() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "aws",
+            r#"case "$6" in
+    prod/db) echo '{"Name":"prod/db","SecretString":"{\"username\":\"app\",\"password\":\"s3cret\"}"}' ;;
+    token) echo '{"Name":"token","SecretString":"raw_token"}' ;;
+    *) echo "unexpected secret id $6" >&2; exit 1 ;;
+esac"#,
+        );
+        let mut p = AwsSecretsManager::new();
+        p.add("aws-sm://eu-west-1/prod%2Fdb/username".to_string())
+            .unwrap();
+        p.add("aws-sm://eu-west-1/prod%2Fdb/password".to_string())
+            .unwrap();
+        p.add("aws-sm://eu-west-1/token".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result.get("aws-sm://eu-west-1/prod%2Fdb/username").unwrap(),
+            "app"
+        );
+        assert_eq!(
+            result.get("aws-sm://eu-west-1/prod%2Fdb/password").unwrap(),
+            "s3cret"
+        );
+        assert_eq!(result.get("aws-sm://eu-west-1/token").unwrap(), "raw_token");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_one_call_per_secret() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        fake_cli(
+            &fake_bin,
+            "aws",
+            &format!(
+                r#"echo "$4 $6" >> {}
+echo '{{"SecretString":"{{\"a\":\"1\",\"b\":\"2\"}}"}}'"#,
+                calls.display()
+            ),
+        );
+        let mut p = AwsSecretsManager::new();
+        p.add("aws-sm://us-east-1/app/a".to_string()).unwrap();
+        p.add("aws-sm://us-east-1/app/b".to_string()).unwrap();
+        p.add("aws-sm://eu-west-1/app/a".to_string()).unwrap();
+        p.resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        let calls = std::fs::read_to_string(calls).unwrap();
+        let mut calls = calls.lines().collect::<Vec<_>>();
+        calls.sort();
+        assert_eq!(calls, vec!["eu-west-1 app", "us-east-1 app"]);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_failure_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "aws",
+            "echo 'An error occurred (ResourceNotFoundException)' >&2\nexit 254",
+        );
+        let mut p = AwsSecretsManager::new();
+        p.add("aws-sm://us-east-1/missing".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("AWS Secrets Manager error"));
+        assert!(err.contains("ResourceNotFoundException"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = AwsSecretsManager::new();
+        p.add("aws-sm://us-east-1/my-secret".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("AWS Secrets Manager CLI not found")
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Ok, Result, bail};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use itertools::Itertools;
+use log::debug;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, add_url, deserialize_output, run_cli};
+
+/// Maximum number of names accepted by a single `ssm get-parameters` call.
+const BATCH_SIZE: usize = 10;
+
+#[derive(Default)]
+pub struct AwsSsm {
+    urls: FxHashMap<Url, String>,
+}
+
+impl AwsSsm {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SsmParameter {
+    name: String,
+    value: String,
+    selector: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SsmParameters {
+    parameters: Vec<SsmParameter>,
+}
+
+/// The name passed to `get-parameters`, including a `:version` selector when
+/// `?version=` is set.
+fn parameter_name(url: &Url) -> Result<String> {
+    let invalid = |message: &str| {
+        Error::invalid_uri(url.as_str(), format!("{message}: {}", redact(url.as_str())))
+    };
+    let path = urlencoding::decode(url.path())
+        .map_err(|_| invalid("SSM parameter path is not valid UTF-8"))?;
+    if path.trim_matches('/').is_empty() {
+        return Err(invalid("SSM URI is missing a parameter path").into());
+    }
+    match url.query_pairs().find(|(k, _)| k == "version") {
+        Some((_, version)) if !version.is_empty() => Ok(format!("{path}:{version}")),
+        _ => Ok(path.into_owned()),
+    }
+}
+
+#[async_trait]
+impl Provider for AwsSsm {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "ssm")
+    }
+
+    fn name(&self) -> &'static str {
+        "AWS SSM Parameter Store"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://docs.aws.amazon.com/cli/latest/userguide/getting-started-install.html"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        let fetches = self
+            .urls
+            .iter()
+            .into_group_map_by(|(url, _)| url.host_str().unwrap_or_default().to_string())
+            .into_iter()
+            .map(|(region, group)| {
+                let extra_env = Arc::clone(&extra_env);
+                async move {
+                    if region.is_empty() {
+                        let value = group[0].1;
+                        return Err(Error::invalid_uri(
+                            value,
+                            format!("SSM URI is missing a region: {}", redact(value)),
+                        )
+                        .into());
+                    }
+                    let region = region.as_str();
+                    let requested = group
+                        .into_iter()
+                        .map(|(url, value)| Ok((parameter_name(url)?, value.clone())))
+                        .collect::<Result<Vec<_>>>()?;
+                    let names = requested
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<BTreeSet<_>>()
+                        .into_iter()
+                        .collect::<Vec<_>>();
+
+                    let calls = names.chunks(BATCH_SIZE).map(|batch| {
+                        let mut cmd = vec!["aws", "ssm", "get-parameters", "--region", region];
+                        cmd.push("--names");
+                        cmd.extend(batch.iter().copied());
+                        cmd.extend(["--with-decryption", "--output", "json"]);
+                        let extra_env = Arc::clone(&extra_env);
+                        async move {
+                            debug!("Lade run: {}", cmd.join(" "));
+                            let child = run_cli(&cmd, &extra_env, name, install_url, None).await?;
+                            if !child.status.success() {
+                                bail!(
+                                    "{name} error in {region}: {}",
+                                    String::from_utf8_lossy(&child.stderr).trim()
+                                );
+                            }
+                            // `InvalidParameters` are left out and reported per URI below.
+                            let loaded: SsmParameters = deserialize_output(&child, name)?;
+                            Ok(loaded
+                                .parameters
+                                .into_iter()
+                                .map(|p| {
+                                    (
+                                        format!("{}{}", p.name, p.selector.unwrap_or_default()),
+                                        p.value,
+                                    )
+                                })
+                                .collect::<Vec<_>>())
+                        }
+                    });
+                    let loaded = try_join_all(calls)
+                        .await?
+                        .into_iter()
+                        .flatten()
+                        .collect::<HashMap<_, _>>();
+
+                    let hydration = requested
+                        .into_iter()
+                        .map(|(parameter, value)| match loaded.get(&parameter) {
+                            Some(resolved) => Ok((value, resolved.clone())),
+                            None => Err(Error::not_found(
+                                &value,
+                                format!("Parameter {parameter} not found in {name} ({region})"),
+                            )
+                            .into()),
+                        })
+                        .collect::<Result<Hydration>>()?;
+                    debug!("hydration: {:?}", hydration);
+                    Ok(hydration)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    #[test]
+    fn test_add_routing() {
+        let mut p = AwsSsm::new();
+        assert!(
+            p.add("ssm://us-east-1/prod/db/password".to_string())
+                .is_ok()
+        );
+        assert!(p.add("aws-sm://us-east-1/secret".to_string()).is_err());
+        assert!(p.add("plainvalue".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parameter_name_with_version() {
+        let url = Url::parse("ssm://us-east-1/prod/db/password?version=3").unwrap();
+        assert_eq!(parameter_name(&url).unwrap(), "/prod/db/password:3");
+        let url = Url::parse("ssm://us-east-1/prod/db/password").unwrap();
+        assert_eq!(parameter_name(&url).unwrap(), "/prod/db/password");
+        let url = Url::parse("ssm://us-east-1/").unwrap();
+        assert!(parameter_name(&url).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_batches_region_into_one_call() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        fake_cli(
+            &fake_bin,
+            "aws",
+            &format!(
+                r#"echo "$@" >> {}
+echo '{{"Parameters":[
+  {{"Name":"/app/user","Value":"admin","Version":1}},
+  {{"Name":"/app/pass","Value":"s3cret","Version":2,"Selector":":2"}}
+],"InvalidParameters":[]}}'"#,
+                calls.display()
+            ),
+        );
+        let mut p = AwsSsm::new();
+        p.add("ssm://us-east-1/app/user".to_string()).unwrap();
+        p.add("ssm://us-east-1/app/pass?version=2".to_string())
+            .unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get("ssm://us-east-1/app/user").unwrap(), "admin");
+        assert_eq!(
+            result.get("ssm://us-east-1/app/pass?version=2").unwrap(),
+            "s3cret"
+        );
+        let calls = std::fs::read_to_string(calls).unwrap();
+        assert_eq!(
+            calls.trim(),
+            "ssm get-parameters --region us-east-1 --names /app/pass:2 /app/user --with-decryption --output json"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_splits_batches_at_api_limit() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        fake_cli(
+            &fake_bin,
+            "aws",
+            &format!(
+                r#"shift 5
+echo "$#" >> {}
+printf '{{"Parameters":['
+sep=''
+for n in "$@"; do
+    [ "$n" = "--with-decryption" ] && break
+    printf '%s{{"Name":"%s","Value":"v%s"}}' "$sep" "$n" "$n"
+    sep=','
+done
+printf ']}}'"#,
+                calls.display()
+            ),
+        );
+        let mut p = AwsSsm::new();
+        for i in 0..12 {
+            p.add(format!("ssm://us-east-1/p{i:02}")).unwrap();
+        }
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 12);
+        assert_eq!(result.get("ssm://us-east-1/p07").unwrap(), "v/p07");
+        let calls = std::fs::read_to_string(calls).unwrap();
+        let mut calls = calls.lines().collect::<Vec<_>>();
+        calls.sort();
+        // Each count is the batch plus the three trailing flags.
+        assert_eq!(calls, vec!["13", "5"]);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_invalid_parameter_error() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "aws",
+            r#"echo '{"Parameters":[],"InvalidParameters":["/app/missing"]}'"#,
+        );
+        let mut p = AwsSsm::new();
+        p.add("ssm://us-east-1/app/missing".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("/app/missing not found"));
+        let err = err.downcast_ref::<Error>().unwrap();
+        assert_eq!(err.kind(), "not_found");
+        assert_eq!(err.uri(), Some("ssm://us-east-1/app/missing"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = AwsSsm::new();
+        p.add("ssm://us-east-1/app/user".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("AWS SSM Parameter Store CLI not found")
+        );
+    }
+}
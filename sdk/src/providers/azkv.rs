@@ -0,0 +1,284 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Ok, Result, bail};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use log::debug;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, add_url, deserialize_output, run_cli};
+
+#[derive(Default)]
+pub struct AzureKeyVault {
+    urls: FxHashMap<Url, String>,
+}
+
+impl AzureKeyVault {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Deserialize)]
+struct AzureSecret {
+    value: String,
+}
+
+/// `(vault, secret, version)` for `azkv://<vault-name>/<secret-name>[/<version>]`.
+fn target(url: &Url) -> Result<(String, String, Option<String>)> {
+    let vault = url.host_str().unwrap_or_default();
+    let mut segments = url.path().split('/').skip(1);
+    let secret = segments.next().unwrap_or_default();
+    let version = segments.next().filter(|v| !v.is_empty());
+    if vault.is_empty() || secret.is_empty() || segments.next().is_some() {
+        return Err(Error::invalid_uri(
+            url.as_str(),
+            format!(
+                "Azure Key Vault URI must be azkv://VAULT/SECRET[/VERSION]: {}",
+                redact(url.as_str())
+            ),
+        )
+        .into());
+    }
+    Ok((
+        vault.to_string(),
+        secret.to_string(),
+        version.map(str::to_string),
+    ))
+}
+
+#[async_trait]
+impl Provider for AzureKeyVault {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "azkv")
+    }
+
+    fn name(&self) -> &'static str {
+        "Azure Key Vault"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://learn.microsoft.com/cli/azure/install-azure-cli"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        // Key Vault throttles per vault, so each vault gets a single fetch
+        // loop while different vaults still resolve concurrently.
+        let mut by_vault =
+            BTreeMap::<String, BTreeMap<(String, Option<String>), Vec<String>>>::new();
+        for (url, value) in &self.urls {
+            let (vault, secret, version) = target(url)?;
+            by_vault
+                .entry(vault)
+                .or_default()
+                .entry((secret, version))
+                .or_default()
+                .push(value.clone());
+        }
+        let fetches = by_vault
+            .into_iter()
+            .map(|(vault, secrets)| {
+                let extra_env = Arc::clone(&extra_env);
+                async move {
+                    let mut hydration = Hydration::default();
+                    for ((secret, version), values) in secrets {
+                        let mut cmd = vec![
+                            "az",
+                            "keyvault",
+                            "secret",
+                            "show",
+                            "--vault-name",
+                            &vault,
+                            "--name",
+                            &secret,
+                        ];
+                        if let Some(version) = &version {
+                            cmd.extend(["--version", version]);
+                        }
+                        cmd.extend(["--output", "json"]);
+                        debug!("Lade run: {}", cmd.join(" "));
+                        let child = run_cli(&cmd, &extra_env, name, install_url, None).await?;
+                        if !child.status.success() {
+                            let stderr = String::from_utf8_lossy(&child.stderr);
+                            let message =
+                                format!("{name} error for {vault}/{secret}: {}", stderr.trim());
+                            if stderr.contains("SecretNotFound") {
+                                return Err(Error::not_found(&values[0], message).into());
+                            }
+                            bail!(message);
+                        }
+                        let loaded: AzureSecret = deserialize_output(&child, name)?;
+                        for value in values {
+                            hydration.insert(value, loaded.value.clone());
+                        }
+                    }
+                    debug!("hydration: {:?}", hydration);
+                    Ok(hydration)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    #[test]
+    fn test_add_routing() {
+        let mut p = AzureKeyVault::new();
+        assert!(p.add("azkv://my-vault/db-password".to_string()).is_ok());
+        assert!(
+            p.add("azkv://my-vault/db-password/abc123".to_string())
+                .is_ok()
+        );
+        assert!(p.add("gcp-sm://proj/secret".to_string()).is_err());
+        assert!(p.add("plainvalue".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_target_with_and_without_version() {
+        let url = Url::parse("azkv://vault/secret").unwrap();
+        assert_eq!(
+            target(&url).unwrap(),
+            ("vault".into(), "secret".into(), None)
+        );
+        let url = Url::parse("azkv://vault/secret/v1").unwrap();
+        assert_eq!(target(&url).unwrap().2, Some("v1".into()));
+        assert!(target(&Url::parse("azkv://vault/").unwrap()).is_err());
+        assert!(target(&Url::parse("azkv://vault/a/b/c").unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_single_secret() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "az",
+            r#"echo '{"id":"https://v.vault.azure.net/secrets/s/1","value":"azure_value"}'"#,
+        );
+        let mut p = AzureKeyVault::new();
+        p.add("azkv://my-vault/db-password".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result.get("azkv://my-vault/db-password").unwrap(),
+            "azure_value"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_passes_vault_name_and_version() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(&fake_bin, "az", r#"printf '{"value":"%s"}' "$*""#);
+        let mut p = AzureKeyVault::new();
+        p.add("azkv://vault-a/token".to_string()).unwrap();
+        p.add("azkv://vault-b/token/v2".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result.get("azkv://vault-a/token").unwrap(),
+            "keyvault secret show --vault-name vault-a --name token --output json"
+        );
+        assert_eq!(
+            result.get("azkv://vault-b/token/v2").unwrap(),
+            "keyvault secret show --vault-name vault-b --name token --version v2 --output json"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_failure_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "az",
+            "echo '(SecretNotFound) A secret with (name/id) missing was not found' >&2\nexit 3",
+        );
+        let mut p = AzureKeyVault::new();
+        p.add("azkv://my-vault/missing".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Azure Key Vault error for my-vault/missing")
+        );
+        assert!(err.to_string().contains("SecretNotFound"));
+        assert_eq!(err.downcast_ref::<Error>().unwrap().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_malformed_json_error() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(&fake_bin, "az", "echo 'not valid json'");
+        let mut p = AzureKeyVault::new();
+        p.add("azkv://my-vault/db-password".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Azure Key Vault error")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = AzureKeyVault::new();
+        p.add("azkv://my-vault/db-password".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Azure Key Vault CLI not found")
+        );
+    }
+}
@@ -0,0 +1,470 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::{Ok, Result, bail};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use itertools::Itertools;
+use log::debug;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, add_url, deserialize_output, run_cli};
+
+/// Runs a `bw`/`bws` command for `uri`, whose missing item (or TOTP seed) is
+/// reported as not found.
+async fn run(
+    cmd: &[&str],
+    uri: &str,
+    extra_env: &HashMap<String, String>,
+    name: &str,
+    install_url: &str,
+) -> Result<std::process::Output> {
+    debug!("Lade run: {}", cmd.join(" "));
+    let child = run_cli(cmd, extra_env, name, install_url, None).await?;
+    if !child.status.success() {
+        let stderr = String::from_utf8_lossy(&child.stderr);
+        let message = format!("{name} error: {}", stderr.trim());
+        let lower = stderr.to_lowercase();
+        if lower.contains("not found") || lower.contains("no totp available") {
+            return Err(Error::not_found(uri, message).into());
+        }
+        bail!(message);
+    }
+    Ok(child)
+}
+
+async fn run_json<T: serde::de::DeserializeOwned>(
+    cmd: &[&str],
+    uri: &str,
+    extra_env: &HashMap<String, String>,
+    name: &str,
+    install_url: &str,
+) -> Result<T> {
+    let child = run(cmd, uri, extra_env, name, install_url).await?;
+    deserialize_output(&child, name)
+}
+
+/// Bitwarden Secrets Manager: `bws://<secret-id>` or
+/// `bws://<project-id>/<secret-key>`. The access token comes from
+/// `BWS_ACCESS_TOKEN`, which a rule can resolve with `bitwarden_access_token`.
+#[derive(Default)]
+pub struct BitwardenSecrets {
+    urls: FxHashMap<Url, String>,
+}
+
+impl BitwardenSecrets {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Deserialize)]
+struct BwsSecret {
+    key: String,
+    value: String,
+}
+
+#[async_trait]
+impl Provider for BitwardenSecrets {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "bws")
+    }
+
+    fn name(&self) -> &'static str {
+        "Bitwarden Secrets Manager"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://bitwarden.com/help/secrets-manager-cli/"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        let fetches = self
+            .urls
+            .iter()
+            .into_group_map_by(|(url, _)| url.host_str().unwrap_or_default().to_string())
+            .into_iter()
+            .map(|(id, group)| {
+                let extra_env = Arc::clone(&extra_env);
+                async move {
+                    let first = group[0].1;
+                    if id.is_empty() {
+                        return Err(Error::invalid_uri(
+                            first,
+                            format!(
+                                "{name} URI is missing a secret or project id: {}",
+                                redact(first)
+                            ),
+                        )
+                        .into());
+                    }
+                    let (by_secret, by_project): (Vec<_>, Vec<_>) = group
+                        .into_iter()
+                        .partition(|(url, _)| url.path().trim_matches('/').is_empty());
+                    let mut hydration = Hydration::default();
+                    if !by_secret.is_empty() {
+                        let cmd = ["bws", "secret", "get", &id, "--output", "json"];
+                        let secret: BwsSecret =
+                            run_json(&cmd, first, &extra_env, name, install_url).await?;
+                        for (_, value) in by_secret {
+                            hydration.insert(value.clone(), secret.value.clone());
+                        }
+                    }
+                    if !by_project.is_empty() {
+                        let cmd = ["bws", "secret", "list", &id, "--output", "json"];
+                        let secrets: Vec<BwsSecret> =
+                            run_json(&cmd, first, &extra_env, name, install_url).await?;
+                        for (url, value) in by_project {
+                            let key = urlencoding::decode(url.path().trim_start_matches('/'))
+                                .map_err(|e| Error::invalid_uri(value, e.to_string()))?;
+                            let secret =
+                                secrets.iter().find(|s| s.key == key).ok_or_else(|| {
+                                    Error::not_found(
+                                        value,
+                                        format!("Secret {key} not found in {name} project {id}"),
+                                    )
+                                })?;
+                            hydration.insert(value.clone(), secret.value.clone());
+                        }
+                    }
+                    debug!("hydration: {:?}", hydration);
+                    Ok(hydration)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+/// Bitwarden password manager: `bw://<item>/<field>`. The unlocked vault
+/// session comes from `BW_SESSION`, which a rule can resolve with
+/// `bitwarden_session`. The `totp` field is the current code from
+/// `bw get totp`, not the stored seed.
+#[derive(Default)]
+pub struct Bitwarden {
+    urls: FxHashMap<Url, String>,
+}
+
+impl Bitwarden {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct BwLogin {
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BwField {
+    name: String,
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BwItem {
+    #[serde(default)]
+    login: Option<BwLogin>,
+    notes: Option<String>,
+    #[serde(default)]
+    fields: Vec<BwField>,
+}
+
+impl BwItem {
+    /// Login fields and notes first, then custom fields by name.
+    fn field(&self, field: &str) -> Option<String> {
+        let login = self.login.as_ref();
+        match field {
+            "username" => login.and_then(|l| l.username.clone()),
+            "password" => login.and_then(|l| l.password.clone()),
+            "notes" => self.notes.clone(),
+            _ => None,
+        }
+        .or_else(|| {
+            self.fields
+                .iter()
+                .find(|f| f.name == field)
+                .and_then(|f| f.value.clone())
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for Bitwarden {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "bw")
+    }
+
+    fn name(&self) -> &'static str {
+        "Bitwarden"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://bitwarden.com/help/cli/"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        let fetches = self
+            .urls
+            .iter()
+            .into_group_map_by(|(url, _)| url.host_str().unwrap_or_default().to_string())
+            .into_iter()
+            .map(|(item, group)| {
+                let extra_env = Arc::clone(&extra_env);
+                async move {
+                    let first = group[0].1;
+                    let invalid = || {
+                        Error::invalid_uri(
+                            first,
+                            format!("{name} URI must be bw://ITEM/FIELD: {}", redact(first)),
+                        )
+                    };
+                    let item = urlencoding::decode(&item)
+                        .map_err(|_| invalid())?
+                        .into_owned();
+                    if item.is_empty() {
+                        return Err(invalid().into());
+                    }
+                    let fields = group
+                        .into_iter()
+                        .map(|(url, value)| {
+                            let field = urlencoding::decode(url.path().trim_start_matches('/'))
+                                .map_err(|e| Error::invalid_uri(value, e.to_string()))?;
+                            Ok((field.into_owned(), value))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let (totps, fields): (Vec<_>, Vec<_>) =
+                        fields.into_iter().partition(|(field, _)| field == "totp");
+                    let mut hydration = Hydration::default();
+                    if let Some((_, uri)) = totps.first() {
+                        let cmd = ["bw", "get", "totp", &item, "--nointeraction"];
+                        let child = run(&cmd, uri, &extra_env, name, install_url).await?;
+                        let code = String::from_utf8_lossy(&child.stdout).trim().to_string();
+                        for (_, value) in totps {
+                            hydration.insert(value.clone(), code.clone());
+                        }
+                    }
+                    if fields.is_empty() {
+                        return Ok(hydration);
+                    }
+                    let cmd = ["bw", "get", "item", &item, "--nointeraction"];
+                    let loaded: BwItem =
+                        run_json(&cmd, first, &extra_env, name, install_url).await?;
+                    for (field, value) in fields {
+                        let resolved = loaded.field(&field).ok_or_else(|| {
+                            Error::not_found(
+                                value,
+                                format!("Field {field} not found in {name} item {item}"),
+                            )
+                        })?;
+                        hydration.insert(value.clone(), resolved);
+                    }
+                    debug!("hydration: {:?}", hydration);
+                    Ok(hydration)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    #[test]
+    fn test_add_routing() {
+        let mut bws = BitwardenSecrets::new();
+        assert!(
+            bws.add("bws://be8e0ad8-d545-4017-a55a-b02f014d4158".to_string())
+                .is_ok()
+        );
+        assert!(bws.add("bw://item/password".to_string()).is_err());
+        let mut bw = Bitwarden::new();
+        assert!(bw.add("bw://github/password".to_string()).is_ok());
+        assert!(bw.add("bws://id".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_bws_resolve_secret_and_project_key() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "bws",
+            r#"[ "$BWS_ACCESS_TOKEN" = "token" ] || { echo 'missing access token' >&2; exit 1; }
+case "$2" in
+    get) echo '{"id":"'"$3"'","key":"API_KEY","value":"by_id"}' ;;
+    list) echo '[{"key":"DB_PASSWORD","value":"db_value"},{"key":"OTHER","value":"x"}]' ;;
+esac"#,
+        );
+        let mut p = BitwardenSecrets::new();
+        p.add("bws://secret-1".to_string()).unwrap();
+        p.add("bws://project-1/DB_PASSWORD".to_string()).unwrap();
+        let mut env = path_env(&fake_bin);
+        env.insert("BWS_ACCESS_TOKEN".to_string(), "token".to_string());
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get("bws://secret-1").unwrap(), "by_id");
+        assert_eq!(
+            result.get("bws://project-1/DB_PASSWORD").unwrap(),
+            "db_value"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_bws_missing_key_and_cli_error() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(&fake_bin, "bws", "echo '[]'");
+        let mut p = BitwardenSecrets::new();
+        p.add("bws://project-1/MISSING".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MISSING not found"));
+        assert_eq!(err.downcast_ref::<Error>().unwrap().kind(), "not_found");
+
+        fake_cli(&fake_bin, "bws", "echo 'unauthorized' >&2\nexit 1");
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Bitwarden Secrets Manager error"));
+        assert!(err.downcast_ref::<Error>().is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_bw_resolve_login_and_custom_fields() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "bw",
+            r#"[ "$BW_SESSION" = "session" ] || { echo 'Vault is locked.' >&2; exit 1; }
+[ "$3" = "My Item" ] || { echo "Not found: $3" >&2; exit 1; }
+echo '{"login":{"username":"alice","password":"pw"},"notes":"n","fields":[{"name":"api key","value":"k"}]}'"#,
+        );
+        let mut p = Bitwarden::new();
+        p.add("bw://My%20Item/username".to_string()).unwrap();
+        p.add("bw://My%20Item/password".to_string()).unwrap();
+        p.add("bw://My%20Item/api%20key".to_string()).unwrap();
+        let mut env = path_env(&fake_bin);
+        env.insert("BW_SESSION".to_string(), "session".to_string());
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get("bw://My%20Item/username").unwrap(), "alice");
+        assert_eq!(result.get("bw://My%20Item/password").unwrap(), "pw");
+        assert_eq!(result.get("bw://My%20Item/api%20key").unwrap(), "k");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_bw_totp_is_the_current_code() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "bw",
+            r#"case "$2/$3" in
+    totp/github) echo '123456' ;;
+    totp/*) echo 'No TOTP available for this login.' >&2; exit 1 ;;
+    item/*) echo '{"login":{"username":"alice","totp":"JBSWY3DPEHPK3PXP"}}' ;;
+esac"#,
+        );
+        let mut p = Bitwarden::new();
+        p.add("bw://github/totp".to_string()).unwrap();
+        p.add("bw://github/username".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get("bw://github/totp").unwrap(), "123456");
+        assert_eq!(result.get("bw://github/username").unwrap(), "alice");
+
+        let mut p = Bitwarden::new();
+        p.add("bw://gitlab/totp".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<Error>().unwrap().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_bw_locked_vault_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(&fake_bin, "bw", "echo 'Vault is locked.' >&2\nexit 1");
+        let mut p = Bitwarden::new();
+        p.add("bw://github/password".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Bitwarden error: Vault is locked."));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = Bitwarden::new();
+        p.add("bw://github/password".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Bitwarden CLI not found")
+        );
+    }
+}
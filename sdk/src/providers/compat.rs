@@ -44,6 +44,72 @@ pub static CLI_SPECS: &[CliSpec] = &[
         version_args: &["--version"],
         min_version: "0.5.0",
     },
+    CliSpec {
+        scheme: "aws-sm",
+        bin: "aws",
+        version_args: &["--version"],
+        min_version: "2.0.0",
+    },
+    CliSpec {
+        scheme: "ssm",
+        bin: "aws",
+        version_args: &["--version"],
+        min_version: "2.0.0",
+    },
+    CliSpec {
+        scheme: "gcp-sm",
+        bin: "gcloud",
+        version_args: &["--version"],
+        min_version: "400.0.0",
+    },
+    CliSpec {
+        scheme: "azkv",
+        bin: "az",
+        version_args: &["--version"],
+        min_version: "2.40.0",
+    },
+    CliSpec {
+        scheme: "bws",
+        bin: "bws",
+        version_args: &["--version"],
+        min_version: "0.5.0",
+    },
+    CliSpec {
+        scheme: "bw",
+        bin: "bw",
+        version_args: &["--version"],
+        min_version: "2023.1.0",
+    },
+    CliSpec {
+        scheme: "pass",
+        bin: "pass",
+        version_args: &["version"],
+        min_version: "1.7.0",
+    },
+    CliSpec {
+        scheme: "gopass",
+        bin: "gopass",
+        version_args: &["--version"],
+        min_version: "1.12.0",
+    },
+    CliSpec {
+        scheme: "sops",
+        bin: "sops",
+        version_args: &["--version"],
+        min_version: "3.7.0",
+    },
+    CliSpec {
+        scheme: "keepass",
+        bin: "keepassxc-cli",
+        version_args: &["--version"],
+        min_version: "2.7.0",
+    },
+    CliSpec {
+        scheme: "k8s-secret",
+        bin: "kubectl",
+        version_args: &["version", "--client", "--output=json"],
+        min_version: "1.27.0",
+    },
 ];
 
 pub fn spec_for(scheme: &str) -> Option<&'static CliSpec> {
@@ -81,7 +147,7 @@ async fn detect_version(
 
 pub async fn check(schemes: &[String], extra_env: &HashMap<String, String>) -> Vec<CompatWarning> {
     let providers = Providers::new();
-    let targets: Vec<(&'static CliSpec, &'static str, &'static str)> = schemes
+    let targets: Vec<(&'static CliSpec, &str, &'static str)> = schemes
         .iter()
         .filter_map(|scheme| {
             let spec = spec_for(scheme)?;
@@ -141,6 +207,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_version_aws_format() {
+        assert_eq!(
+            parse_version("aws-cli/2.15.30 Python/3.11.8 Linux/6.5.0 exe/x86_64"),
+            Some(Version::new(2, 15, 30))
+        );
+    }
+
     #[test]
     fn test_parse_version_none() {
         assert_eq!(parse_version("no version here"), None);
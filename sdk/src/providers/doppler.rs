@@ -2,17 +2,22 @@ use std::{collections::HashMap, path::Path, sync::Arc};
 
 use rustc_hash::FxHashMap;
 
-use anyhow::{Ok, Result};
+use anyhow::{Ok, Result, bail};
 use async_trait::async_trait;
 use futures::future::try_join_all;
 use itertools::Itertools;
 use log::debug;
+use reqwest::Client;
 use serde::Deserialize;
+use serde_json::json;
 use url::Url;
 
-use crate::Hydration;
+use crate::{Error, Hydration};
 
-use super::{Provider, Warnings, add_url, deserialize_output, host_with_port, run_cli};
+use super::{
+    Provider, Warnings, add_url, api_base, deserialize_output, env_var, fetch_json, require_parts,
+    run_cli, run_cli_with_stdin,
+};
 
 #[derive(Default)]
 pub struct Doppler {
@@ -30,6 +35,46 @@ struct DopplerExport {
     computed: String,
 }
 
+/// The computed secrets of one config, from the API with `DOPPLER_TOKEN`
+/// (a service or personal token) or else from the CLI.
+async fn download(
+    base: &str,
+    project: &str,
+    config: &str,
+    extra_env: &HashMap<String, String>,
+    name: &str,
+    install_url: &str,
+) -> Result<HashMap<String, String>> {
+    if let Some(token) = env_var(extra_env, "DOPPLER_TOKEN") {
+        let url = Url::parse_with_params(
+            &format!("{base}/v3/configs/config/secrets/download"),
+            [("project", project), ("config", config), ("format", "json")],
+        )?;
+        debug!("Doppler GET {url}");
+        let request = Client::new().get(url).bearer_auth(token);
+        let loaded = fetch_json(request, name, &format!("{project}/{config}")).await?;
+        return Ok(serde_json::from_value(loaded)?);
+    }
+    let cmd = [
+        "doppler",
+        "--api-host",
+        base,
+        "secrets",
+        "--project",
+        project,
+        "--config",
+        config,
+        "--json",
+    ];
+    debug!("Lade run: {}", cmd.join(" "));
+    let child = run_cli(&cmd, extra_env, name, install_url, None).await?;
+    let loaded: HashMap<String, DopplerExport> = deserialize_output(&child, name)?;
+    Ok(loaded
+        .into_iter()
+        .map(|(key, export)| (key, export.computed))
+        .collect())
+}
+
 #[async_trait]
 impl Provider for Doppler {
     fn add(&mut self, value: String) -> Result<()> {
@@ -54,26 +99,30 @@ impl Provider for Doppler {
         extra_env: &HashMap<String, String>,
         _: &Warnings,
     ) -> Result<Hydration> {
+        require_parts(&self.urls, 3, "doppler://HOST/PROJECT/CONFIG/NAME")?;
         let extra_env = Arc::new(extra_env.clone());
         let name = self.name();
         let install_url = self.install_url();
         let fetches = self
             .urls
             .iter()
-            .into_group_map_by(|(url, _)| host_with_port(url))
+            .map(|(url, value)| Ok((api_base(url)?, (url, value))))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .into_group_map()
             .into_iter()
             .flat_map(|(host, group)| {
                 group
                     .into_iter()
                     .into_group_map_by(|(url, _)| {
-                        url.path().split('/').nth(1).expect("Missing project")
+                        url.path().split('/').nth(1).expect("project checked above")
                     })
                     .into_iter()
                     .flat_map(|(project, group)| {
                         group
                             .into_iter()
                             .into_group_map_by(|(url, _)| {
-                                url.path().split('/').nth(2).expect("Missing env")
+                                url.path().split('/').nth(2).expect("config checked above")
                             })
                             .into_iter()
                             .map(|(env, group)| {
@@ -81,7 +130,7 @@ impl Provider for Doppler {
                                     .iter()
                                     .map(|(u, value)| {
                                         (
-                                            u.path().split('/').nth(3).expect("Missing variable"),
+                                            u.path().split('/').nth(3).expect("name checked above"),
                                             (*value).clone(),
                                         )
                                     })
@@ -89,40 +138,28 @@ impl Provider for Doppler {
                                 let host = host.clone();
                                 let extra_env = Arc::clone(&extra_env);
                                 async move {
-                                    let cmd = [
-                                        "doppler",
-                                        "--api-host",
-                                        &format!("https://{}", host),
-                                        "secrets",
-                                        "--project",
+                                    let loaded = download(
+                                        &host,
                                         project,
-                                        "--config",
                                         env,
-                                        "--json",
-                                    ];
-                                    debug!("Lade run: {}", cmd.join(" "));
-                                    let child =
-                                        run_cli(&cmd, &extra_env, name, install_url, None).await?;
-                                    let loaded: HashMap<String, DopplerExport> =
-                                        deserialize_output(&child, name)?;
+                                        &extra_env,
+                                        name,
+                                        install_url,
+                                    )
+                                    .await?;
                                     let hydration = vars
                                         .into_iter()
                                         .map(|(key, value)| {
-                                            (
-                                                value,
-                                                loaded
-                                                    .get(key)
-                                                    .unwrap_or_else(|| {
-                                                        panic!(
-                                                            "Variable not found in Doppler: {}",
-                                                            key
-                                                        )
-                                                    })
-                                                    .computed
-                                                    .clone(),
-                                            )
+                                            let Some(computed) = loaded.get(key) else {
+                                                return Err(Error::not_found(
+                                                    &value,
+                                                    format!("Variable not found in Doppler: {key}"),
+                                                )
+                                                .into());
+                                            };
+                                            Ok((value, computed.clone()))
                                         })
-                                        .collect::<Hydration>();
+                                        .collect::<Result<Hydration>>()?;
                                     debug!("hydration: {:?}", hydration);
                                     Ok(hydration)
                                 }
@@ -135,12 +172,70 @@ impl Provider for Doppler {
 
         Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
     }
+
+    /// Sets one secret, through the API with `DOPPLER_TOKEN` like reads, or
+    /// else with the CLI.
+    async fn write(
+        &self,
+        uri: &str,
+        value: &str,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<()> {
+        let url = Url::parse(uri).map_err(|e| Error::invalid_uri(uri, e.to_string()))?;
+        let parts = url.path().split('/').skip(1).collect::<Vec<_>>();
+        let [project, config, secret] = parts.as_slice() else {
+            return Err(Error::invalid_uri(
+                uri,
+                "doppler URI must be doppler://HOST/PROJECT/CONFIG/NAME",
+            )
+            .into());
+        };
+        let base = api_base(&url)?;
+        let name = self.name();
+        if let Some(token) = env_var(extra_env, "DOPPLER_TOKEN") {
+            let url = format!("{base}/v3/configs/config/secrets");
+            debug!("Doppler POST {url}");
+            let body =
+                json!({ "project": project, "config": config, "secrets": { *secret: value } });
+            let request = Client::new()
+                .post(url)
+                .bearer_auth(token)
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+            fetch_json(request, name, &format!("{project}/{config}")).await?;
+            return Ok(());
+        }
+        // `doppler secrets set NAME` reads the value from its standard input.
+        let cmd = [
+            "doppler",
+            "--api-host",
+            &base,
+            "secrets",
+            "set",
+            secret,
+            "--project",
+            project,
+            "--config",
+            config,
+            "--silent",
+        ];
+        debug!("Lade run: {}", cmd.join(" "));
+        let child = run_cli_with_stdin(&cmd, value, extra_env, name, self.install_url()).await?;
+        if !child.status.success() {
+            bail!(
+                "{name} error for {project}/{config}: {}",
+                String::from_utf8_lossy(&child.stderr).trim()
+            );
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::providers::fake_cli;
+    use crate::providers::{fake_cli, stand_in};
     use std::path::Path;
     use tempfile::tempdir;
 
@@ -249,4 +344,95 @@ mod tests {
             .await;
         assert!(result.unwrap_err().to_string().contains("Doppler error"));
     }
+
+    #[tokio::test]
+    async fn test_resolve_with_token_uses_the_api_once_per_config() {
+        let (address, requests) = stand_in(vec![(
+            "GET /v3/configs/config/secrets/download?project=myproject&config=dev&format=json ",
+            200,
+            r#"{"KEY1":"val1","KEY2":"val2"}"#,
+        )])
+        .await;
+        let uri = |key: &str| format!("doppler://{address}/myproject/dev/{key}?tls=false");
+        // No CLI on the PATH: the token must be enough.
+        let empty_bin = tempdir().unwrap();
+        let mut env = path_env(&empty_bin);
+        env.insert("DOPPLER_TOKEN".to_string(), "dp.st.dev.token".to_string());
+
+        let mut p = Doppler::new();
+        p.add(uri("KEY1")).unwrap();
+        p.add(uri("KEY2")).unwrap();
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result[&uri("KEY1")], "val1");
+        assert_eq!(result[&uri("KEY2")], "val2");
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 1);
+            assert!(requests[0].contains("authorization: Bearer dp.st.dev.token"));
+        }
+
+        let mut p = Doppler::new();
+        p.add(uri("MISSING")).unwrap();
+        let error = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    async fn test_write_sends_the_value_on_stdin_or_to_the_api() {
+        let fake_bin = tempdir().unwrap();
+        let record = fake_bin.path().join("record");
+        fake_cli(
+            &fake_bin,
+            "doppler",
+            &format!(
+                "echo \"$@\" > {0}; read -r value; echo \"$value\" >> {0}",
+                record.display()
+            ),
+        );
+        Doppler::new()
+            .write(
+                "doppler://api.doppler.com/myproject/dev/API_KEY",
+                "s3cret",
+                Path::new("."),
+                &path_env(&fake_bin),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&record).unwrap(),
+            "--api-host https://api.doppler.com secrets set API_KEY --project myproject \
+             --config dev --silent\ns3cret\n"
+        );
+
+        let (address, requests) = stand_in(vec![(
+            "POST /v3/configs/config/secrets ",
+            200,
+            r#"{"secrets":{}}"#,
+        )])
+        .await;
+        let mut env = path_env(&tempdir().unwrap());
+        env.insert("DOPPLER_TOKEN".to_string(), "dp.st.dev.token".to_string());
+        Doppler::new()
+            .write(
+                &format!("doppler://{address}/myproject/dev/API_KEY?tls=false"),
+                "s3cret",
+                Path::new("."),
+                &env,
+            )
+            .await
+            .unwrap();
+        let request = requests.lock().unwrap().remove(0);
+        assert!(request.contains("authorization: Bearer dp.st.dev.token"));
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(body).unwrap(),
+            json!({"project": "myproject", "config": "dev", "secrets": {"API_KEY": "s3cret"}})
+        );
+    }
 }
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use url::form_urlencoded;
+
+use super::{Provider, Warnings};
+use crate::{Error, Hydration};
+
+/// `env://NAME[?default=VALUE]`: a variable of lade's own environment, so
+/// rules can rename what CI injects under vendor-specific names.
+#[derive(Default)]
+pub struct Env {
+    values: Vec<String>,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+/// The variable name and default of an `env://` URI. The name is taken as
+/// written: URL hosts would be lowercased.
+fn parse(value: &str) -> Result<(&str, Option<String>)> {
+    let rest = value.strip_prefix("env://").unwrap_or(value);
+    let (name, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(Error::invalid_uri(value, "env URI must be env://NAME[?default=VALUE]").into());
+    }
+    let mut default = None;
+    for (key, option) in form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "default" => default = Some(option.into_owned()),
+            other => {
+                return Err(
+                    Error::invalid_uri(value, format!("unknown env option ?{other}=")).into(),
+                );
+            }
+        }
+    }
+    Ok((name, default))
+}
+
+#[async_trait]
+impl Provider for Env {
+    fn add(&mut self, value: String) -> Result<()> {
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Environment"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://github.com/zifeo/lade#secrets"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        _: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        self.values
+            .iter()
+            .map(|value| {
+                let (name, default) = parse(value)?;
+                let resolved = match std::env::var(name) {
+                    Ok(resolved) => resolved,
+                    Err(_) => default.ok_or_else(|| {
+                        Error::not_found(value, format!("environment variable {name} is not set"))
+                    })?,
+                };
+                Ok((value.clone(), resolved))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn resolve(values: &[&str]) -> Result<Hydration> {
+        let mut p = Env::new();
+        for value in values {
+            p.add(value.to_string()).unwrap();
+        }
+        p.resolve(Path::new("."), &HashMap::new(), &Warnings::default())
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_resolve_keeps_name_case_and_defaults() {
+        let result = temp_env::async_with_vars(
+            [
+                ("LADE_TEST_GH_TOKEN", Some("t0ken")),
+                ("LADE_TEST_UNSET", None),
+            ],
+            resolve(&[
+                "env://LADE_TEST_GH_TOKEN",
+                "env://LADE_TEST_UNSET?default=fallback%20value",
+                "env://LADE_TEST_UNSET?default=",
+            ]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["env://LADE_TEST_GH_TOKEN"], "t0ken");
+        assert_eq!(
+            result["env://LADE_TEST_UNSET?default=fallback%20value"],
+            "fallback value"
+        );
+        assert_eq!(result["env://LADE_TEST_UNSET?default="], "");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_errors() {
+        let missing = temp_env::async_with_vars(
+            [("LADE_TEST_UNSET", None::<&str>)],
+            resolve(&["env://LADE_TEST_UNSET"]),
+        )
+        .await
+        .unwrap_err();
+        let missing = missing.downcast_ref::<Error>().unwrap();
+        assert_eq!(missing.kind(), "not_found");
+        assert_eq!(
+            missing.to_string(),
+            "environment variable LADE_TEST_UNSET is not set"
+        );
+
+        for invalid in ["env://", "env://1ABC", "env://A-B", "env://A?fallback=x"] {
+            let error = resolve(&[invalid]).await.unwrap_err();
+            assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "invalid_uri");
+        }
+    }
+}
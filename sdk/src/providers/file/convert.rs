@@ -27,9 +27,10 @@ pub fn toml2json(toml: toml::Value) -> Value {
     match toml {
         toml::Value::String(s) => Value::String(s),
         toml::Value::Integer(i) => Value::Number(i.into()),
-        toml::Value::Float(f) => {
-            Value::Number(serde_json::Number::from_f64(f).expect("nan not allowed"))
-        }
+        // JSON has no NaN or infinity, keep them as TOML spells them.
+        toml::Value::Float(f) => serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(toml::Value::Float(f).to_string())),
         toml::Value::Boolean(b) => Value::Bool(b),
         toml::Value::Array(arr) => Value::Array(arr.into_iter().map(toml2json).collect()),
         toml::Value::Table(table) => {
@@ -39,6 +40,28 @@ pub fn toml2json(toml: toml::Value) -> Value {
     }
 }
 
+/// `KEY=value` lines as written by `sops -d` for dotenv files. Comments,
+/// blank lines and an `export ` prefix are ignored, and matching outer quotes
+/// are stripped.
+pub fn dotenv2json(content: &str) -> Value {
+    let map = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), Value::String(value.to_string())))
+        })
+        .collect();
+    Value::Object(map)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,4 +166,20 @@ mod tests {
             panic!("expected Object");
         }
     }
+
+    #[test]
+    fn test_dotenv2json() {
+        let result = dotenv2json(
+            "# comment\n\nDB_USER=app\nexport DB_PASS=\"s3cret=1\"\nEMPTY=\nQUOTED='x'\n",
+        );
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "DB_USER": "app",
+                "DB_PASS": "s3cret=1",
+                "EMPTY": "",
+                "QUOTED": "x",
+            })
+        );
+    }
 }
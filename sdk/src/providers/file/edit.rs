@@ -0,0 +1,298 @@
+use serde_json::Value;
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+
+/// One step of a `?query=` path: `.field` or `[index]`.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Field(String),
+    Index(usize),
+}
+
+/// Splits a query the way `access_json` reads it, `None` when it is not a
+/// plain path.
+fn steps(query: &str) -> Option<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut rest = query;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let field = &after[..end];
+            if field.is_empty() || field.contains(char::is_whitespace) {
+                return None;
+            }
+            steps.push(Step::Field(field.to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let (index, after) = after.split_once(']')?;
+            steps.push(Step::Index(index.trim().parse().ok()?));
+            rest = after;
+        } else {
+            return None;
+        }
+    }
+    Some(steps)
+}
+
+/// A parsed document lade can set a string in: JSON, or YAML kept as YAML so
+/// its tags and non-string keys survive the rewrite.
+pub(super) trait Node: Sized {
+    fn table() -> Self;
+    fn string(value: &str) -> Self;
+    fn field(&mut self, key: &str) -> Option<&mut Self>;
+    fn item(&mut self, index: usize) -> Option<&mut Self>;
+}
+
+impl Node for Value {
+    fn table() -> Self {
+        Value::Object(Default::default())
+    }
+
+    fn string(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+
+    fn field(&mut self, key: &str) -> Option<&mut Self> {
+        if self.is_null() {
+            *self = Self::table();
+        }
+        Some(self.as_object_mut()?.entry(key).or_insert(Value::Null))
+    }
+
+    fn item(&mut self, index: usize) -> Option<&mut Self> {
+        self.as_array_mut()?.get_mut(index)
+    }
+}
+
+impl Node for serde_yaml::Value {
+    fn table() -> Self {
+        serde_yaml::Value::Mapping(Default::default())
+    }
+
+    fn string(value: &str) -> Self {
+        serde_yaml::Value::String(value.to_string())
+    }
+
+    fn field(&mut self, key: &str) -> Option<&mut Self> {
+        if self.is_null() {
+            *self = Self::table();
+        }
+        Some(
+            self.as_mapping_mut()?
+                .entry(key.into())
+                .or_insert(serde_yaml::Value::Null),
+        )
+    }
+
+    fn item(&mut self, index: usize) -> Option<&mut Self> {
+        self.as_sequence_mut()?.get_mut(index)
+    }
+}
+
+fn parse(query: &str) -> Result<Vec<Step>, String> {
+    let steps = steps(query).ok_or_else(|| format!("cannot write to query {query}"))?;
+    if steps.is_empty() {
+        return Err(format!("query {query} selects the whole file"));
+    }
+    Ok(steps)
+}
+
+/// Sets the value at `query` to `value`, adding the missing fields on the way.
+pub(super) fn set<N: Node>(document: &mut N, query: &str, value: &str) -> Result<(), String> {
+    let steps = parse(query)?;
+    let mut node = document;
+    for step in &steps {
+        node = match step {
+            Step::Field(key) => node.field(key),
+            Step::Index(index) => node.item(*index),
+        }
+        .ok_or_else(|| format!("no place for query {query} in the file"))?;
+    }
+    *node = N::string(value);
+    Ok(())
+}
+
+/// [`set`] for TOML, edited in place so comments, ordering and formatting
+/// outside the changed value are kept.
+pub(super) fn set_toml(document: &mut DocumentMut, query: &str, value: &str) -> Result<(), String> {
+    let steps = parse(query)?;
+    set_in_table(document.as_table_mut(), &steps, value)
+        .ok_or_else(|| format!("no place for query {query} in the file"))
+}
+
+fn set_in_table(table: &mut Table, steps: &[Step], value: &str) -> Option<()> {
+    let (Step::Field(key), rest) = steps.split_first()? else {
+        return None;
+    };
+    if rest.is_empty() {
+        match table.get_mut(key) {
+            Some(Item::Value(current)) => replace(current, value),
+            Some(item) => *item = toml_edit::value(value),
+            None => {
+                table.insert(key, toml_edit::value(value));
+            }
+        }
+        return Some(());
+    }
+    if !table.contains_key(key) {
+        let mut child = Table::new();
+        child.set_implicit(true);
+        table.insert(key, Item::Table(child));
+    }
+    match table.get_mut(key)? {
+        Item::Table(child) => set_in_table(child, rest, value),
+        Item::ArrayOfTables(tables) => match rest {
+            [Step::Index(index), rest @ ..] if !rest.is_empty() => {
+                set_in_table(tables.get_mut(*index)?, rest, value)
+            }
+            _ => None,
+        },
+        Item::Value(child) => set_in_value(child, rest, value),
+        Item::None => None,
+    }
+}
+
+fn set_in_inline(table: &mut InlineTable, steps: &[Step], value: &str) -> Option<()> {
+    let (Step::Field(key), rest) = steps.split_first()? else {
+        return None;
+    };
+    if rest.is_empty() {
+        match table.get_mut(key) {
+            Some(current) => replace(current, value),
+            None => {
+                // The space before `}` moves to the new last value.
+                let mut added = toml_edit::Value::from(value);
+                if let Some((_, last)) = table.iter_mut().last() {
+                    if let Some(suffix) = last.decor().suffix().cloned() {
+                        added.decor_mut().set_suffix(suffix);
+                    }
+                    last.decor_mut().set_suffix("");
+                }
+                table.insert(key, added);
+            }
+        }
+        return Some(());
+    }
+    if !table.contains_key(key) {
+        table.insert(key, InlineTable::new().into());
+    }
+    set_in_value(table.get_mut(key)?, rest, value)
+}
+
+fn set_in_value(current: &mut toml_edit::Value, steps: &[Step], value: &str) -> Option<()> {
+    match current {
+        toml_edit::Value::InlineTable(table) => set_in_inline(table, steps, value),
+        toml_edit::Value::Array(array) => match steps {
+            [Step::Index(index), rest @ ..] => {
+                let item = array.get_mut(*index)?;
+                if rest.is_empty() {
+                    replace(item, value);
+                    Some(())
+                } else {
+                    set_in_value(item, rest, value)
+                }
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Swaps in `value`, keeping the spacing and comment around the old one.
+fn replace(current: &mut toml_edit::Value, value: &str) {
+    let decor = current.decor().clone();
+    *current = value.into();
+    *current.decor_mut() = decor;
+}
+
+/// What re-emitting this YAML would drop, since it is rewritten from its
+/// parsed form. Errs on the side of refusing: a `#` or `&`/`*` token inside a
+/// quoted string counts too.
+pub(super) fn yaml_loses(content: &str) -> Option<&'static str> {
+    let tokens = content
+        .lines()
+        .flat_map(|line| line.split([' ', '\t', '[', '{', ',']))
+        .collect::<Vec<_>>();
+    if tokens.iter().any(|token| token.starts_with('#')) {
+        Some("comments")
+    } else if tokens
+        .iter()
+        .any(|token| token.len() > 1 && token.starts_with(['&', '*']))
+    {
+        Some("anchors")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_steps() {
+        assert_eq!(
+            steps(".db.hosts[1].password"),
+            Some(vec![
+                Step::Field("db".to_string()),
+                Step::Field("hosts".to_string()),
+                Step::Index(1),
+                Step::Field("password".to_string()),
+            ])
+        );
+        assert_eq!(steps(""), Some(vec![]));
+        for invalid in ["db", ".", ".a[x]", ".a[1", ".a b"] {
+            assert_eq!(steps(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_set_adds_missing_fields() {
+        let mut document = json!({"db": {"hosts": [{"name": "a"}, {"name": "b"}]}});
+        set(&mut document, ".db.hosts[1].password", "s3cret").unwrap();
+        set(&mut document, ".api.token", "t0ken").unwrap();
+        assert_eq!(
+            document,
+            json!({
+                "db": {"hosts": [{"name": "a"}, {"name": "b", "password": "s3cret"}]},
+                "api": {"token": "t0ken"},
+            })
+        );
+        assert!(set(&mut document, ".db.hosts[2].password", "x").is_err());
+        assert!(set(&mut document, ".db.hosts.password", "x").is_err());
+        assert!(set(&mut document, "", "x").is_err());
+    }
+
+    #[test]
+    fn test_set_toml_keeps_comments_and_layout() {
+        let mut document = "# shared settings\n[db]\nport = 5432 # default\nhosts = [{ name = \"a\" }, { name = \"b\" }]\n\n[[users]]\nname = \"x\"\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        set_toml(&mut document, ".db.port", "5433").unwrap();
+        set_toml(&mut document, ".db.hosts[1].password", "s3cret").unwrap();
+        set_toml(&mut document, ".users[0].token", "t0ken").unwrap();
+        set_toml(&mut document, ".api.key", "k3y").unwrap();
+        assert_eq!(
+            document.to_string(),
+            "# shared settings\n[db]\nport = \"5433\" # default\nhosts = [{ name = \"a\" }, { name = \"b\", password = \"s3cret\" }]\n\n[[users]]\nname = \"x\"\ntoken = \"t0ken\"\n\n[api]\nkey = \"k3y\"\n"
+        );
+        assert!(set_toml(&mut document, ".db.hosts[2].password", "x").is_err());
+        assert!(set_toml(&mut document, ".users.token", "x").is_err());
+        assert!(set_toml(&mut document, "", "x").is_err());
+    }
+
+    #[test]
+    fn test_yaml_loses() {
+        assert_eq!(yaml_loses("db:\n  password: a#b\n  host: x\n"), None);
+        assert_eq!(yaml_loses("# settings\ndb: {}\n"), Some("comments"));
+        assert_eq!(
+            yaml_loses("db:\n  port: 5432 # default\n"),
+            Some("comments")
+        );
+        assert_eq!(
+            yaml_loses("base: &base\n  host: x\nprod: *base\n"),
+            Some("anchors")
+        );
+        assert_eq!(yaml_loses("prod: [*base]\n"), Some("anchors"));
+    }
+}
@@ -1,13 +1,16 @@
 mod convert;
+mod edit;
 
 use std::{
     collections::HashMap,
+    io::Read,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
 use access_json::JSONQuery;
-use anyhow::{Result, bail};
+use age::armor::ArmoredReader;
+use anyhow::{Result, anyhow, bail};
 use async_trait::async_trait;
 use futures::future::try_join_all;
 use rustc_hash::FxHashMap;
@@ -16,19 +19,140 @@ use tokio::fs;
 use url::Url;
 
 use itertools::Itertools;
+use log::debug;
 
-use super::{Provider, Warnings};
-use crate::Hydration;
-use convert::{ini2json, toml2json};
+use super::{Provider, Warnings, run_cli};
+use crate::{Error, Hydration, error::redact};
+use convert::{dotenv2json, ini2json, toml2json};
 
-#[derive(Default)]
+/// Structured files queried with `?query=`. The `sops://` variant decrypts
+/// the file with `sops -d` first, and `age://` decrypts it in-process with the
+/// `?identity=` key file, before the plaintext is parsed the same way.
 pub struct File {
+    scheme: &'static str,
     urls: FxHashMap<Url, String>,
 }
 
 impl File {
     pub fn new() -> Self {
-        Default::default()
+        Self::with_scheme("file")
+    }
+
+    pub fn sops() -> Self {
+        Self::with_scheme("sops")
+    }
+
+    pub fn age() -> Self {
+        Self::with_scheme("age")
+    }
+
+    fn with_scheme(scheme: &'static str) -> Self {
+        Self {
+            scheme,
+            urls: FxHashMap::default(),
+        }
+    }
+
+    async fn read(
+        &self,
+        path: &Path,
+        uri: &str,
+        identity: Option<&Path>,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<String> {
+        match self.scheme {
+            "sops" => {
+                let path = path.to_string_lossy();
+                let cmd = ["sops", "-d", path.as_ref()];
+                debug!("Lade run: {}", cmd.join(" "));
+                let child = run_cli(&cmd, extra_env, self.name(), self.install_url(), None).await?;
+                if !child.status.success() {
+                    bail!(
+                        "{} error for {path}: {}",
+                        self.name(),
+                        String::from_utf8_lossy(&child.stderr).trim()
+                    );
+                }
+                Ok(String::from_utf8(child.stdout)?)
+            }
+            "age" => {
+                let Some(identity) = identity else {
+                    return Err(Error::invalid_uri(
+                        uri,
+                        format!(
+                            "age URI is missing ?identity=KEYFILE for {}",
+                            path.display()
+                        ),
+                    )
+                    .into());
+                };
+                let encrypted = fs::read(path).await.map_err(|e| {
+                    Error::not_found(uri, format!("cannot read file {}: {e}", path.display()))
+                })?;
+                decrypt_age(&encrypted, identity)
+            }
+            _ => Ok(fs::read_to_string(path).await.map_err(|e| {
+                Error::not_found(uri, format!("cannot read file {}: {e}", path.display()))
+            })?),
+        }
+    }
+
+    /// The file `value` points at, its query stripped and decoded.
+    fn path(&self, url: &Url, value: &str, cwd: &Path) -> Result<PathBuf> {
+        let prefix = format!("{}://", self.scheme);
+        let stripped = value
+            .replace(&prefix, "")
+            .replace(&format!("?{}", url.query().unwrap_or_default()), "");
+        let path = urlencoding::decode(&stripped).map_err(|_| {
+            Error::invalid_uri(
+                value,
+                format!("invalid percent-encoding in {}", redact(value)),
+            )
+        })?;
+        Ok(expand_path(&path, cwd))
+    }
+}
+
+fn decrypt_age(encrypted: &[u8], identity: &Path) -> Result<String> {
+    let identities = age::IdentityFile::from_file(identity.to_string_lossy().into_owned())
+        .map_err(|e| anyhow!("cannot read age identity {}: {e}", identity.display()))?
+        .into_identities()?;
+    let decryptor = age::Decryptor::new(ArmoredReader::new(encrypted))?;
+    let mut reader = decryptor.decrypt(identities.iter().map(|i| i.as_ref() as _))?;
+    let mut plaintext = String::new();
+    reader.read_to_string(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Resolves `~/` and `$HOME/` against the user's home and anything else
+/// against `cwd`.
+pub(super) fn expand_path(path: &str, cwd: &Path) -> PathBuf {
+    let user = directories::UserDirs::new().expect("cannot get HOME location");
+    if let Some(rest) = path.strip_prefix("~/") {
+        user.home_dir().join(rest)
+    } else if let Some(rest) = path.strip_prefix("$HOME/") {
+        user.home_dir().join(rest)
+    } else {
+        cwd.join(path)
+    }
+}
+
+/// The format comes from the extension, ignoring a trailing `.age`, so
+/// `prod.yaml.age` is parsed as YAML.
+fn file_format(path: &Path) -> Option<&str> {
+    let path = match path.extension() {
+        Some(ext) if ext == "age" => Path::new(path.file_stem().unwrap_or_default()),
+        _ => path,
+    };
+    if path.file_name().is_some_and(|name| name == ".env") {
+        return Some("env");
+    }
+    path.extension()?.to_str()
+}
+
+impl Default for File {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -37,22 +161,29 @@ impl Provider for File {
     fn add(&mut self, value: String) -> Result<()> {
         match Url::parse(&value) {
             Ok(url)
-                if url.scheme() == "file"
+                if url.scheme() == self.scheme
                     && url.query_pairs().into_iter().any(|(k, _v)| k == "query") =>
             {
                 self.urls.insert(url, value);
                 Ok(())
             }
-            _ => bail!("Not an file scheme or missing ?query=.field part"),
+            _ => bail!("Not a {} scheme or missing ?query=.field part", self.scheme),
         }
     }
 
     fn name(&self) -> &'static str {
-        "File"
+        match self.scheme {
+            "sops" => "SOPS",
+            "age" => "age",
+            _ => "File",
+        }
     }
 
     fn install_url(&self) -> &'static str {
-        "https://github.com/zifeo/lade#file-loader"
+        match self.scheme {
+            "sops" => "https://github.com/getsops/sops#download",
+            _ => "https://github.com/zifeo/lade#file-loader",
+        }
     }
 
     fn has_work(&self) -> bool {
@@ -62,50 +193,43 @@ impl Provider for File {
     async fn resolve(
         &self,
         cwd: &Path,
-        _: &HashMap<String, String>,
+        extra_env: &HashMap<String, String>,
         _: &Warnings,
     ) -> Result<Hydration> {
-        let fetches = self
+        let targets = self
             .urls
             .iter()
-            .into_group_map_by(|(raw_url, value)| {
-                let url = urlencoding::decode(
-                    &value
-                        .replace("file://", "")
-                        .replace(&format!("?{}", raw_url.query().unwrap()), ""),
-                )
-                .expect("invalid percent-encoding in file:// URL")
-                .into_owned();
-                let user = directories::UserDirs::new().expect("cannot get HOME location");
-
-                if url.starts_with("~/") {
-                    user.home_dir()
-                        .join(url.chars().skip(2).collect::<String>())
-                } else if url.starts_with("$HOME/") {
-                    user.home_dir()
-                        .join(url.chars().skip(6).collect::<String>())
-                } else {
-                    cwd.join(url)
-                }
+            .map(|(raw_url, value)| {
+                let path = self.path(raw_url, value, cwd)?;
+                let identity = raw_url
+                    .query_pairs()
+                    .find(|(k, _)| k == "identity")
+                    .map(|(_, v)| expand_path(&v, cwd));
+                Ok(((path, identity), (raw_url, value)))
             })
+            .collect::<Result<Vec<_>>>()?;
+        let fetches = targets
             .into_iter()
-            .map(|(mut path, group)| async move {
+            .into_group_map()
+            .into_iter()
+            .map(|((mut path, identity), group)| async move {
                 if !path.starts_with(PathBuf::from_str("/")?) {
                     path = cwd.join(path);
                 }
-                let format = path
-                    .extension()
-                    .expect("no file format found")
-                    .to_str()
-                    .expect("cannot get file format");
-                let str = fs::read_to_string(&path)
-                    .await
-                    .unwrap_or_else(|_| panic!("cannot read file {}", path.display()));
+                // File-level failures are reported against the first URI.
+                let uri = group[0].1;
+                let format = file_format(&path).ok_or_else(|| {
+                    Error::invalid_uri(uri, format!("no file format found for {}", path.display()))
+                })?;
+                let str = self
+                    .read(&path, uri, identity.as_deref(), extra_env)
+                    .await?;
                 let json = match format {
                     "yaml" | "yml" => serde_yaml::from_str::<Value>(&str)?,
                     "json" => serde_json::from_str::<Value>(&str)?,
                     "toml" => toml2json(toml::from_str::<toml::Value>(&str)?),
                     "ini" => ini2json(ini::Ini::load_from_str(&str)?),
+                    "env" => dotenv2json(&str),
                     _ => bail!("unsupported file format: {}", format),
                 };
 
@@ -118,19 +242,24 @@ impl Provider for File {
                             .find(|(k, _v)| k == "query")
                             .unwrap()
                             .1;
-                        let compiled = JSONQuery::parse(&query)
-                            .unwrap_or_else(|_| panic!("cannot compile query {}", query));
+                        let compiled = JSONQuery::parse(&query).map_err(|_| {
+                            Error::invalid_uri(value, format!("cannot compile query {query}"))
+                        })?;
                         let res = compiled
                             .execute(&json)
-                            .unwrap()
-                            .unwrap_or_else(|| panic!("no query result for {}", query));
+                            .map_err(|e| {
+                                Error::invalid_uri(value, format!("cannot run query {query}: {e}"))
+                            })?
+                            .ok_or_else(|| {
+                                Error::not_found(value, format!("no query result for {query}"))
+                            })?;
                         let output = match res {
                             Value::String(s) => s,
                             x => x.to_string(),
                         };
-                        (value.clone(), output)
+                        Ok((value.clone(), output))
                     })
-                    .collect::<Hydration>();
+                    .collect::<Result<Hydration>>()?;
 
                 Ok(hydration)
             })
@@ -138,11 +267,114 @@ impl Provider for File {
 
         Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
     }
+
+    /// Sets `?query=` in a JSON, YAML or TOML file to `value`, creating the
+    /// file and the missing fields. Comments and formatting are not kept.
+    async fn write(
+        &self,
+        uri: &str,
+        value: &str,
+        cwd: &Path,
+        _: &HashMap<String, String>,
+    ) -> Result<()> {
+        if self.scheme != "file" {
+            return Err(
+                Error::invalid_uri(uri, format!("{} cannot write secrets", self.name())).into(),
+            );
+        }
+        let url = Url::parse(uri).map_err(|e| Error::invalid_uri(uri, e.to_string()))?;
+        let query = url
+            .query_pairs()
+            .find(|(k, _)| k == "query")
+            .map(|(_, query)| query.into_owned())
+            .ok_or_else(|| Error::invalid_uri(uri, "file URI is missing ?query=.field"))?;
+        let path = self.path(&url, uri, cwd)?;
+        let existing = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => bail!("cannot read file {}: {e}", path.display()),
+        };
+        let invalid = |message| Error::invalid_uri(uri, message);
+        let content = match file_format(&path) {
+            Some("json") => {
+                let mut json = match existing.trim() {
+                    "" => Value::Null,
+                    content => serde_json::from_str::<Value>(content)?,
+                };
+                edit::set(&mut json, &query, value).map_err(invalid)?;
+                serde_json::to_string_pretty(&json)? + "\n"
+            }
+            Some("yaml" | "yml") => {
+                if let Some(lost) = edit::yaml_loses(&existing) {
+                    return Err(Error::provider(
+                        "file",
+                        format!(
+                            "cannot rewrite {} without losing its {lost}, set the value by hand",
+                            path.display()
+                        ),
+                    )
+                    .into());
+                }
+                let mut yaml = serde_yaml::from_str::<serde_yaml::Value>(&existing)?;
+                edit::set(&mut yaml, &query, value).map_err(invalid)?;
+                serde_yaml::to_string(&yaml)?
+            }
+            Some("toml") => {
+                let mut toml = existing.parse::<toml_edit::DocumentMut>()?;
+                edit::set_toml(&mut toml, &query, value).map_err(invalid)?;
+                toml.to_string()
+            }
+            format => {
+                return Err(Error::invalid_uri(
+                    uri,
+                    format!(
+                        "cannot write {} files, only JSON, YAML and TOML",
+                        format.unwrap_or("extensionless")
+                    ),
+                )
+                .into());
+            }
+        };
+        replace(&path, content.as_bytes()).map_err(|e| {
+            Error::provider("file", format!("cannot write file {}: {e}", path.display()))
+        })?;
+        Ok(())
+    }
+}
+
+/// Swaps `content` in for `path` through a synced sibling temp file, so a
+/// crash never leaves a half-written file. The file keeps its mode, and a new
+/// one is only readable by its owner.
+fn replace(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let permissions = std::fs::metadata(path).map(|metadata| metadata.permissions());
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&tmp).and_then(|mut file| {
+        if let Ok(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::fake_cli;
     use std::collections::HashMap;
     use tempfile::tempdir;
 
@@ -250,4 +482,242 @@ mod tests {
             "nested_pass"
         );
     }
+
+    #[tokio::test]
+    async fn test_resolve_dotenv_file() {
+        let dir = tempdir().unwrap();
+        assert_eq!(
+            resolve_file(&dir, "prod.env", "A=1\nTOKEN=dotenv_value\n", ".TOKEN").await,
+            "dotenv_value"
+        );
+    }
+
+    #[test]
+    fn test_sops_add_routing() {
+        let mut p = File::sops();
+        assert!(
+            p.add("sops://secrets/prod.yaml?query=.db.password".to_string())
+                .is_ok()
+        );
+        assert!(p.add("sops://secrets/prod.yaml".to_string()).is_err());
+        assert!(
+            p.add("file://secrets/prod.yaml?query=.db".to_string())
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_sops_decrypts_before_query() {
+        let dir = tempdir().unwrap();
+        let fake_bin = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("prod.yaml"),
+            "db: ENC[AES256_GCM,data:...]\n",
+        )
+        .unwrap();
+        fake_cli(
+            &fake_bin,
+            "sops",
+            r#"[ "$1" = "-d" ] || exit 2
+printf 'db:\n  password: decrypted\n'"#,
+        );
+        let url = "sops://prod.yaml?query=.db.password".to_string();
+        let mut p = File::sops();
+        p.add(url.clone()).unwrap();
+        assert!(p.masks_in_output());
+        let env = HashMap::from([(
+            "PATH".to_string(),
+            fake_bin.path().to_string_lossy().into_owned(),
+        )]);
+        let result = p
+            .resolve(dir.path(), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get(&url).unwrap(), "decrypted");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_sops_failure_reports_stderr() {
+        let dir = tempdir().unwrap();
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "sops",
+            "echo 'Failed to get the data key required to decrypt the SOPS file.' >&2\nexit 128",
+        );
+        let mut p = File::sops();
+        p.add("sops://prod.json?query=.key".to_string()).unwrap();
+        let env = HashMap::from([(
+            "PATH".to_string(),
+            fake_bin.path().to_string_lossy().into_owned(),
+        )]);
+        let err = p
+            .resolve(dir.path(), &env, &Warnings::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SOPS error"));
+        assert!(err.contains("Failed to get the data key"));
+    }
+
+    fn age_fixture(dir: &tempfile::TempDir, filename: &str, plaintext: &str) {
+        use age::secrecy::ExposeSecret;
+        let identity = age::x25519::Identity::generate();
+        std::fs::write(
+            dir.path().join("key.txt"),
+            identity.to_string().expose_secret(),
+        )
+        .unwrap();
+        let encrypted = age::encrypt(&identity.to_public(), plaintext.as_bytes()).unwrap();
+        std::fs::write(dir.path().join(filename), encrypted).unwrap();
+    }
+
+    #[test]
+    fn test_file_format_ignores_age_suffix() {
+        assert_eq!(file_format(Path::new("/s/prod.yaml.age")), Some("yaml"));
+        assert_eq!(file_format(Path::new("/s/prod.toml")), Some("toml"));
+        assert_eq!(file_format(Path::new("/s/.env.age")), Some("env"));
+        assert_eq!(file_format(Path::new("/s/Makefile")), None);
+    }
+
+    #[tokio::test]
+    async fn test_age_decrypts_before_query() {
+        let dir = tempdir().unwrap();
+        age_fixture(&dir, "prod.toml.age", "[db]\npassword = \"age_value\"\n");
+        let url = "age://prod.toml.age?identity=key.txt&query=.db.password".to_string();
+        let mut p = File::age();
+        p.add(url.clone()).unwrap();
+        assert!(p.masks_in_output());
+        let result = p
+            .resolve(dir.path(), &HashMap::new(), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get(&url).unwrap(), "age_value");
+    }
+
+    #[tokio::test]
+    async fn test_age_wrong_or_missing_identity() {
+        let dir = tempdir().unwrap();
+        age_fixture(&dir, "prod.json.age", r#"{"key":"v"}"#);
+        let mut p = File::age();
+        p.add("age://prod.json.age?query=.key".to_string()).unwrap();
+        let err = p
+            .resolve(dir.path(), &HashMap::new(), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("missing ?identity="));
+
+        let other = tempdir().unwrap();
+        age_fixture(&other, "unused.json.age", "{}");
+        let url = format!(
+            "age://prod.json.age?identity={}&query=.key",
+            other.path().join("key.txt").display()
+        );
+        let mut p = File::age();
+        p.add(url).unwrap();
+        assert!(
+            p.resolve(dir.path(), &HashMap::new(), &Warnings::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_then_read_back() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("config.toml"),
+            "[db]\nport = 5432\nsince = 2024-01-01\n",
+        )
+        .unwrap();
+        for (filename, query) in [
+            ("config.json", ".db.password"),
+            ("config.yaml", ".db.password"),
+            ("config.toml", ".db.password"),
+        ] {
+            let uri = format!("file://{filename}?query={query}");
+            File::new()
+                .write(&uri, "s3cret", dir.path(), &HashMap::new())
+                .await
+                .unwrap();
+            let mut p = File::new();
+            p.add(uri.clone()).unwrap();
+            let result = p
+                .resolve(dir.path(), &HashMap::new(), &Warnings::default())
+                .await
+                .unwrap();
+            assert_eq!(result[&uri], "s3cret", "{filename}");
+        }
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.toml")).unwrap(),
+            "[db]\nport = 5432\nsince = 2024-01-01\npassword = \"s3cret\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_refuses_yaml_it_cannot_keep() {
+        let dir = tempdir().unwrap();
+        let original = "db:\n  port: 5432 # default\n";
+        std::fs::write(dir.path().join("config.yaml"), original).unwrap();
+        let error = File::new()
+            .write(
+                "file://config.yaml?query=.db.password",
+                "s3cret",
+                dir.path(),
+                &HashMap::new(),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("without losing its comments"));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.yaml")).unwrap(),
+            original
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_rejects_other_formats_and_schemes() {
+        let dir = tempdir().unwrap();
+        for (p, uri) in [
+            (File::new(), "file://config.ini?query=.db.password"),
+            (File::new(), "file://.env?query=.PASSWORD"),
+            (File::new(), "file://config.json?query=db"),
+            (File::sops(), "sops://config.json?query=.db.password"),
+        ] {
+            let error = p
+                .write(uri, "s3cret", dir.path(), &HashMap::new())
+                .await
+                .unwrap_err();
+            let error = error.downcast_ref::<Error>().unwrap();
+            assert_eq!(error.kind(), "invalid_uri", "{uri}");
+        }
+        assert!(!dir.path().join("config.json").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_keeps_mode_and_creates_private_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let shared = dir.path().join("shared.json");
+        std::fs::write(&shared, "{}").unwrap();
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o640)).unwrap();
+        for (filename, mode) in [("shared.json", 0o640), ("new.json", 0o600)] {
+            File::new()
+                .write(
+                    &format!("file://{filename}?query=.password"),
+                    "s3cret",
+                    dir.path(),
+                    &HashMap::new(),
+                )
+                .await
+                .unwrap();
+            let metadata = std::fs::metadata(dir.path().join(filename)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, mode, "{filename}");
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
 }
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::{Ok, Result, anyhow, bail};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::alphabet::URL_SAFE;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use futures::future::try_join_all;
+use itertools::Itertools;
+use log::debug;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, add_url, deserialize_output, run_cli};
+
+#[derive(Default)]
+pub struct GcpSecretManager {
+    urls: FxHashMap<Url, String>,
+}
+
+impl GcpSecretManager {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+/// gcloud prints `payload.data` in the URL-safe base64 alphabet.
+const PAYLOAD: GeneralPurpose = GeneralPurpose::new(
+    &URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[derive(Deserialize)]
+struct GcpPayload {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct GcpSecretVersion {
+    payload: GcpPayload,
+}
+
+/// `(project, secret, version)` for `gcp-sm://<project>/<secret>[/<version>]`.
+fn target(url: &Url) -> Result<(String, String, String)> {
+    let project = url.host_str().unwrap_or_default();
+    let mut segments = url.path().split('/').skip(1);
+    let secret = segments.next().unwrap_or_default();
+    let version = segments
+        .next()
+        .filter(|v| !v.is_empty())
+        .unwrap_or("latest");
+    if project.is_empty() || secret.is_empty() || segments.next().is_some() {
+        return Err(Error::invalid_uri(
+            url.as_str(),
+            format!(
+                "Google Cloud Secret Manager URI must be gcp-sm://PROJECT/SECRET[/VERSION]: {}",
+                redact(url.as_str())
+            ),
+        )
+        .into());
+    }
+    Ok((project.to_string(), secret.to_string(), version.to_string()))
+}
+
+#[async_trait]
+impl Provider for GcpSecretManager {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "gcp-sm")
+    }
+
+    fn name(&self) -> &'static str {
+        "Google Cloud Secret Manager"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://cloud.google.com/sdk/docs/install"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        let targets = self
+            .urls
+            .iter()
+            .map(|(url, value)| Ok((target(url)?, value.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        let fetches = targets
+            .into_iter()
+            .into_group_map()
+            .into_iter()
+            .map(|((project, secret, version), values)| {
+                let extra_env = Arc::clone(&extra_env);
+                async move {
+                    let cmd = [
+                        "gcloud",
+                        "secrets",
+                        "versions",
+                        "access",
+                        &version,
+                        &format!("--secret={secret}"),
+                        &format!("--project={project}"),
+                        "--format=json",
+                    ];
+                    debug!("Lade run: {}", cmd.join(" "));
+                    let child = run_cli(&cmd, &extra_env, name, install_url, None).await?;
+                    if !child.status.success() {
+                        let stderr = String::from_utf8_lossy(&child.stderr);
+                        let message = format!(
+                            "{name} error for {project}/{secret}/{version}: {}",
+                            stderr.trim()
+                        );
+                        if stderr.contains("NOT_FOUND") {
+                            return Err(Error::not_found(&values[0], message).into());
+                        }
+                        bail!(message);
+                    }
+                    let loaded: GcpSecretVersion = deserialize_output(&child, name)?;
+                    // Standard-alphabet payloads decode the same.
+                    let data = loaded.payload.data.replace('+', "-").replace('/', "_");
+                    let bytes = PAYLOAD
+                        .decode(data)
+                        .map_err(|e| anyhow!("{name} error: invalid payload encoding: {e}"))?;
+                    let resolved = String::from_utf8(bytes)
+                        .map_err(|_| anyhow!("{name} secret {project}/{secret} is not UTF-8"))?;
+                    let hydration = values
+                        .into_iter()
+                        .map(|value| (value, resolved.clone()))
+                        .collect::<Hydration>();
+                    debug!("hydration: {:?}", hydration);
+                    Ok(hydration)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    #[test]
+    fn test_add_routing() {
+        let mut p = GcpSecretManager::new();
+        assert!(p.add("gcp-sm://my-project/db-password".to_string()).is_ok());
+        assert!(
+            p.add("gcp-sm://my-project/db-password/3".to_string())
+                .is_ok()
+        );
+        assert!(p.add("aws-sm://us-east-1/secret".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_target_defaults_to_latest() {
+        let url = Url::parse("gcp-sm://proj/secret").unwrap();
+        assert_eq!(
+            target(&url).unwrap(),
+            ("proj".into(), "secret".into(), "latest".into())
+        );
+        let url = Url::parse("gcp-sm://proj/secret/7").unwrap();
+        assert_eq!(target(&url).unwrap().2, "7");
+        assert!(target(&Url::parse("gcp-sm://proj/").unwrap()).is_err());
+        assert!(target(&Url::parse("gcp-sm://proj/a/1/extra").unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_latest_and_pinned_versions() {
+        let fake_bin = tempdir().unwrap();
+        // "bGF0ZXN0" is base64 for "latest", "cGlubmVkPj4_" URL-safe base64 for
+        // "pinned>>?".
+        fake_cli(
+            &fake_bin,
+            "gcloud",
+            r#"case "$4" in
+    latest) echo '{"name":"projects/p/secrets/s/versions/9","payload":{"data":"bGF0ZXN0"}}' ;;
+    2) echo '{"name":"projects/p/secrets/s/versions/2","payload":{"data":"cGlubmVkPj4_"}}' ;;
+    *) echo "unexpected version $4" >&2; exit 1 ;;
+esac"#,
+        );
+        let mut p = GcpSecretManager::new();
+        p.add("gcp-sm://proj/api-key".to_string()).unwrap();
+        p.add("gcp-sm://proj/api-key/2".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get("gcp-sm://proj/api-key").unwrap(), "latest");
+        assert_eq!(result.get("gcp-sm://proj/api-key/2").unwrap(), "pinned>>?");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_failure_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "gcloud",
+            "echo 'ERROR: (gcloud.secrets.versions.access) NOT_FOUND' >&2\nexit 1",
+        );
+        let mut p = GcpSecretManager::new();
+        p.add("gcp-sm://proj/missing".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Google Cloud Secret Manager error")
+        );
+        assert!(err.to_string().contains("NOT_FOUND"));
+        assert_eq!(err.downcast_ref::<Error>().unwrap().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = GcpSecretManager::new();
+        p.add("gcp-sm://proj/api-key".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Google Cloud Secret Manager CLI not found")
+        );
+    }
+}
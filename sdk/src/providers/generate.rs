@@ -0,0 +1,255 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use url::form_urlencoded;
+
+use super::{Provider, Warnings};
+use crate::{Error, Hydration};
+
+const DEFAULT_LENGTH: usize = 32;
+const DEFAULT_BYTES: usize = 32;
+/// Bounds a typo like `length=32000000` to something reasonable.
+const MAX_LENGTH: usize = 4096;
+
+const DIGITS: &str = "0123456789";
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+/// `gen://password`, `gen://uuid` and `gen://hex`: a fresh random value per
+/// invocation, from the OS random source, for throwaway credentials.
+#[derive(Default)]
+pub struct Generate {
+    values: Vec<String>,
+}
+
+impl Generate {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+enum Kind {
+    Password { length: usize, charset: String },
+    Uuid,
+    Hex { bytes: usize },
+}
+
+fn parse(value: &str) -> Result<Kind> {
+    let rest = value.strip_prefix("gen://").unwrap_or(value);
+    let (kind, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let mut options = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect::<HashMap<_, _>>();
+    let kind = match kind {
+        "password" => Kind::Password {
+            length: size(value, &mut options, "length", DEFAULT_LENGTH)?,
+            charset: charset(value, options.remove("charset").as_deref())?,
+        },
+        "uuid" => Kind::Uuid,
+        "hex" => Kind::Hex {
+            bytes: size(value, &mut options, "bytes", DEFAULT_BYTES)?,
+        },
+        _ => {
+            return Err(Error::invalid_uri(
+                value,
+                "gen URI must be gen://password, gen://uuid or gen://hex",
+            )
+            .into());
+        }
+    };
+    if let Some(option) = options.keys().next() {
+        return Err(Error::invalid_uri(value, format!("unknown gen option ?{option}=")).into());
+    }
+    Ok(kind)
+}
+
+fn size(
+    value: &str,
+    options: &mut HashMap<String, String>,
+    key: &str,
+    default: usize,
+) -> Result<usize> {
+    let Some(size) = options.remove(key) else {
+        return Ok(default);
+    };
+    match size.parse::<usize>() {
+        Ok(size) if (1..=MAX_LENGTH).contains(&size) => Ok(size),
+        _ => Err(Error::invalid_uri(
+            value,
+            format!("gen ?{key}= must be between 1 and {MAX_LENGTH}"),
+        )
+        .into()),
+    }
+}
+
+fn charset(value: &str, name: Option<&str>) -> Result<String> {
+    Ok(match name.unwrap_or("alnum") {
+        "alnum" => format!("{DIGITS}{LOWER}{UPPER}"),
+        "alpha" => format!("{LOWER}{UPPER}"),
+        "digits" => DIGITS.to_string(),
+        "lower" => format!("{DIGITS}{LOWER}"),
+        "upper" => format!("{DIGITS}{UPPER}"),
+        "symbols" => format!("{DIGITS}{LOWER}{UPPER}{SYMBOLS}"),
+        other => {
+            return Err(Error::invalid_uri(
+                value,
+                format!(
+                    "unknown gen charset {other}, use alnum, alpha, digits, lower, upper or symbols"
+                ),
+            )
+            .into());
+        }
+    })
+}
+
+fn random_bytes(count: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0; count];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| Error::provider("gen", format!("no random source: {e}")))?;
+    Ok(bytes)
+}
+
+/// `length` characters of `charset`, each drawn uniformly: bytes that would
+/// favour the first characters are rejected.
+fn password(length: usize, charset: &str) -> Result<String> {
+    let charset = charset.as_bytes();
+    let limit = 256 - 256 % charset.len();
+    let mut password = String::with_capacity(length);
+    while password.len() < length {
+        for byte in random_bytes(length)? {
+            if (byte as usize) < limit && password.len() < length {
+                password.push(charset[byte as usize % charset.len()] as char);
+            }
+        }
+    }
+    Ok(password)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+/// A version 4 (random) UUID.
+fn uuid() -> Result<String> {
+    let mut bytes = random_bytes(16)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex(&bytes);
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+#[async_trait]
+impl Provider for Generate {
+    fn add(&mut self, value: String) -> Result<()> {
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Generator"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://github.com/zifeo/lade#secrets"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        _: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        self.values
+            .iter()
+            .map(|value| {
+                let generated = match parse(value)? {
+                    Kind::Password { length, charset } => password(length, &charset)?,
+                    Kind::Uuid => uuid()?,
+                    Kind::Hex { bytes } => hex(&random_bytes(bytes)?),
+                };
+                Ok((value.clone(), generated))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn resolve(values: &[&str]) -> Result<Hydration> {
+        let mut p = Generate::new();
+        for value in values {
+            p.add(value.to_string()).unwrap();
+        }
+        p.resolve(Path::new("."), &HashMap::new(), &Warnings::default())
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_resolve_shapes() {
+        let result = resolve(&[
+            "gen://password",
+            "gen://password?length=12&charset=digits",
+            "gen://uuid",
+            "gen://hex?bytes=4",
+        ])
+        .await
+        .unwrap();
+        let password = &result["gen://password"];
+        assert_eq!(password.len(), DEFAULT_LENGTH);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        let pin = &result["gen://password?length=12&charset=digits"];
+        assert_eq!(pin.len(), 12);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        let uuid = &result["gen://uuid"];
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!("89ab".contains(&uuid[19..20]));
+        let hex = &result["gen://hex?bytes=4"];
+        assert_eq!(hex.len(), 8);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[tokio::test]
+    async fn test_values_are_fresh() {
+        let first = resolve(&["gen://hex"]).await.unwrap();
+        let second = resolve(&["gen://hex"]).await.unwrap();
+        assert_ne!(first["gen://hex"], second["gen://hex"]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_rejects_invalid_uris() {
+        for invalid in [
+            "gen://pin",
+            "gen://password?length=0",
+            "gen://password?length=x",
+            "gen://password?charset=emoji",
+            "gen://uuid?length=3",
+            "gen://hex?bytes=100000",
+        ] {
+            let error = resolve(&[invalid]).await.unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<Error>().unwrap().kind(),
+                "invalid_uri",
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -1,17 +1,20 @@
-use anyhow::{Ok, Result, anyhow, bail};
+use anyhow::{Ok, Result, anyhow};
 use async_trait::async_trait;
 use futures::future::try_join_all;
 use itertools::Itertools;
 use log::debug;
+use reqwest::Client;
 use rustc_hash::FxHashMap;
 use serde::Deserialize;
+use serde_json::json;
 use std::{collections::HashMap, fs::File, io::Write, path::Path, sync::Arc};
 use tempfile::tempdir;
+use tokio::sync::OnceCell;
 use url::Url;
 
-use crate::Hydration;
+use crate::{Error, Hydration};
 
-use super::{Provider, Warnings, add_url, host_with_port, run_cli};
+use super::{Provider, Warnings, add_url, api_base, env_var, fetch_json, require_parts, run_cli};
 
 #[derive(Default)]
 pub struct Infisical {
@@ -30,6 +33,78 @@ struct InfisicalExport {
     value: String,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSecret {
+    secret_key: String,
+    secret_value: String,
+}
+
+/// An API token for `base`: `INFISICAL_TOKEN` as is, or a machine identity
+/// login with `INFISICAL_UNIVERSAL_AUTH_CLIENT_ID` and `_CLIENT_SECRET`.
+/// Without either, secrets come from the logged-in CLI.
+async fn access_token(
+    base: &str,
+    extra_env: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<String>> {
+    if let Some(token) = env_var(extra_env, "INFISICAL_TOKEN") {
+        return Ok(Some(token));
+    }
+    let (Some(client_id), Some(client_secret)) = (
+        env_var(extra_env, "INFISICAL_UNIVERSAL_AUTH_CLIENT_ID"),
+        env_var(extra_env, "INFISICAL_UNIVERSAL_AUTH_CLIENT_SECRET"),
+    ) else {
+        return Ok(None);
+    };
+    let url = format!("{base}/api/v1/auth/universal-auth/login");
+    debug!("Infisical POST {url}");
+    let request = Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(json!({ "clientId": client_id, "clientSecret": client_secret }).to_string());
+    let response = fetch_json(request, name, "universal auth login").await?;
+    response["accessToken"]
+        .as_str()
+        .map(|token| Some(token.to_string()))
+        .ok_or_else(|| anyhow!("Infisical error: no access token in universal auth login"))
+}
+
+/// The secrets of one folder, with imported ones after its own like the CLI
+/// export, and references expanded.
+async fn fetch_secrets(
+    base: &str,
+    token: &str,
+    project: &str,
+    env: &str,
+    path: &str,
+    name: &str,
+) -> Result<Vec<(String, String)>> {
+    let url = Url::parse_with_params(
+        &format!("{base}/api/v3/secrets/raw"),
+        [
+            ("workspaceId", project),
+            ("environment", env),
+            ("secretPath", path),
+            ("expandSecretReferences", "true"),
+            ("include_imports", "true"),
+        ],
+    )?;
+    debug!("Infisical GET {url}");
+    let request = Client::new().get(url).bearer_auth(token);
+    let response = fetch_json(request, name, &format!("{project}/{env}{path}")).await?;
+    let mut secrets = serde_json::from_value::<Vec<RawSecret>>(response["secrets"].clone())?;
+    for import in response["imports"].as_array().into_iter().flatten() {
+        secrets.extend(serde_json::from_value::<Vec<RawSecret>>(
+            import["secrets"].clone(),
+        )?);
+    }
+    Ok(secrets
+        .into_iter()
+        .map(|secret| (secret.secret_key, secret.secret_value))
+        .collect())
+}
+
 #[async_trait]
 impl Provider for Infisical {
     fn add(&mut self, value: String) -> Result<()> {
@@ -54,31 +129,37 @@ impl Provider for Infisical {
         extra_env: &HashMap<String, String>,
         _: &Warnings,
     ) -> Result<Hydration> {
+        require_parts(&self.urls, 3, "infisical://HOST/PROJECT/ENV/NAME")?;
         let extra_env = Arc::new(extra_env.clone());
         let name = self.name();
         let install_url = self.install_url();
         let fetches = self
             .urls
             .iter()
-            .into_group_map_by(|(url, _)| host_with_port(url))
+            .map(|(url, value)| Ok((api_base(url)?, (url, value))))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .into_group_map()
             .into_iter()
             .flat_map(|(host, group)| {
+                // One login per instance, shared by its projects.
+                let login = Arc::new(OnceCell::new());
                 group
                     .into_iter()
-                    .into_group_map_by(|(url, _)| url.path().split('/').nth(1).expect("Missing project"))
+                    .into_group_map_by(|(url, _)| url.path().split('/').nth(1).expect("project checked above"))
                     .into_iter()
                     .flat_map(|(project, group)| {
                         group
                             .into_iter()
-                            .into_group_map_by(|(url, _)| url.path().split('/').nth(2).expect("Missing env"))
+                            .into_group_map_by(|(url, _)| url.path().split('/').nth(2).expect("env checked above"))
                             .into_iter()
                             .map(|(env, group)| {
                                 let path_groups = group
                                     .iter()
                                     .map(|(url, value)| {
                                         let segs: Vec<&str> = url.path().split('/').collect();
-                                        let variable = urlencoding::decode(segs.last().expect("Missing variable"))
-                                            .expect("invalid percent-encoding in infisical:// URL")
+                                        let variable = urlencoding::decode(segs.last().expect("name checked above"))
+                                            .expect("encoding checked above")
                                             .into_owned();
                                         let path = if segs.len() > 4 {
                                             format!("/{}", segs[3..segs.len()-1].join("/"))
@@ -94,42 +175,60 @@ impl Provider for Infisical {
 
                                 let host = host.clone();
                                 let extra_env = Arc::clone(&extra_env);
+                                let login = Arc::clone(&login);
                                 async move {
-                                    let temp_dir = tempdir()?;
-                                    let config = HashMap::from([("workspaceId", project), ("defaultEnvironment", "")]);
-                                    let config_path = temp_dir.path().join(".infisical.json");
-                                    let mut file = File::create(config_path)?;
-                                    write!(file, "{}", serde_json::to_string(&config)?)?;
-                                    drop(file);
+                                    let token = login
+                                        .get_or_try_init(|| access_token(&host, &extra_env, name))
+                                        .await?
+                                        .clone();
+                                    // The CLI reads the project from a config file in its cwd.
+                                    let temp_dir = match token {
+                                        Some(_) => None,
+                                        None => {
+                                            let temp_dir = tempdir()?;
+                                            let config = HashMap::from([("workspaceId", project), ("defaultEnvironment", "")]);
+                                            let config_path = temp_dir.path().join(".infisical.json");
+                                            let mut file = File::create(config_path)?;
+                                            write!(file, "{}", serde_json::to_string(&config)?)?;
+                                            drop(file);
+                                            Some(temp_dir)
+                                        }
+                                    };
 
-                                    let temp_dir_path = Arc::new(temp_dir.path().to_path_buf());
+                                    let temp_dir_path = Arc::new(temp_dir.as_ref().map(|dir| dir.path().to_path_buf()));
                                     let path_futures = path_groups.into_iter().map(|(path, variables)| {
                                         let host = host.clone();
                                         let extra_env = Arc::clone(&extra_env);
                                         let temp_dir_path = Arc::clone(&temp_dir_path);
+                                        let token = token.clone();
                                         async move {
-                                            let domain = format!("https://{}/api", host);
                                             let path_arg = if path.is_empty() { "/".to_string() } else { path.clone() };
-                                            let cmd = [
-                                                "infisical", "--domain", &domain,
-                                                "export", "--path", &path_arg,
-                                                "--env", env, "--projectId", project, "--format", "json",
-                                            ];
-                                            let child = run_cli(&cmd, &extra_env, name, install_url, Some(&temp_dir_path)).await?;
-                                            let loaded = serde_json::from_slice::<Vec<InfisicalExport>>(&child.stdout)
-                                                .map_err(|err| {
-                                                    let stderr = String::from_utf8_lossy(&child.stderr);
-                                                    if stderr.contains("login expired") {
-                                                        anyhow!("Login expired for Infisical instance {host}: {stderr}")
-                                                    } else if stderr.contains("unable to validate environment") {
-                                                        anyhow!("Workspace seems not accessible from logged account on {host}: {stderr}")
-                                                    } else {
-                                                        anyhow!("Infisical error: {err} (stderr: {stderr})")
-                                                    }
-                                                })?
-                                                .into_iter()
-                                                .map(|e| (e.key, e.value))
-                                                .collect::<Vec<_>>();
+                                            let loaded = match &token {
+                                                Some(token) => fetch_secrets(&host, token, project, env, &path_arg, name).await?,
+                                                None => {
+                                                    let domain = format!("{host}/api");
+                                                    let cmd = [
+                                                        "infisical", "--domain", &domain,
+                                                        "export", "--path", &path_arg,
+                                                        "--env", env, "--projectId", project, "--format", "json",
+                                                    ];
+                                                    let child = run_cli(&cmd, &extra_env, name, install_url, temp_dir_path.as_deref()).await?;
+                                                    serde_json::from_slice::<Vec<InfisicalExport>>(&child.stdout)
+                                                        .map_err(|err| {
+                                                            let stderr = String::from_utf8_lossy(&child.stderr);
+                                                            if stderr.contains("login expired") {
+                                                                anyhow!("Login expired for Infisical instance {host}: {stderr}")
+                                                            } else if stderr.contains("unable to validate environment") {
+                                                                anyhow!("Workspace seems not accessible from logged account on {host}: {stderr}")
+                                                            } else {
+                                                                anyhow!("Infisical error: {err} (stderr: {stderr})")
+                                                            }
+                                                        })?
+                                                        .into_iter()
+                                                        .map(|e| (e.key, e.value))
+                                                        .collect::<Vec<_>>()
+                                                }
+                                            };
 
                                             let mut missing_vars = Vec::new();
                                             let mut partial = Hydration::default();
@@ -137,11 +236,12 @@ impl Provider for Infisical {
                                                 if let Some((_, value)) = loaded.iter().find(|(key, _)| key == &var_name) {
                                                     partial.insert(original_url, value.clone());
                                                 } else {
-                                                    missing_vars.push(var_name);
+                                                    missing_vars.push((var_name, original_url));
                                                 }
                                             }
-                                            if !missing_vars.is_empty() {
-                                                bail!("Variables {} not found in path {} of Infisical project {}", missing_vars.join(", "), path, project);
+                                            if let Some((_, first)) = missing_vars.first() {
+                                                let names = missing_vars.iter().map(|(name, _)| name.as_str()).join(", ");
+                                                return Err(Error::not_found(first, format!("Variables {names} not found in path {path} of Infisical project {project}")).into());
                                             }
                                             Ok(partial)
                                         }
@@ -153,7 +253,9 @@ impl Provider for Infisical {
                                         .flatten()
                                         .collect();
 
-                                    temp_dir.close()?;
+                                    if let Some(temp_dir) = temp_dir {
+                                        temp_dir.close()?;
+                                    }
                                     debug!("hydration: {:?}", hydration);
                                     Ok(hydration)
                                 }
@@ -171,7 +273,7 @@ impl Provider for Infisical {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::providers::fake_cli;
+    use crate::providers::{fake_cli, stand_in};
     use std::path::Path;
     use tempfile::tempdir;
 
@@ -272,4 +374,66 @@ mod tests {
                 .contains("Infisical CLI not found")
         );
     }
+
+    #[tokio::test]
+    async fn test_resolve_with_machine_identity_uses_the_api() {
+        let (address, requests) = stand_in(vec![
+            (
+                "POST /api/v1/auth/universal-auth/login ",
+                200,
+                r#"{"accessToken":"at","expiresIn":7200}"#,
+            ),
+            (
+                "GET /api/v3/secrets/raw?workspaceId=proj123&environment=dev&secretPath=%2F&",
+                200,
+                r#"{"secrets":[{"secretKey":"DB_URL","secretValue":"own"}],"imports":[{"secrets":[{"secretKey":"DB_URL","secretValue":"imported"},{"secretKey":"SHARED","secretValue":"shared"}]}]}"#,
+            ),
+            (
+                "GET /api/v3/secrets/raw?workspaceId=proj123&environment=dev&secretPath=%2Fapi&",
+                200,
+                r#"{"secrets":[{"secretKey":"TOKEN","secretValue":"nested"}]}"#,
+            ),
+        ])
+        .await;
+        let uris = [
+            format!("infisical://{address}/proj123/dev/DB_URL?tls=false"),
+            format!("infisical://{address}/proj123/dev/SHARED?tls=false"),
+            format!("infisical://{address}/proj123/dev/api/TOKEN?tls=false"),
+        ];
+        let mut p = Infisical::new();
+        for uri in &uris {
+            p.add(uri.clone()).unwrap();
+        }
+        let empty_bin = tempdir().unwrap();
+        let mut env = path_env(&empty_bin);
+        env.insert(
+            "INFISICAL_UNIVERSAL_AUTH_CLIENT_ID".to_string(),
+            "id".to_string(),
+        );
+        env.insert(
+            "INFISICAL_UNIVERSAL_AUTH_CLIENT_SECRET".to_string(),
+            "secret".to_string(),
+        );
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result[&uris[0]], "own");
+        assert_eq!(result[&uris[1]], "shared");
+        assert_eq!(result[&uris[2]], "nested");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        let logins = requests
+            .iter()
+            .filter(|request| request.starts_with("POST "))
+            .collect::<Vec<_>>();
+        assert_eq!(logins.len(), 1);
+        assert!(logins[0].contains(r#""clientSecret":"secret""#));
+        assert!(
+            requests
+                .iter()
+                .filter(|request| request.starts_with("GET "))
+                .all(|request| request.contains("authorization: Bearer at"))
+        );
+    }
 }
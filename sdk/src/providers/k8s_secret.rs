@@ -0,0 +1,353 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Ok, Result, anyhow, bail};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures::future::try_join_all;
+use log::debug;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{
+    Provider, Warnings, add_url, deserialize_output, host_with_port,
+    network::kubeconfig::select_kube_context, run_cli,
+};
+
+#[derive(Default)]
+pub struct K8sSecret {
+    urls: FxHashMap<Url, String>,
+}
+
+impl K8sSecret {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Deserialize)]
+struct Secret {
+    #[serde(default)]
+    data: HashMap<String, String>,
+}
+
+/// `(cluster-endpoint, context, namespace, secret)` and the key of
+/// `k8s-secret://<cluster-endpoint>/<context>/<namespace>/<secret>/<key>`.
+type SecretRef = (String, String, String, String);
+
+fn target(url: &Url) -> Result<(SecretRef, String)> {
+    let invalid = || {
+        Error::invalid_uri(
+            url.as_str(),
+            format!(
+                "Kubernetes Secret URI must be k8s-secret://CLUSTER:PORT/CONTEXT/NAMESPACE/SECRET/KEY: {}",
+                redact(url.as_str())
+            ),
+        )
+    };
+    let segments = url
+        .path()
+        .split('/')
+        .skip(1)
+        .map(|segment| {
+            urlencoding::decode(segment)
+                .map(|segment| segment.into_owned())
+                .map_err(|_| invalid())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [context, namespace, secret, key] = segments.as_slice() else {
+        return Err(invalid().into());
+    };
+    if url.host().is_none() || segments.iter().any(String::is_empty) {
+        return Err(invalid().into());
+    }
+    Ok((
+        (
+            host_with_port(url)?,
+            context.clone(),
+            namespace.clone(),
+            secret.clone(),
+        ),
+        key.clone(),
+    ))
+}
+
+#[async_trait]
+impl Provider for K8sSecret {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, "k8s-secret")
+    }
+
+    fn name(&self) -> &'static str {
+        "Kubernetes Secret"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://kubernetes.io/docs/tasks/tools/"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let extra_env = Arc::new(extra_env.clone());
+        let name = self.name();
+        let install_url = self.install_url();
+        let mut by_secret = BTreeMap::<SecretRef, Vec<(String, &String)>>::new();
+        for (url, value) in &self.urls {
+            let (secret, key) = target(url)?;
+            by_secret.entry(secret).or_default().push((key, value));
+        }
+
+        // Contexts are matched against the cluster endpoint the same way as
+        // `kubectl://`, from a single read of the local kubeconfig.
+        let cmd = ["kubectl", "config", "view", "-o", "json"];
+        debug!("Lade run: {}", cmd.join(" "));
+        let child = run_cli(&cmd, &extra_env, name, install_url, None).await?;
+        if !child.status.success() {
+            bail!(
+                "{name} error reading kubeconfig: {}",
+                String::from_utf8_lossy(&child.stderr).trim()
+            );
+        }
+        let kubeconfig = child.stdout;
+
+        let fetches = by_secret
+            .into_iter()
+            .map(|((endpoint, selector, namespace, secret), keys)| {
+                let extra_env = Arc::clone(&extra_env);
+                let context = select_kube_context(&kubeconfig, &endpoint, Some(&selector));
+                async move {
+                    let context = context?;
+                    let cmd = [
+                        "kubectl",
+                        "--context",
+                        &context,
+                        "-n",
+                        &namespace,
+                        "get",
+                        "secret",
+                        &secret,
+                        "-o",
+                        "json",
+                    ];
+                    debug!("Lade run: {}", cmd.join(" "));
+                    let child = run_cli(&cmd, &extra_env, name, install_url, None).await?;
+                    if !child.status.success() {
+                        let stderr = String::from_utf8_lossy(&child.stderr);
+                        let message =
+                            format!("{name} error for {namespace}/{secret}: {}", stderr.trim());
+                        if stderr.contains("(NotFound)") {
+                            return Err(Error::not_found(keys[0].1, message).into());
+                        }
+                        bail!(message);
+                    }
+                    let loaded: Secret = deserialize_output(&child, name)?;
+                    let hydration = keys
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let encoded = loaded.data.get(&key).ok_or_else(|| {
+                                Error::not_found(
+                                    value,
+                                    format!("Key {key} not found in {name} {namespace}/{secret}"),
+                                )
+                            })?;
+                            let decoded = STANDARD.decode(encoded).map_err(|e| {
+                                anyhow!("{name} error: invalid encoding for key {key}: {e}")
+                            })?;
+                            let decoded = String::from_utf8(decoded).map_err(|_| {
+                                anyhow!("{name} key {key} of {namespace}/{secret} is not UTF-8")
+                            })?;
+                            Ok((value.clone(), decoded))
+                        })
+                        .collect::<Result<Hydration>>()?;
+                    debug!("hydration: {:?}", hydration);
+                    Ok(hydration)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    const KUBECONFIG: &str = r#"{"clusters":[{"name":"dev","cluster":{"server":"https://127.0.0.1:6443"}}],"contexts":[{"name":"kind-dev","context":{"cluster":"dev"}}]}"#;
+
+    #[test]
+    fn test_add_routing() {
+        let mut p = K8sSecret::new();
+        assert!(
+            p.add("k8s-secret://127.0.0.1:6443/kind-dev/default/db/password".to_string())
+                .is_ok()
+        );
+        assert!(
+            p.add("kubectl://127.0.0.1:6443/kind-dev/default/svc/db/5432".to_string())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_target() {
+        let url = Url::parse("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/password").unwrap();
+        assert_eq!(
+            target(&url).unwrap(),
+            (
+                (
+                    "127.0.0.1:6443".into(),
+                    "kind-dev".into(),
+                    "apps".into(),
+                    "db".into()
+                ),
+                "password".into()
+            )
+        );
+        let url = Url::parse("k8s-secret://127.0.0.1:6443/kind-dev/apps/db").unwrap();
+        assert!(target(&url).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_batches_keys_per_secret() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        // "YXBw" is base64 for "app", "czNjcmV0" for "s3cret".
+        fake_cli(
+            &fake_bin,
+            "kubectl",
+            &format!(
+                r#"if [ "$1" = "config" ]; then echo '{KUBECONFIG}'; exit 0; fi
+echo "$@" >> {}
+echo '{{"kind":"Secret","data":{{"username":"YXBw","password":"czNjcmV0"}}}}'"#,
+                calls.display()
+            ),
+        );
+        let mut p = K8sSecret::new();
+        p.add("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/username".to_string())
+            .unwrap();
+        p.add("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/password".to_string())
+            .unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result
+                .get("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/username")
+                .unwrap(),
+            "app"
+        );
+        assert_eq!(
+            result
+                .get("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/password")
+                .unwrap(),
+            "s3cret"
+        );
+        let calls = std::fs::read_to_string(calls).unwrap();
+        assert_eq!(
+            calls.trim(),
+            "--context kind-dev -n apps get secret db -o json"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_missing_key_and_context() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "kubectl",
+            &format!(
+                r#"if [ "$1" = "config" ]; then echo '{KUBECONFIG}'; exit 0; fi
+echo '{{"data":{{}}}}'"#
+            ),
+        );
+        let mut p = K8sSecret::new();
+        p.add("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/missing".to_string())
+            .unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Key missing not found"));
+        assert_eq!(err.downcast_ref::<Error>().unwrap().kind(), "not_found");
+
+        let mut p = K8sSecret::new();
+        p.add("k8s-secret://10.0.0.1:6443/other/apps/db/password".to_string())
+            .unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no kubeconfig cluster matches"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_failure_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "kubectl",
+            &format!(
+                r#"if [ "$1" = "config" ]; then echo '{KUBECONFIG}'; exit 0; fi
+echo 'Error from server (Forbidden): secrets "db" is forbidden' >&2
+exit 1"#
+            ),
+        );
+        let mut p = K8sSecret::new();
+        p.add("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/password".to_string())
+            .unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Kubernetes Secret error for apps/db"));
+        assert!(err.contains("Forbidden"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = K8sSecret::new();
+        p.add("k8s-secret://127.0.0.1:6443/kind-dev/apps/db/password".to_string())
+            .unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Kubernetes Secret CLI not found")
+        );
+    }
+}
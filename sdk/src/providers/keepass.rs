@@ -0,0 +1,446 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use anyhow::{Ok, Result, anyhow, bail};
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, file::expand_path, run_cli_with_stdin};
+
+/// Extra env var carrying a database password typed at the interactive
+/// prompt, when the URI has no `?password=${NAME}` binding.
+pub const PASSWORD_ENV: &str = "KEEPASS_PASSWORD";
+
+/// `keepass://<path-to.kdbx>/<group>/<entry>/<field>[?password=${NAME}]`
+/// through `keepassxc-cli show`. Groups may nest, so the entry path is
+/// everything between the database and the last segment.
+#[derive(Default)]
+pub struct KeePass {
+    values: Vec<String>,
+}
+
+impl KeePass {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// One `keepassxc-cli show` printing each attribute on its own line. The
+    /// password is written to stdin, the only place the CLI reads it from.
+    /// A missing entry or attribute is reported as not found for `uri`.
+    async fn show(
+        &self,
+        uri: &str,
+        path: &str,
+        entry: &str,
+        attributes: &[&str],
+        password: &str,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<String> {
+        let name = self.name();
+        let mut cmd = vec!["keepassxc-cli", "show", "-q"];
+        for attribute in attributes {
+            cmd.extend(["-a", attribute]);
+        }
+        cmd.extend([path, entry]);
+        debug!("Lade run: {}", cmd.join(" "));
+        let stdin = format!("{password}\n");
+        let child = run_cli_with_stdin(&cmd, &stdin, extra_env, name, self.install_url()).await?;
+        if !child.status.success() {
+            let stderr = String::from_utf8_lossy(&child.stderr);
+            let message = format!("{name} error for {entry} in {path}: {}", stderr.trim());
+            if stderr.contains("Could not find entry") || stderr.contains("unknown attribute") {
+                return Err(Error::not_found(uri, message).into());
+            }
+            bail!(message);
+        }
+        Ok(String::from_utf8(child.stdout)?)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Target {
+    database: String,
+    entry: String,
+    attribute: String,
+    /// Binding holding the database password, from `?password=${NAME}`.
+    password: Option<String>,
+}
+
+fn target(value: &str) -> Result<Target> {
+    let invalid = |message: &str| -> anyhow::Error {
+        Error::invalid_uri(value, format!("{message}: {}", redact(value))).into()
+    };
+    let shape = "KeePassXC URI must be keepass://DATABASE.kdbx/GROUP/ENTRY/FIELD";
+    let rest = value
+        .strip_prefix("keepass://")
+        .ok_or_else(|| invalid("Not a keepass scheme"))?;
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let segments = path
+        .split('/')
+        .map(|segment| {
+            urlencoding::decode(segment)
+                .map(|segment| segment.into_owned())
+                .map_err(|_| invalid("KeePassXC URI is not valid UTF-8"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let Some(split) = segments.iter().position(|s| s.ends_with(".kdbx")) else {
+        return Err(invalid("KeePassXC URI must point to a .kdbx database"));
+    };
+    let (database, rest) = segments.split_at(split + 1);
+    let Some((attribute, entry)) = rest.split_last() else {
+        return Err(invalid(shape));
+    };
+    if entry.is_empty() || attribute.is_empty() {
+        return Err(invalid(shape));
+    }
+    let mut password = None;
+    for (key, param) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            // Anything but a binding is rejected so database passwords never
+            // sit in `lade.yml`.
+            "password" => {
+                let binding = param
+                    .strip_prefix("${")
+                    .and_then(|r| r.strip_suffix('}'))
+                    .map(|r| r.trim_start_matches('.'))
+                    .ok_or_else(|| {
+                        invalid(
+                            "KeePassXC password must reference a binding like ?password=${NAME}",
+                        )
+                    })?;
+                password = Some(binding.to_string());
+            }
+            _ => return Err(invalid(&format!("Unsupported KeePassXC query {key}"))),
+        }
+    }
+    Ok(Target {
+        database: database.join("/"),
+        entry: entry.join("/"),
+        attribute: attribute.clone(),
+        password,
+    })
+}
+
+/// The database of a `keepass://` source that has no `?password=` binding,
+/// i.e. one that needs the interactive prompt to unlock.
+pub fn locked_database(source: &str) -> Option<String> {
+    target(source)
+        .ok()
+        .filter(|t| t.password.is_none())
+        .map(|t| t.database)
+}
+
+/// `?password=${NAME}` reads the binding forwarded as env, else the password
+/// typed at the interactive prompt.
+fn password(target: &Target, extra_env: &HashMap<String, String>) -> Result<String> {
+    let var = target.password.as_deref().unwrap_or(PASSWORD_ENV);
+    extra_env.get(var).cloned().ok_or_else(|| {
+        anyhow!(
+            "KeePassXC database {} is locked: add ?password=${{NAME}} or run interactively",
+            target.database
+        )
+    })
+}
+
+#[async_trait]
+impl Provider for KeePass {
+    fn add(&mut self, value: String) -> Result<()> {
+        if !value.starts_with("keepass://") {
+            bail!("Not a keepass scheme");
+        }
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "KeePassXC"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://keepassxc.org/download/"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        cwd: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        // Keyed by password binding too: URIs of one entry may unlock it
+        // differently, and each must use its own password.
+        let mut by_entry =
+            BTreeMap::<(String, String, Option<String>), Vec<(Target, &String)>>::new();
+        for value in &self.values {
+            let target = target(value)?;
+            by_entry
+                .entry((
+                    target.database.clone(),
+                    target.entry.clone(),
+                    target.password.clone(),
+                ))
+                .or_default()
+                .push((target, value));
+        }
+        // Unlocking runs the database KDF, which is deliberately expensive, so
+        // entries are read one after the other with all their attributes in a
+        // single `show`.
+        let mut hydration = Hydration::default();
+        for ((database, entry, _), group) in by_entry {
+            let path = expand_path(&database, cwd);
+            let path = path.to_string_lossy();
+            let password = password(&group[0].0, extra_env)?;
+            let attributes = group
+                .iter()
+                .map(|(t, _)| t.attribute.as_str())
+                .collect::<Vec<_>>();
+            let output = self
+                .show(group[0].1, &path, &entry, &attributes, &password, extra_env)
+                .await?;
+            let lines = output.strip_suffix('\n').unwrap_or(&output);
+            let lines = lines.split('\n').collect::<Vec<_>>();
+            if lines.len() == attributes.len() {
+                for ((_, value), line) in group.iter().zip(lines) {
+                    hydration.insert((*value).clone(), line.to_string());
+                }
+            } else {
+                // A multi-line attribute (usually notes) shifts the lines, so
+                // read each attribute on its own.
+                for (target, value) in &group {
+                    let attribute = [target.attribute.as_str()];
+                    let output = self
+                        .show(value, &path, &entry, &attribute, &password, extra_env)
+                        .await?;
+                    let resolved = output.strip_suffix('\n').unwrap_or(&output);
+                    hydration.insert((*value).clone(), resolved.to_string());
+                }
+            }
+        }
+        debug!("hydration: {:?}", hydration);
+        Ok(hydration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    #[test]
+    fn test_target_with_nested_groups() {
+        let t = target("keepass://vaults/team.kdbx/Prod/Databases/Main%20DB/Password").unwrap();
+        assert_eq!(t.database, "vaults/team.kdbx");
+        assert_eq!(t.entry, "Prod/Databases/Main DB");
+        assert_eq!(t.attribute, "Password");
+        assert_eq!(t.password, None);
+        let t = target("keepass:///srv/team.kdbx/Entry/UserName?password=${KDBX}").unwrap();
+        assert_eq!(t.database, "/srv/team.kdbx");
+        assert_eq!(t.entry, "Entry");
+        assert_eq!(t.password.as_deref(), Some("KDBX"));
+        assert!(target("keepass://team.kdbx/Password").is_err());
+        assert!(target("keepass://team/Entry/Password").is_err());
+        assert!(target("keepass://team.kdbx/Entry/Password?key=x").is_err());
+    }
+
+    #[test]
+    fn test_locked_database() {
+        assert_eq!(
+            locked_database("keepass://~/team.kdbx/Entry/Password").as_deref(),
+            Some("~/team.kdbx")
+        );
+        assert_eq!(
+            locked_database("keepass://team.kdbx/Entry/Password?password=${P}"),
+            None
+        );
+        assert_eq!(locked_database("op://a/b/c/d"), None);
+    }
+
+    #[test]
+    fn test_password_sources() {
+        let env = HashMap::from([
+            ("KDBX".to_string(), "from_binding".to_string()),
+            (PASSWORD_ENV.to_string(), "prompted".to_string()),
+        ]);
+        let bound = target("keepass://t.kdbx/E/Password?password=${KDBX}").unwrap();
+        assert_eq!(password(&bound, &env).unwrap(), "from_binding");
+        let prompted = target("keepass://t.kdbx/E/Password").unwrap();
+        assert_eq!(password(&prompted, &env).unwrap(), "prompted");
+        let literal = target("keepass://t.kdbx/E/Password?password=hunter2").unwrap_err();
+        assert_eq!(
+            literal.downcast_ref::<Error>().unwrap().kind(),
+            "invalid_uri"
+        );
+        assert!(!literal.to_string().contains("hunter2"));
+        assert!(
+            password(&prompted, &HashMap::new())
+                .unwrap_err()
+                .to_string()
+                .contains("is locked")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_batches_attributes_per_entry() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        fake_cli(
+            &fake_bin,
+            "keepassxc-cli",
+            &format!(
+                r#"read -r password
+[ "$password" = "s3cret" ] || {{ echo 'Invalid credentials' >&2; exit 1; }}
+echo "$@" >> {}
+shift 2
+while [ "$1" = "-a" ]; do echo "$2-value"; shift 2; done"#,
+                calls.display()
+            ),
+        );
+        let mut p = KeePass::new();
+        p.add("keepass:///db/team.kdbx/Prod/DB/UserName".to_string())
+            .unwrap();
+        p.add("keepass:///db/team.kdbx/Prod/DB/Password".to_string())
+            .unwrap();
+        let mut env = path_env(&fake_bin);
+        env.insert(PASSWORD_ENV.to_string(), "s3cret".to_string());
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result
+                .get("keepass:///db/team.kdbx/Prod/DB/UserName")
+                .unwrap(),
+            "UserName-value"
+        );
+        assert_eq!(
+            result
+                .get("keepass:///db/team.kdbx/Prod/DB/Password")
+                .unwrap(),
+            "Password-value"
+        );
+        let calls = std::fs::read_to_string(calls).unwrap();
+        assert_eq!(calls.lines().count(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_uses_each_uri_password() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "keepassxc-cli",
+            r#"read -r password
+shift 2
+while [ "$1" = "-a" ]; do echo "$2-$password"; shift 2; done"#,
+        );
+        let mut p = KeePass::new();
+        let first = "keepass:///db/team.kdbx/Prod/DB/UserName?password=${FIRST}";
+        let second = "keepass:///db/team.kdbx/Prod/DB/Password?password=${SECOND}";
+        p.add(first.to_string()).unwrap();
+        p.add(second.to_string()).unwrap();
+        let mut env = path_env(&fake_bin);
+        env.insert("FIRST".to_string(), "one".to_string());
+        env.insert("SECOND".to_string(), "two".to_string());
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get(first).unwrap(), "UserName-one");
+        assert_eq!(result.get(second).unwrap(), "Password-two");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_multiline_attribute_falls_back_per_attribute() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "keepassxc-cli",
+            r#"shift 2
+while [ "$1" = "-a" ]; do
+    case "$2" in
+        Notes) printf 'line one\nline two\n' ;;
+        *) echo "$2-value" ;;
+    esac
+    shift 2
+done"#,
+        );
+        let mut p = KeePass::new();
+        p.add("keepass:///db/team.kdbx/E/Notes".to_string())
+            .unwrap();
+        p.add("keepass:///db/team.kdbx/E/Password".to_string())
+            .unwrap();
+        let mut env = path_env(&fake_bin);
+        env.insert(PASSWORD_ENV.to_string(), "pw".to_string());
+        let result = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result.get("keepass:///db/team.kdbx/E/Notes").unwrap(),
+            "line one\nline two"
+        );
+        assert_eq!(
+            result.get("keepass:///db/team.kdbx/E/Password").unwrap(),
+            "Password-value"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_failure_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "keepassxc-cli",
+            "echo 'Could not find entry with path Prod/Missing.' >&2\nexit 1",
+        );
+        let mut p = KeePass::new();
+        p.add("keepass:///db/team.kdbx/Prod/Missing/Password".to_string())
+            .unwrap();
+        let mut env = path_env(&fake_bin);
+        env.insert(PASSWORD_ENV.to_string(), "pw".to_string());
+        let err = p
+            .resolve(Path::new("."), &env, &Warnings::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("KeePassXC error for Prod/Missing"));
+        assert!(err.contains("Could not find entry"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = KeePass::new();
+        p.add("keepass:///db/team.kdbx/E/Password".to_string())
+            .unwrap();
+        let mut env = path_env(&empty_bin);
+        env.insert(PASSWORD_ENV.to_string(), "pw".to_string());
+        let result = p.resolve(Path::new("."), &env, &Warnings::default()).await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("KeePassXC CLI not found")
+        );
+    }
+}
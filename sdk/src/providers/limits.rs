@@ -0,0 +1,288 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use log::debug;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{Error, Hydration};
+
+use super::{Provider, Warnings};
+
+/// First pause between two attempts, doubled on each retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Shells run arbitrary commands, so they keep a timeout unless told otherwise.
+const SHELL_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    /// Slots left under the `concurrency` limit of the provider resolving.
+    static PERMITS: Option<Arc<Semaphore>>;
+}
+
+/// How long, how often and how widely one provider is called. Unset fields
+/// fall back to the next layer (see [`Limits::or`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Limits {
+    /// Time allowed to one attempt of the provider.
+    pub timeout: Option<Duration>,
+    /// Attempts after the first when the backend fails (not for a missing
+    /// secret or a malformed URI).
+    pub retries: Option<u32>,
+    /// Backend calls (CLI runs, HTTP requests) in flight at once.
+    pub concurrency: Option<usize>,
+}
+
+impl Limits {
+    /// These limits, completed by `fallback` where unset.
+    pub fn or(&self, fallback: &Limits) -> Limits {
+        Limits {
+            timeout: self.timeout.or(fallback.timeout),
+            retries: self.retries.or(fallback.retries),
+            concurrency: self.concurrency.or(fallback.concurrency),
+        }
+    }
+
+    fn builtin(scheme: &str) -> Limits {
+        match scheme {
+            "sh" | "bash" | "zsh" | "fish" => Limits {
+                timeout: Some(SHELL_TIMEOUT),
+                ..Limits::default()
+            },
+            _ => Limits::default(),
+        }
+    }
+}
+
+/// Limits of an invocation: `all` applies to every scheme and wins over
+/// `by_scheme`, which wins over built-in defaults.
+#[derive(Clone, Debug, Default)]
+pub struct ProviderLimits {
+    pub all: Limits,
+    pub by_scheme: HashMap<String, Limits>,
+}
+
+impl ProviderLimits {
+    pub fn for_scheme(&self, scheme: &str) -> Limits {
+        let configured = self.by_scheme.get(scheme).cloned().unwrap_or_default();
+        self.all.or(&configured).or(&Limits::builtin(scheme))
+    }
+}
+
+/// Waits for a slot under the `concurrency` limit of the provider being
+/// resolved. Every backend call holds one while in flight.
+pub(crate) async fn throttle() -> Option<OwnedSemaphorePermit> {
+    let semaphore = PERMITS.try_with(Clone::clone).ok().flatten()?;
+    semaphore.acquire_owned().await.ok()
+}
+
+/// Resolves `provider` within `limits`, retrying with backoff when the
+/// backend itself failed.
+pub(super) async fn resolve_limited(
+    provider: &dyn Provider,
+    scheme: &str,
+    limits: &Limits,
+    cwd: &Path,
+    extra_env: &HashMap<String, String>,
+    warnings: &Warnings,
+) -> Result<Hydration> {
+    let permits = limits
+        .concurrency
+        .map(|concurrency| Arc::new(Semaphore::new(concurrency.max(1))));
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let resolving = PERMITS.scope(permits.clone(), provider.resolve(cwd, extra_env, warnings));
+        let result = match limits.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, resolving)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::provider(
+                        scheme,
+                        format!("{} timed out after {timeout:?}", provider.name()),
+                    )
+                    .into())
+                }),
+            None => resolving.await,
+        }
+        .map_err(|e| super::typed(e, scheme));
+        match result {
+            Err(e)
+                if attempt < limits.retries.unwrap_or_default()
+                    && matches!(e.downcast_ref::<Error>(), Some(Error::Provider(_))) =>
+            {
+                attempt += 1;
+                debug!(
+                    "{} attempt {attempt} failed, retrying: {e}",
+                    provider.name()
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails `failures` times (or always, with `missing`, as a secret that
+    /// does not exist), then returns one value after `delay`, and records how
+    /// many backend calls ran at once.
+    #[derive(Default)]
+    struct Flaky {
+        failures: usize,
+        missing: bool,
+        delay: Duration,
+        calls: AtomicUsize,
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Provider for Flaky {
+        fn add(&mut self, _: String) -> Result<()> {
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "Flaky"
+        }
+
+        fn install_url(&self) -> &'static str {
+            ""
+        }
+
+        async fn resolve(
+            &self,
+            _: &Path,
+            _: &HashMap<String, String>,
+            _: &Warnings,
+        ) -> Result<Hydration> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.missing {
+                return Err(Error::not_found("flaky://0", "no such secret").into());
+            }
+            if calls < self.failures {
+                anyhow::bail!("backend unavailable");
+            }
+            let fetches = (0..4).map(|i| async move {
+                let _permit = throttle().await;
+                let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(self.delay).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                (format!("flaky://{i}"), i.to_string())
+            });
+            Ok(futures::future::join_all(fetches)
+                .await
+                .into_iter()
+                .collect())
+        }
+    }
+
+    async fn resolve(provider: &Flaky, limits: Limits) -> Result<Hydration> {
+        resolve_limited(
+            provider,
+            "flaky",
+            &limits,
+            Path::new("."),
+            &HashMap::new(),
+            &Warnings::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_retries_backend_failures() {
+        let provider = Flaky {
+            failures: 2,
+            ..Flaky::default()
+        };
+        let limits = Limits {
+            retries: Some(1),
+            ..Limits::default()
+        };
+        let error = resolve(&provider, limits).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "provider");
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+
+        let provider = Flaky {
+            failures: 2,
+            ..Flaky::default()
+        };
+        let limits = Limits {
+            retries: Some(2),
+            ..Limits::default()
+        };
+        assert_eq!(resolve(&provider, limits).await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_missing_secrets_are_not_retried() {
+        let provider = Flaky {
+            missing: true,
+            ..Flaky::default()
+        };
+        let limits = Limits {
+            retries: Some(2),
+            ..Limits::default()
+        };
+        let error = resolve(&provider, limits).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "not_found");
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_concurrency() {
+        let provider = Flaky {
+            delay: Duration::from_millis(20),
+            ..Flaky::default()
+        };
+        let limits = Limits {
+            concurrency: Some(2),
+            ..Limits::default()
+        };
+        resolve(&provider, limits).await.unwrap();
+        assert_eq!(provider.peak.load(Ordering::SeqCst), 2);
+
+        let provider = Flaky {
+            delay: Duration::from_secs(5),
+            ..Flaky::default()
+        };
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(50)),
+            ..Limits::default()
+        };
+        let error = resolve(&provider, limits).await.unwrap_err();
+        assert_eq!(error.to_string(), "Flaky timed out after 50ms");
+    }
+
+    #[test]
+    fn test_layers() {
+        let limits = ProviderLimits {
+            all: Limits {
+                retries: Some(3),
+                ..Limits::default()
+            },
+            by_scheme: HashMap::from([(
+                "vault".to_string(),
+                Limits {
+                    retries: Some(1),
+                    concurrency: Some(4),
+                    ..Limits::default()
+                },
+            )]),
+        };
+        let sh = limits.for_scheme("sh");
+        assert_eq!(sh.timeout, Some(SHELL_TIMEOUT));
+        assert_eq!(sh.retries, Some(3));
+        let vault = limits.for_scheme("vault");
+        assert_eq!(vault.timeout, None);
+        assert_eq!(vault.retries, Some(3));
+        assert_eq!(vault.concurrency, Some(4));
+    }
+}
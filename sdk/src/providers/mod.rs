@@ -10,13 +10,17 @@ use futures::future::try_join_all;
 use rustc_hash::{FxHashMap, FxHashSet};
 use serde::de::DeserializeOwned;
 use std::process::Stdio;
-use tokio::process::Command;
+use tokio::{io::AsyncWriteExt, process::Command};
 use url::Url;
 
-use crate::Hydration;
+use crate::{Error, Hydration, error::redact};
+use limits::{ProviderLimits, throttle};
 
 pub mod compat;
+pub mod keepass;
+pub mod limits;
 pub mod network;
+pub mod vault;
 
 #[derive(Clone, Default)]
 pub struct Warnings(Arc<Mutex<Vec<String>>>);
@@ -30,21 +34,31 @@ impl Warnings {
         std::mem::take(&mut *self.0.lock().unwrap())
     }
 }
+mod aws_sm;
+mod aws_ssm;
+mod azkv;
+mod bitwarden;
 mod doppler;
+mod env;
 mod file;
+mod gcp_sm;
+mod generate;
 mod infisical;
+mod k8s_secret;
 mod onepassword;
+mod pass;
 mod passbolt;
+mod plugin;
 mod raw;
 mod sh;
-mod vault;
+mod totp;
 
 #[async_trait]
 pub trait Provider: Sync {
     fn add(&mut self, value: String) -> Result<()>;
 
     /// Human-readable provider name, used in user-facing messages.
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 
     /// Where to install/find the backing tool, used in user-facing messages.
     fn install_url(&self) -> &'static str;
@@ -58,17 +72,36 @@ pub trait Provider: Sync {
         true
     }
 
+    /// Leases issued while resolving, to revoke once the command exits.
+    fn leases(&self) -> Vec<vault::Lease> {
+        Vec::new()
+    }
+
     async fn resolve(
         &self,
         cwd: &Path,
         extra_env: &HashMap<String, String>,
         warnings: &Warnings,
     ) -> Result<Hydration>;
+
+    /// Stores `value` at `uri`, for providers whose backend can be written
+    /// to. The others reject the URI.
+    async fn write(
+        &self,
+        uri: &str,
+        _value: &str,
+        _cwd: &Path,
+        _extra_env: &HashMap<String, String>,
+    ) -> Result<()> {
+        Err(Error::invalid_uri(uri, format!("{} cannot write secrets", self.name())).into())
+    }
 }
 
 pub struct Providers {
     by_scheme: FxHashMap<&'static str, Box<dyn Provider + Send>>,
+    plugins: FxHashMap<String, Box<dyn Provider + Send>>,
     fallback: Box<dyn Provider + Send>,
+    limits: ProviderLimits,
 }
 
 impl Default for Providers {
@@ -81,11 +114,38 @@ impl Providers {
     pub fn new() -> Self {
         let mut by_scheme: FxHashMap<&'static str, Box<dyn Provider + Send>> = FxHashMap::default();
         by_scheme.insert("doppler", Box::new(doppler::Doppler::new()));
+        by_scheme.insert("env", Box::new(env::Env::new()));
         by_scheme.insert("infisical", Box::new(infisical::Infisical::new()));
         by_scheme.insert("op", Box::new(onepassword::OnePassword::new()));
         by_scheme.insert("vault", Box::new(vault::Vault::new()));
         by_scheme.insert("passbolt", Box::new(passbolt::Passbolt::new()));
         by_scheme.insert("file", Box::new(file::File::new()));
+        by_scheme.insert("sops", Box::new(file::File::sops()));
+        by_scheme.insert("age", Box::new(file::File::age()));
+        by_scheme.insert("aws-sm", Box::new(aws_sm::AwsSecretsManager::new()));
+        by_scheme.insert("ssm", Box::new(aws_ssm::AwsSsm::new()));
+        by_scheme.insert("gcp-sm", Box::new(gcp_sm::GcpSecretManager::new()));
+        by_scheme.insert("azkv", Box::new(azkv::AzureKeyVault::new()));
+        by_scheme.insert("bws", Box::new(bitwarden::BitwardenSecrets::new()));
+        by_scheme.insert("bw", Box::new(bitwarden::Bitwarden::new()));
+        by_scheme.insert("keepass", Box::new(keepass::KeePass::new()));
+        by_scheme.insert("k8s-secret", Box::new(k8s_secret::K8sSecret::new()));
+        by_scheme.insert(
+            "pass",
+            Box::new(pass::PasswordStore::new(
+                "pass",
+                "pass",
+                "https://www.passwordstore.org/",
+            )),
+        );
+        by_scheme.insert(
+            "gopass",
+            Box::new(pass::PasswordStore::new(
+                "gopass",
+                "gopass",
+                "https://www.gopass.pw/",
+            )),
+        );
         by_scheme.insert(
             "sh",
             Box::new(sh::Shell::new(
@@ -110,9 +170,20 @@ impl Providers {
             "fish",
             Box::new(sh::Shell::new("fish", "fish", "https://fishshell.com/")),
         );
+        by_scheme.insert("gen", Box::new(generate::Generate::new()));
+        by_scheme.insert("totp", Box::new(totp::Totp::new()));
         Self {
             by_scheme,
+            plugins: FxHashMap::default(),
             fallback: Box::new(raw::Raw::new()),
+            limits: ProviderLimits::default(),
+        }
+    }
+
+    pub fn with_limits(limits: ProviderLimits) -> Self {
+        Self {
+            limits,
+            ..Self::new()
         }
     }
 
@@ -129,8 +200,33 @@ impl Providers {
                 // (e.g. file:// without ?query=) falls back to Raw rather than returning an error.
                 Err(_) => self.fallback.add(value),
             },
-            None => self.fallback.add(value),
+            None => self.add_unknown(scheme.to_string(), value),
+        }
+    }
+
+    /// Unknown schemes go to a `lade-provider-<scheme>` executable on `PATH`
+    /// when there is one, and are kept as plain values otherwise.
+    fn add_unknown(&mut self, scheme: String, value: String) -> Result<()> {
+        if !self.plugins.contains_key(&scheme) {
+            match plugin::Plugin::find(&scheme, std::env::var_os("PATH").as_deref()) {
+                Some(plugin) => {
+                    self.plugins.insert(scheme.clone(), Box::new(plugin));
+                }
+                None => return self.fallback.add(value),
+            }
         }
+        self.plugins
+            .get_mut(&scheme)
+            .expect("plugin registered above")
+            .add(value)
+    }
+
+    pub fn leases(&self) -> Vec<vault::Lease> {
+        self.by_scheme
+            .values()
+            .chain(self.plugins.values())
+            .flat_map(|p| p.leases())
+            .collect()
     }
 
     pub async fn resolve(
@@ -139,16 +235,26 @@ impl Providers {
         extra_env: &HashMap<String, String>,
         warnings: &Warnings,
     ) -> Result<(Hydration, FxHashSet<String>)> {
-        let active: Vec<&dyn Provider> = self
+        let active: Vec<(&str, &dyn Provider)> = self
             .by_scheme
-            .values()
-            .map(|p| p.as_ref() as &dyn Provider)
-            .chain(std::iter::once(self.fallback.as_ref() as &dyn Provider))
-            .filter(|p| p.has_work())
+            .iter()
+            .map(|(scheme, p)| (*scheme, p.as_ref() as &dyn Provider))
+            .chain(
+                self.plugins
+                    .iter()
+                    .map(|(scheme, p)| (scheme.as_str(), p.as_ref() as &dyn Provider)),
+            )
+            .chain(std::iter::once((
+                "",
+                self.fallback.as_ref() as &dyn Provider,
+            )))
+            .filter(|(_, p)| p.has_work())
             .collect();
 
-        let results = try_join_all(active.iter().map(|p| async move {
-            let hydration = p.resolve(cwd, extra_env, warnings).await?;
+        let results = try_join_all(active.iter().map(|(scheme, p)| async move {
+            let limits = self.limits.for_scheme(scheme);
+            let hydration =
+                limits::resolve_limited(*p, scheme, &limits, cwd, extra_env, warnings).await?;
             Ok::<_, anyhow::Error>((p.masks_in_output(), hydration))
         }))
         .await?;
@@ -165,6 +271,46 @@ impl Providers {
 
         Ok((full_hydration, maskable_sources))
     }
+
+    /// Stores `value` at `uri` with the provider of its scheme.
+    pub async fn write(
+        &self,
+        uri: &str,
+        value: &str,
+        cwd: &Path,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<()> {
+        let scheme = uri.split_once("://").map(|(s, _)| s).unwrap_or("");
+        let Some(provider) = self.by_scheme.get(scheme) else {
+            let message = match scheme {
+                "" => "not a secret URI, nothing to write".to_string(),
+                scheme => format!("{scheme}:// secrets cannot be written"),
+            };
+            return Err(Error::invalid_uri(uri, message).into());
+        };
+        provider
+            .write(uri, value, cwd, extra_env)
+            .await
+            .map_err(|e| typed(e, scheme))
+    }
+}
+
+/// Gives every provider failure a typed [`Error`], keeping the ones the
+/// provider raised itself.
+fn typed(error: anyhow::Error, scheme: &str) -> anyhow::Error {
+    match error.downcast::<Error>() {
+        Ok(error) => error.with_scheme(scheme).into(),
+        Err(error) => Error::provider(scheme, format!("{error:#}")).into(),
+    }
+}
+
+/// `name` from the env given to providers, else from lade's own, when set.
+pub(crate) fn env_var(extra_env: &HashMap<String, String>, name: &str) -> Option<String> {
+    extra_env
+        .get(name)
+        .cloned()
+        .or_else(|| std::env::var(name).ok())
+        .filter(|value| !value.is_empty())
 }
 
 pub fn add_url(urls: &mut FxHashMap<Url, String>, value: String, scheme: &str) -> Result<()> {
@@ -184,20 +330,59 @@ pub async fn run_cli(
     install_url: &str,
     cwd: Option<&Path>,
 ) -> Result<std::process::Output> {
+    let _permit = throttle().await;
+    let mut c = cli_command(cmd, extra_env);
+    if let Some(dir) = cwd {
+        c.current_dir(dir);
+    }
+    c.output()
+        .await
+        .map_err(|e| cli_spawn_error(e, name, install_url))
+}
+
+/// Like [`run_cli`], but writes `stdin` to the child, for CLIs that only read
+/// credentials from their standard input.
+pub async fn run_cli_with_stdin(
+    cmd: &[&str],
+    stdin: &str,
+    extra_env: &HashMap<String, String>,
+    name: &str,
+    install_url: &str,
+) -> Result<std::process::Output> {
+    let _permit = throttle().await;
+    let mut child = cli_command(cmd, extra_env)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| cli_spawn_error(e, name, install_url))?;
+    let mut pipe = child.stdin.take().expect("piped stdin");
+    // A child that exits without reading stdin reports through its status.
+    match pipe.write_all(stdin.as_bytes()).await {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e.into()),
+        _ => {}
+    }
+    drop(pipe);
+    Ok(child.wait_with_output().await?)
+}
+
+fn cli_command(cmd: &[&str], extra_env: &HashMap<String, String>) -> Command {
     let mut c = Command::new(cmd[0]);
-    c.args(&cmd[1..])
+    // A provider that timed out drops its future: do not leave the CLI
+    // waiting, e.g. for a biometric unlock.
+    c.kill_on_drop(true)
+        .args(&cmd[1..])
         .envs(extra_env.iter())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
-    if let Some(dir) = cwd {
-        c.current_dir(dir);
-    }
-    c.output().await.map_err(|e| match e.kind() {
+    c
+}
+
+fn cli_spawn_error(e: std::io::Error, name: &str, install_url: &str) -> anyhow::Error {
+    match e.kind() {
         std::io::ErrorKind::NotFound => anyhow!(
             "{name} CLI not found. Make sure the binary is in your PATH or install it from {install_url}."
         ),
         _ => anyhow!("{name} error: {e}"),
-    })
+    }
 }
 
 pub fn deserialize_output<T: DeserializeOwned>(
@@ -210,11 +395,145 @@ pub fn deserialize_output<T: DeserializeOwned>(
     })
 }
 
-pub fn host_with_port(url: &Url) -> String {
-    match url.port() {
-        Some(port) => format!("{}:{}", url.host().expect("Missing host"), port),
-        None => url.host().expect("Missing host").to_string(),
+pub fn host_with_port(url: &Url) -> Result<String> {
+    let Some(host) = url.host() else {
+        return Err(Error::invalid_uri(
+            url.as_str(),
+            format!("missing host in {}", redact(url.as_str())),
+        )
+        .into());
+    };
+    Ok(match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    })
+}
+
+/// `https://HOST[:PORT]` for a self-hosted API, or `http://` with
+/// `?tls=false` for a local server.
+pub fn api_base(url: &Url) -> Result<String> {
+    let scheme = match url.query_pairs().find(|(key, _)| key == "tls") {
+        None => "https",
+        Some((_, tls)) if tls == "true" => "https",
+        Some((_, tls)) if tls == "false" => "http",
+        Some(_) => {
+            return Err(Error::invalid_uri(url.as_str(), "?tls= must be true or false").into());
+        }
+    };
+    Ok(format!("{scheme}://{}", host_with_port(url)?))
+}
+
+/// Sends `request` within the provider's concurrency limit and returns its
+/// JSON body, or the server's message when the status is not a success.
+pub(crate) async fn fetch_json(
+    request: reqwest::RequestBuilder,
+    name: &str,
+    what: &str,
+) -> Result<serde_json::Value> {
+    let _permit = throttle().await;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| anyhow!("{name} error for {what}: {e}"))?;
+    let status = response.status();
+    let bytes = response.bytes().await?;
+    let value = serde_json::from_slice::<serde_json::Value>(&bytes).map_err(|e| {
+        anyhow!(
+            "{name} error for {what}: {e} (body: {})",
+            String::from_utf8_lossy(&bytes)
+        )
+    })?;
+    if !status.is_success() {
+        let message = match (&value["message"], &value["messages"]) {
+            (serde_json::Value::String(message), _) => message.clone(),
+            (_, serde_json::Value::Array(messages)) => messages
+                .iter()
+                .filter_map(serde_json::Value::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::new(),
+        };
+        bail!("{name} error for {what}: {status} {message}");
     }
+    Ok(value)
+}
+
+/// Rejects URLs without a host, or without `segments` non-empty and
+/// decodable path segments, before a provider groups them by those parts.
+pub fn require_parts(urls: &FxHashMap<Url, String>, segments: usize, shape: &str) -> Result<()> {
+    for (url, value) in urls {
+        let complete = url
+            .path()
+            .split('/')
+            .skip(1)
+            .take(segments)
+            .filter(|s| !s.is_empty())
+            .count()
+            == segments;
+        let decodable = url
+            .path()
+            .split('/')
+            .all(|s| urlencoding::decode(s).is_ok());
+        if url.host().is_none() || !complete || !decodable {
+            return Err(
+                Error::invalid_uri(value, format!("{} must be {shape}", redact(value))).into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub type Requests = std::sync::Arc<std::sync::Mutex<Vec<String>>>;
+
+/// Stand-in HTTP server answering `(request line prefix, status, body)` routes,
+/// recording every raw request. Unknown routes get a 404.
+#[cfg(test)]
+pub async fn stand_in(routes: Vec<(&'static str, u16, &'static str)>) -> (String, Requests) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let requests = Requests::default();
+    let recorded = std::sync::Arc::clone(&requests);
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut bytes = Vec::new();
+            let mut buffer = [0; 4096];
+            let request = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                bytes.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&bytes).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length || read == 0 {
+                        break text;
+                    }
+                }
+            };
+            let (status, body) = routes
+                .iter()
+                .find(|(prefix, ..)| request.starts_with(prefix))
+                .map(|(_, status, body)| (*status, *body))
+                .unwrap_or((404, r#"{"errors":[]}"#));
+            recorded.lock().unwrap().push(request);
+            let response = format!(
+                "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (address, requests)
 }
 
 #[cfg(test)]
@@ -267,6 +586,24 @@ mod tests {
         assert!(!fallback_has_work("doppler://api.doppler.com/proj/env/KEY"));
     }
 
+    #[test]
+    fn test_dispatch_env() {
+        assert!(has_work_for("env", "env://GH_TOKEN?default=none"));
+        assert!(!fallback_has_work("env://GH_TOKEN"));
+    }
+
+    #[test]
+    fn test_dispatch_gen() {
+        assert!(has_work_for("gen", "gen://password?length=16"));
+        assert!(!fallback_has_work("gen://uuid"));
+    }
+
+    #[test]
+    fn test_dispatch_totp() {
+        assert!(has_work_for("totp", "totp://?secret=GEZDGNBV"));
+        assert!(!fallback_has_work("totp://?secret=GEZDGNBV"));
+    }
+
     #[test]
     fn test_dispatch_vault() {
         assert!(has_work_for("vault", "vault://localhost/secret/app/pass"));
@@ -279,6 +616,88 @@ mod tests {
         assert!(!fallback_has_work("op://my.1password.com/vault/item/field"));
     }
 
+    #[test]
+    fn test_dispatch_aws_sm() {
+        assert!(has_work_for("aws-sm", "aws-sm://us-east-1/app/password"));
+        assert!(!fallback_has_work("aws-sm://us-east-1/app/password"));
+    }
+
+    #[test]
+    fn test_dispatch_ssm() {
+        assert!(has_work_for(
+            "ssm",
+            "ssm://us-east-1/app/password?version=2"
+        ));
+        assert!(!fallback_has_work("ssm://us-east-1/app/password?version=2"));
+    }
+
+    #[test]
+    fn test_dispatch_gcp_sm() {
+        assert!(has_work_for("gcp-sm", "gcp-sm://my-project/api-key/3"));
+        assert!(!fallback_has_work("gcp-sm://my-project/api-key/3"));
+    }
+
+    #[test]
+    fn test_dispatch_azkv() {
+        assert!(has_work_for("azkv", "azkv://my-vault/db-password"));
+        assert!(!fallback_has_work("azkv://my-vault/db-password"));
+    }
+
+    #[test]
+    fn test_dispatch_bitwarden() {
+        assert!(has_work_for("bws", "bws://project-id/DB_PASSWORD"));
+        assert!(has_work_for("bw", "bw://github/password"));
+        assert!(!fallback_has_work("bw://github/password"));
+    }
+
+    #[test]
+    fn test_dispatch_pass() {
+        assert!(has_work_for("pass", "pass://email/github?field=login"));
+        assert!(has_work_for("gopass", "gopass://email/github"));
+        assert!(!fallback_has_work("pass://email/github"));
+    }
+
+    #[test]
+    fn test_dispatch_sops() {
+        assert!(has_work_for("sops", "sops://secrets/prod.yaml?query=.db"));
+        assert!(fallback_has_work("sops://secrets/prod.yaml"));
+    }
+
+    #[test]
+    fn test_dispatch_age() {
+        assert!(has_work_for(
+            "age",
+            "age://prod.yaml.age?identity=key.txt&query=.db"
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_keepass() {
+        assert!(has_work_for(
+            "keepass",
+            "keepass://team.kdbx/Prod/DB/Password?password=${KDBX_PASSWORD}"
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_k8s_secret() {
+        assert!(has_work_for(
+            "k8s-secret",
+            "k8s-secret://127.0.0.1:6443/kind-dev/default/db/password"
+        ));
+        assert!(!fallback_has_work(
+            "k8s-secret://127.0.0.1:6443/kind-dev/default/db/password"
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_unknown_scheme_without_plugin_to_fallback() {
+        let mut p = Providers::new();
+        p.add("nosuchprovider://host/key".to_string()).unwrap();
+        assert!(p.plugins.is_empty());
+        assert!(p.fallback.has_work());
+    }
+
     #[test]
     fn test_dispatch_plain_value_to_fallback() {
         assert!(fallback_has_work("plainvalue"));
@@ -309,4 +728,20 @@ mod tests {
         ));
         assert!(!fallback_has_work("file:///path/to/config.json?query=.key"));
     }
+
+    #[tokio::test]
+    async fn test_write_is_unsupported_by_default() {
+        let providers = Providers::new();
+        for uri in ["env://HOME", "custom://secret", "plain value"] {
+            let error = providers
+                .write(uri, "value", Path::new("."), &HashMap::new())
+                .await
+                .unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<Error>().unwrap().kind(),
+                "invalid_uri",
+                "{uri}"
+            );
+        }
+    }
 }
@@ -7,10 +7,7 @@ use std::process::Command;
 /// this is safe to call concurrently from multiple threads without any
 /// shared cache or lock: caching would only save a cheap subprocess call at
 /// the cost of serializing unrelated bindings against each other.
-pub(crate) fn resolve_kube_context(
-    cluster_endpoint: &str,
-    selector: Option<&str>,
-) -> Result<String> {
+pub fn resolve_kube_context(cluster_endpoint: &str, selector: Option<&str>) -> Result<String> {
     let output = Command::new("kubectl")
         .args(["config", "view", "-o", "json"])
         .output()?;
@@ -21,7 +18,17 @@ pub(crate) fn resolve_kube_context(
             String::from_utf8_lossy(&output.stderr)
         );
     }
-    let config: KubeConfig = serde_json::from_slice(&output.stdout)?;
+    select_kube_context(&output.stdout, cluster_endpoint, selector)
+}
+
+/// Picks the context of `kubectl config view -o json` output whose cluster
+/// server matches `cluster_endpoint`, honoring an explicit `selector`.
+pub fn select_kube_context(
+    config: &[u8],
+    cluster_endpoint: &str,
+    selector: Option<&str>,
+) -> Result<String> {
+    let config: KubeConfig = serde_json::from_slice(config)?;
     let clusters = config
         .clusters
         .unwrap_or_default()
@@ -2,6 +2,7 @@ use anyhow::{Result, bail};
 use rustc_hash::FxHashMap;
 use std::collections::HashMap;
 
+pub mod kubeconfig;
 mod kubectl;
 mod kubefwd;
 mod ssh;
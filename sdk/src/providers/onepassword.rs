@@ -5,15 +5,20 @@ use async_trait::async_trait;
 use futures::future::try_join_all;
 use itertools::Itertools;
 use log::{debug, warn};
+use reqwest::Client;
 use rustc_hash::FxHashMap;
+use serde_json::{Value, json};
 use std::process::Stdio;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use url::Url;
 
-use crate::Hydration;
+use crate::{Error, Hydration};
 
-use super::{Provider, Warnings, add_url};
+use super::{
+    Provider, Warnings, add_url, deserialize_output, env_var, fetch_json, limits::throttle,
+    require_parts, run_cli, run_cli_with_stdin,
+};
 
 static SEP: &str = "'Km5Ge8AbNc+QSBauOIN0jg'";
 
@@ -45,6 +50,7 @@ async fn read_one(
     secret_ref: &str,
     extra_env: &HashMap<String, String>,
 ) -> Result<String> {
+    let (secret_ref, query) = secret_ref.split_once('?').unwrap_or((secret_ref, ""));
     // Strip "op://host/" prefix to get "vault/item/[section/]field"
     let path = secret_ref
         .strip_prefix(&format!("op://{account}/"))
@@ -61,15 +67,24 @@ async fn read_one(
         [v, i, _section, f] => (*v, *i, format!("label={f}")),
         _ => bail!("1Password: invalid reference (need vault/item/field): {secret_ref}"),
     };
+    // `?attribute=otp` asks for the current code of a one-time password field.
+    let otp = url::form_urlencoded::parse(query.as_bytes())
+        .any(|(key, value)| key == "attribute" && value == "otp");
+    let selector = if otp {
+        vec!["--otp"]
+    } else {
+        vec!["--fields", &field_filter, "--reveal"]
+    };
 
+    let _permit = throttle().await;
     let process = Command::new("op")
+        .kill_on_drop(true)
         .args([
             "item", "get", item,
             "--vault", vault,
             "--account", account,
-            "--fields", &field_filter,
-            "--reveal",
         ])
+        .args(selector)
         .envs(extra_env.iter())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -92,6 +107,239 @@ async fn read_one(
         .replace('\n', "\\n"))
 }
 
+/// A 1Password Connect server, used instead of the `op` CLI when
+/// `OP_CONNECT_HOST` and `OP_CONNECT_TOKEN` are set or a URI has `?connect=`.
+struct Connect {
+    http: Client,
+    host: String,
+    token: String,
+}
+
+/// The Connect server and token for `url`, if it should not go through the
+/// CLI. `?connect=HOST` wins over `OP_CONNECT_HOST`.
+fn connect_server(
+    url: &Url,
+    extra_env: &HashMap<String, String>,
+) -> Result<Option<(String, String)>> {
+    let host = url
+        .query_pairs()
+        .find(|(key, _)| key == "connect")
+        .map(|(_, host)| host.into_owned())
+        .or_else(|| env_var(extra_env, "OP_CONNECT_HOST"));
+    match (host, env_var(extra_env, "OP_CONNECT_TOKEN")) {
+        (Some(host), Some(token)) if host.contains("://") => Ok(Some((host, token))),
+        (Some(host), Some(token)) => Ok(Some((format!("https://{host}"), token))),
+        (Some(host), None) => Err(Error::provider(
+            "op",
+            format!("1Password Connect server {host} needs OP_CONNECT_TOKEN"),
+        )
+        .into()),
+        (None, _) => Ok(None),
+    }
+}
+
+/// The parts of `op://ACCOUNT/VAULT/ITEM/[SECTION/]FIELD` Connect looks up,
+/// each matched by name or ID like the CLI does.
+struct Reference {
+    vault: String,
+    item: String,
+    section: Option<String>,
+    field: String,
+    otp: bool,
+}
+
+impl Reference {
+    fn from_url(url: &Url, value: &str) -> Result<Self> {
+        let segments = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .map(|segment| Ok(urlencoding::decode(segment)?.into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        let (vault, item, section, field) = match segments.as_slice() {
+            [vault, item, field] => (vault, item, None, field),
+            [vault, item, section, field] => (vault, item, Some(section.clone()), field),
+            _ => {
+                return Err(Error::invalid_uri(
+                    value,
+                    "op URI must be op://ACCOUNT/VAULT/ITEM/[SECTION/]FIELD",
+                )
+                .into());
+            }
+        };
+        let mut otp = false;
+        for (key, option) in url.query_pairs() {
+            match (key.as_ref(), option.as_ref()) {
+                ("connect", _) | ("attribute", "value") => {}
+                ("attribute", "otp") => otp = true,
+                _ => {
+                    return Err(Error::invalid_uri(
+                        value,
+                        format!("1Password Connect does not support ?{key}={option}"),
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(Self {
+            vault: vault.clone(),
+            item: item.clone(),
+            section,
+            field: field.clone(),
+            otp,
+        })
+    }
+}
+
+/// The ID of the entry of `list` whose ID or `label` is `name`.
+fn find_id(list: &Value, label: &str, name: &str) -> Option<String> {
+    list.as_array()?
+        .iter()
+        .find(|entry| entry["id"].as_str() == Some(name) || entry[label].as_str() == Some(name))?
+        ["id"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The index in `fields` of the field `reference` names, in the section
+/// with ID `section`.
+fn find_field(fields: &[Value], reference: &Reference, section: Option<&str>) -> Option<usize> {
+    let matches = |field: &Value| {
+        (field["id"].as_str() == Some(&reference.field)
+            || field["label"].as_str() == Some(&reference.field))
+            && section.is_none_or(|section| field["section"]["id"].as_str() == Some(section))
+    };
+    match section {
+        Some(_) => fields.iter().position(matches),
+        // Without a section, a top-level field wins over same-named ones in sections.
+        None => fields
+            .iter()
+            .position(|field| matches(field) && field["section"].is_null())
+            .or_else(|| fields.iter().position(matches)),
+    }
+}
+
+fn field_value(item: &Value, reference: &Reference) -> Option<String> {
+    let section = match &reference.section {
+        Some(section) => Some(find_id(&item["sections"], "label", section)?),
+        None => None,
+    };
+    let fields = item["fields"].as_array()?;
+    let field = &fields[find_field(fields, reference, section.as_deref())?];
+    let value = if reference.otp {
+        field["totp"].as_str()?
+    } else {
+        field["value"].as_str().unwrap_or_default()
+    };
+    // Same shape as `op inject` output, so both backends agree.
+    Some(value.trim().replace('\n', "\\n"))
+}
+
+/// Sets the field `reference` names in an item as `op item get --format
+/// json` prints it, adding the field, and its section, when missing.
+fn set_field(item: &mut Value, reference: &Reference, value: &str) {
+    let section = reference.section.as_ref().map(|section| {
+        find_id(&item["sections"], "label", section).unwrap_or_else(|| {
+            if !item["sections"].is_array() {
+                item["sections"] = json!([]);
+            }
+            let sections = item["sections"].as_array_mut().expect("sections array");
+            sections.push(json!({ "id": section, "label": section }));
+            section.clone()
+        })
+    });
+    if !item["fields"].is_array() {
+        item["fields"] = json!([]);
+    }
+    let fields = item["fields"].as_array_mut().expect("fields array");
+    match find_field(fields, reference, section.as_deref()) {
+        Some(index) => fields[index]["value"] = Value::String(value.to_string()),
+        None => {
+            let mut field = json!({
+                "id": reference.field,
+                "label": reference.field,
+                "type": "CONCEALED",
+                "value": value,
+            });
+            if let Some(section) = section {
+                field["section"] = json!({ "id": section });
+            }
+            fields.push(field);
+        }
+    }
+}
+
+impl Connect {
+    async fn get(&self, path: &str) -> Result<Value> {
+        let url = format!("{}/v1/{path}", self.host.trim_end_matches('/'));
+        debug!("1Password Connect GET {url}");
+        let request = self.http.get(&url).bearer_auth(&self.token);
+        fetch_json(request, "1Password Connect", path).await
+    }
+
+    /// Lists vaults once, items once per vault, and fetches each item once
+    /// for all its fields, whether they name it by title or by ID.
+    async fn resolve(&self, refs: Vec<(Reference, &String)>) -> Result<Hydration> {
+        let vaults = self.get("vaults").await?;
+        let mut by_vault = HashMap::<String, Vec<_>>::new();
+        for (reference, value) in refs {
+            let vault_id = find_id(&vaults, "name", &reference.vault).ok_or_else(|| {
+                Error::not_found(
+                    value,
+                    format!("1Password vault {} not found", reference.vault),
+                )
+            })?;
+            by_vault
+                .entry(vault_id)
+                .or_default()
+                .push((reference, value));
+        }
+        let listings = by_vault.into_iter().map(|(vault_id, refs)| async move {
+            let items = self.get(&format!("vaults/{vault_id}/items")).await?;
+            refs.into_iter()
+                .map(|(reference, value)| {
+                    let item_id = find_id(&items, "title", &reference.item).ok_or_else(|| {
+                        Error::not_found(
+                            value,
+                            format!(
+                                "1Password item {} not found in vault {}",
+                                reference.item, reference.vault
+                            ),
+                        )
+                    })?;
+                    Ok(((vault_id.clone(), item_id), (reference, value)))
+                })
+                .collect::<Result<Vec<_>>>()
+        });
+        let fetches = try_join_all(listings)
+            .await?
+            .into_iter()
+            .flatten()
+            .into_group_map()
+            .into_iter()
+            .map(|((vault_id, item_id), refs)| async move {
+                let item = self
+                    .get(&format!("vaults/{vault_id}/items/{item_id}"))
+                    .await?;
+                refs.into_iter()
+                    .map(|(reference, value)| {
+                        let resolved = field_value(&item, &reference).ok_or_else(|| {
+                            Error::not_found(
+                                value,
+                                format!(
+                                    "1Password field {} not found in item {}",
+                                    reference.field, reference.item
+                                ),
+                            )
+                        })?;
+                        Ok((value.clone(), resolved))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            });
+        Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
+    }
+}
+
 #[async_trait]
 impl Provider for OnePassword {
     fn add(&mut self, value: String) -> Result<()> {
@@ -127,11 +375,34 @@ impl Provider for OnePassword {
         extra_env: &HashMap<String, String>,
         warnings: &Warnings,
     ) -> Result<Hydration> {
+        require_parts(&self.urls, 0, "op://ACCOUNT/VAULT/ITEM/FIELD")?;
+        let mut connected = HashMap::<(String, String), Vec<_>>::new();
+        let mut by_cli = Vec::new();
+        for (url, value) in &self.urls {
+            match connect_server(url, extra_env)? {
+                Some(server) => connected
+                    .entry(server)
+                    .or_default()
+                    .push((Reference::from_url(url, value)?, value)),
+                None => by_cli.push((url, value)),
+            }
+        }
+        let connect_fetches = connected
+            .into_iter()
+            .map(|((host, token), refs)| async move {
+                let connect = Connect {
+                    http: Client::new(),
+                    host,
+                    token,
+                };
+                connect.resolve(refs).await
+            });
+        let connected = try_join_all(connect_fetches).await?;
+
         let extra_env = Arc::new(extra_env.clone());
-        let fetches = self
-            .urls
-            .iter()
-            .into_group_map_by(|(url, _)| url.host().expect("Missing host"))
+        let fetches = by_cli
+            .into_iter()
+            .into_group_map_by(|(url, _)| url.host().expect("account checked above"))
             .into_iter()
             .map(|(host, group)| {
                 let vars = group
@@ -157,7 +428,9 @@ impl Provider for OnePassword {
                     let cmd = &["op", "inject", "--account", &account];
                     debug!("Lade run: {}", cmd.join(" "));
 
+                    let _permit = throttle().await;
                     let mut process = Command::new(cmd[0])
+                        .kill_on_drop(true)
                         .args(&cmd[1..])
                         .envs(extra_env.iter())
                         .stdout(Stdio::piped())
@@ -233,15 +506,83 @@ impl Provider for OnePassword {
         Ok(try_join_all(fetches)
             .await?
             .into_iter()
+            .chain(connected)
             .flatten()
             .collect::<Hydration>())
     }
+
+    /// Updates the field by piping the edited item to `op item edit`, so the
+    /// value never shows in the process list. The CLI goes through the same
+    /// Connect server as reads when one is configured.
+    async fn write(
+        &self,
+        uri: &str,
+        value: &str,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<()> {
+        let url = Url::parse(uri).map_err(|e| Error::invalid_uri(uri, e.to_string()))?;
+        let account = url.host_str().unwrap_or_default().to_string();
+        let reference = Reference::from_url(&url, uri)?;
+        if account.is_empty() {
+            return Err(
+                Error::invalid_uri(uri, "op URI must be op://ACCOUNT/VAULT/ITEM/FIELD").into(),
+            );
+        }
+        if reference.otp {
+            return Err(Error::invalid_uri(
+                uri,
+                "1Password one-time codes cannot be written, write the otpauth:// seed instead",
+            )
+            .into());
+        }
+        let mut env = extra_env.clone();
+        if let Some((host, token)) = connect_server(&url, extra_env)? {
+            env.insert("OP_CONNECT_HOST".to_string(), host);
+            env.insert("OP_CONNECT_TOKEN".to_string(), token);
+        }
+        let target = [
+            reference.item.as_str(),
+            "--vault",
+            &reference.vault,
+            "--account",
+            &account,
+        ];
+        let get = [&["op", "item", "get"], &target[..], &["--format", "json"]].concat();
+        debug!("Lade run: {}", get.join(" "));
+        let child = run_cli(&get, &env, self.name(), self.install_url(), None).await?;
+        if !child.status.success() {
+            bail!(
+                "1Password error: {}",
+                String::from_utf8_lossy(&child.stderr).trim()
+            );
+        }
+        let mut item: Value = deserialize_output(&child, self.name())?;
+        set_field(&mut item, &reference, value);
+        let edit = [&["op", "item", "edit"], &target[..]].concat();
+        debug!("Lade run: {} < item", edit.join(" "));
+        let child = run_cli_with_stdin(
+            &edit,
+            &item.to_string(),
+            &env,
+            self.name(),
+            self.install_url(),
+        )
+        .await?;
+        if !child.status.success() {
+            bail!(
+                "1Password error: {}",
+                String::from_utf8_lossy(&child.stderr).trim()
+            );
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::providers::fake_cli;
+    use crate::providers::{fake_cli, stand_in};
     use std::collections::HashMap;
     use std::path::Path;
     use tempfile::tempdir;
@@ -536,4 +877,267 @@ fi"#,
             "secret_from_item_get"
         );
     }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_one_time_password_attribute() {
+        // op inject understands ?attribute=otp itself; the fallback asks op item get for --otp.
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "op",
+            r#"if [ "$1" = "inject" ]; then
+    IFS= read -r stdin
+    case "$stdin" in
+        *"&"*) echo '[ERROR] invalid character in secret reference: &' >&2; exit 1 ;;
+        *"?attribute=otp") printf '123456' ;;
+    esac
+elif [ "$1" = "item" ] && [ "$8" = "--otp" ]; then
+    printf '654321'
+else
+    printf '[ERROR] expected --otp, got: %s\n' "$8" >&2
+    exit 1
+fi"#,
+        );
+        let extra = HashMap::from([(
+            "PATH".to_string(),
+            fake_bin.path().to_string_lossy().into_owned(),
+        )]);
+        for (reference, expected) in [
+            (
+                "op://my.1password.com/vault/Deploy/one-time password?attribute=otp",
+                "123456",
+            ),
+            (
+                "op://my.1password.com/Ops&Deploy/Deploy/one-time password?attribute=otp",
+                "654321",
+            ),
+        ] {
+            let mut p = OnePassword::new();
+            p.add(reference.to_string()).unwrap();
+            let result = p
+                .resolve(Path::new("."), &extra, &Warnings::default())
+                .await
+                .unwrap();
+            assert_eq!(result[reference], expected);
+        }
+    }
+
+    const CONNECT_ITEM: &str = r#"{
+        "id": "i1",
+        "title": "Deploy Key",
+        "sections": [{"id": "s1", "label": "Prod"}],
+        "fields": [
+            {"id": "password", "label": "password", "value": "s3cret"},
+            {"id": "f2", "label": "token", "section": {"id": "s1"}, "value": "prod-token"},
+            {"id": "f3", "label": "token", "value": "default-token"},
+            {"id": "otp", "label": "one-time password", "type": "OTP", "value": "otpauth://totp/x?secret=GEZDGNBV", "totp": "123456"}
+        ]
+    }"#;
+
+    async fn connect(
+        routes: Vec<(&'static str, u16, &'static str)>,
+        uris: &[&str],
+        env: &[(&str, &str)],
+    ) -> (Result<Hydration>, crate::providers::Requests) {
+        let (address, requests) = stand_in(routes).await;
+        let mut p = OnePassword::new();
+        for uri in uris {
+            p.add(uri.replace("ADDRESS", &address)).unwrap();
+        }
+        // No `op` on the PATH: everything must go through Connect.
+        let empty_bin = tempdir().unwrap();
+        let mut extra = env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.replace("ADDRESS", &address)))
+            .collect::<HashMap<_, _>>();
+        extra.insert(
+            "PATH".to_string(),
+            empty_bin.path().to_string_lossy().into_owned(),
+        );
+        let result = p
+            .resolve(Path::new("."), &extra, &Warnings::default())
+            .await;
+        (result, requests)
+    }
+
+    #[tokio::test]
+    async fn test_connect_resolves_references_like_the_cli() {
+        let (result, requests) = connect(
+            vec![
+                ("GET /v1/vaults ", 200, r#"[{"id": "v1", "name": "Infra"}]"#),
+                (
+                    "GET /v1/vaults/v1/items ",
+                    200,
+                    r#"[{"id": "i1", "title": "Deploy Key"}]"#,
+                ),
+                ("GET /v1/vaults/v1/items/i1 ", 200, CONNECT_ITEM),
+            ],
+            &[
+                "op://my.1password.com/Infra/Deploy Key/password",
+                "op://my.1password.com/Infra/Deploy Key/Prod/token",
+                "op://my.1password.com/v1/i1/one-time password?attribute=otp",
+            ],
+            &[
+                ("OP_CONNECT_HOST", "http://ADDRESS"),
+                ("OP_CONNECT_TOKEN", "connect-token"),
+            ],
+        )
+        .await;
+        let result = result.unwrap();
+        assert_eq!(
+            result["op://my.1password.com/Infra/Deploy Key/password"],
+            "s3cret"
+        );
+        assert_eq!(
+            result["op://my.1password.com/Infra/Deploy Key/Prod/token"],
+            "prod-token"
+        );
+        assert_eq!(
+            result["op://my.1password.com/v1/i1/one-time password?attribute=otp"],
+            "123456"
+        );
+        let requests = requests.lock().unwrap();
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.contains("authorization: Bearer connect-token"))
+        );
+        let item_fetches = requests
+            .iter()
+            .filter(|request| request.starts_with("GET /v1/vaults/v1/items/i1 "))
+            .count();
+        assert_eq!(item_fetches, 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_query_and_errors() {
+        let routes = || {
+            vec![
+                ("GET /v1/vaults ", 200, r#"[{"id": "v1", "name": "Infra"}]"#),
+                (
+                    "GET /v1/vaults/v1/items ",
+                    200,
+                    r#"[{"id": "i1", "title": "Deploy Key"}]"#,
+                ),
+                ("GET /v1/vaults/v1/items/i1 ", 200, CONNECT_ITEM),
+            ]
+        };
+        let (result, _) = connect(
+            routes(),
+            &["op://my.1password.com/Infra/Deploy Key/token?connect=http://ADDRESS"],
+            &[("OP_CONNECT_TOKEN", "connect-token")],
+        )
+        .await;
+        let resolved = result.unwrap().into_values().collect::<Vec<_>>();
+        assert_eq!(resolved, ["default-token"]);
+
+        let (result, _) = connect(
+            routes(),
+            &["op://my.1password.com/Infra/Deploy Key/missing?connect=http://ADDRESS"],
+            &[],
+        )
+        .await;
+        let error = result.unwrap_err();
+        let error = error.downcast_ref::<Error>().unwrap();
+        assert_eq!(error.kind(), "provider");
+        assert!(error.to_string().contains("OP_CONNECT_TOKEN"));
+
+        for (uri, message) in [
+            (
+                "op://my.1password.com/Infra/Deploy Key/missing",
+                "field missing",
+            ),
+            ("op://my.1password.com/Infra/Other/password", "item Other"),
+            ("op://my.1password.com/Ops/Deploy Key/password", "vault Ops"),
+        ] {
+            let (result, _) = connect(
+                routes(),
+                &[uri],
+                &[
+                    ("OP_CONNECT_HOST", "http://ADDRESS"),
+                    ("OP_CONNECT_TOKEN", "connect-token"),
+                ],
+            )
+            .await;
+            let error = result.unwrap_err();
+            let error = error.downcast_ref::<Error>().unwrap();
+            assert_eq!(error.kind(), "not_found", "{uri}");
+            assert!(error.to_string().contains(message), "{uri}: {error}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_edits_the_item_field() {
+        let fake_bin = tempdir().unwrap();
+        let record = fake_bin.path().join("record");
+        let stdin = fake_bin.path().join("stdin");
+        fake_cli(
+            &fake_bin,
+            "op",
+            &format!(
+                r#"printf '%s|' "$@" >> {record}
+case "$2" in
+  get) echo '{{"id":"i1","fields":[{{"id":"password","label":"password","value":"old"}},{{"id":"f2","label":"password","section":{{"id":"s1"}},"value":"other"}}],"sections":[{{"id":"s1","label":"db"}}]}}' ;;
+  edit) read -r item; echo "$item" >> {stdin} ;;
+esac"#,
+                record = record.display(),
+                stdin = stdin.display(),
+            ),
+        );
+        let extra = HashMap::from([(
+            "PATH".to_string(),
+            fake_bin.path().to_string_lossy().into_owned(),
+        )]);
+        for reference in [
+            "op://my.1password.com/vault/Deploy/password",
+            "op://my.1password.com/vault/Deploy/db.prod/api=key",
+            "op://my.1password.com/vault/Deploy/db/password",
+        ] {
+            OnePassword::new()
+                .write(reference, "s3cret=1", Path::new("."), &extra)
+                .await
+                .unwrap();
+        }
+        let record = std::fs::read_to_string(&record).unwrap();
+        assert!(!record.contains("s3cret"));
+        assert!(record.starts_with(
+            "item|get|Deploy|--vault|vault|--account|my.1password.com|--format|json|\
+             item|edit|Deploy|--vault|vault|--account|my.1password.com|"
+        ));
+        let items = std::fs::read_to_string(&stdin)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(items[0]["fields"][0]["value"], "s3cret=1");
+        assert_eq!(items[0]["fields"][1]["value"], "other");
+        assert_eq!(
+            items[1]["sections"][1],
+            serde_json::json!({"id": "db.prod", "label": "db.prod"})
+        );
+        assert_eq!(
+            items[1]["fields"][2],
+            serde_json::json!({
+                "id": "api=key",
+                "label": "api=key",
+                "type": "CONCEALED",
+                "value": "s3cret=1",
+                "section": {"id": "db.prod"},
+            })
+        );
+        assert_eq!(items[2]["fields"][0]["value"], "old");
+        assert_eq!(items[2]["fields"][1]["value"], "s3cret=1");
+
+        let error = OnePassword::new()
+            .write(
+                "op://my.1password.com/vault/Deploy/one-time password?attribute=otp",
+                "123456",
+                Path::new("."),
+                &extra,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "invalid_uri");
+    }
 }
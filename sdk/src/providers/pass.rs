@@ -0,0 +1,299 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Ok, Result, anyhow, bail};
+use async_trait::async_trait;
+use itertools::Itertools;
+use log::debug;
+use rustc_hash::FxHashMap;
+use url::Url;
+
+use crate::{Error, Hydration, error::redact};
+
+use super::{Provider, Warnings, add_url, run_cli};
+
+/// `pass://<path>[?line=N|field=name]` for the standard Unix password store,
+/// and `gopass://` for gopass, which shares the same entry layout.
+pub struct PasswordStore {
+    bin: &'static str,
+    name: &'static str,
+    install_url: &'static str,
+    urls: FxHashMap<Url, String>,
+}
+
+impl PasswordStore {
+    pub fn new(bin: &'static str, name: &'static str, install_url: &'static str) -> Self {
+        Self {
+            bin,
+            name,
+            install_url,
+            urls: FxHashMap::default(),
+        }
+    }
+}
+
+enum Selector {
+    Line(usize),
+    Field(String),
+}
+
+/// A malformed store URI, named in the message without its query values.
+fn invalid_uri(url: &Url, message: &str) -> anyhow::Error {
+    Error::invalid_uri(url.as_str(), format!("{message}: {}", redact(url.as_str()))).into()
+}
+
+fn entry(url: &Url) -> Result<String> {
+    let path = format!("{}{}", url.host_str().unwrap_or_default(), url.path());
+    let path = urlencoding::decode(path.trim_matches('/'))
+        .map_err(|_| invalid_uri(url, "Password store entry path is not valid UTF-8"))?;
+    if path.is_empty() {
+        return Err(invalid_uri(
+            url,
+            "Password store URI is missing an entry path",
+        ));
+    }
+    Ok(path.into_owned())
+}
+
+fn selector(url: &Url) -> Result<Selector> {
+    let mut selected = None;
+    for (key, value) in url.query_pairs() {
+        let next = match key.as_ref() {
+            "line" => match value.parse::<usize>() {
+                std::result::Result::Ok(n) if n > 0 => Selector::Line(n),
+                _ => {
+                    return Err(invalid_uri(
+                        url,
+                        "Password store line must be a positive number",
+                    ));
+                }
+            },
+            "field" if !value.is_empty() => Selector::Field(value.into_owned()),
+            _ => {
+                return Err(invalid_uri(
+                    url,
+                    &format!("Unsupported password store query {key}"),
+                ));
+            }
+        };
+        if selected.replace(next).is_some() {
+            return Err(invalid_uri(
+                url,
+                "Password store URI accepts either line or field, not both",
+            ));
+        }
+    }
+    Ok(selected.unwrap_or(Selector::Line(1)))
+}
+
+/// The first line is the password, later lines are free-form and usually
+/// hold `key: value` pairs.
+fn extract(body: &str, selector: &Selector, entry: &str, uri: &str) -> Result<String> {
+    let missing = |message| anyhow::Error::from(Error::not_found(uri, message));
+    match selector {
+        Selector::Line(n) => body.lines().nth(n - 1).map(str::to_string).ok_or_else(|| {
+            missing(format!(
+                "Line {n} not found in password store entry {entry}"
+            ))
+        }),
+        Selector::Field(field) => body
+            .lines()
+            .skip(1)
+            .find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key.trim() == field).then(|| value.trim().to_string())
+            })
+            .ok_or_else(|| {
+                missing(format!(
+                    "Field {field} not found in password store entry {entry}"
+                ))
+            }),
+    }
+}
+
+#[async_trait]
+impl Provider for PasswordStore {
+    fn add(&mut self, value: String) -> Result<()> {
+        add_url(&mut self.urls, value, self.bin)
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn install_url(&self) -> &'static str {
+        self.install_url
+    }
+
+    fn has_work(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        extra_env: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let name = self.name();
+        let groups = self
+            .urls
+            .iter()
+            .map(|(url, value)| Ok((entry(url)?, (url, value))))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .into_group_map();
+        let mut hydration = Hydration::default();
+        // Entries are decrypted one at a time so a locked gpg-agent asks for
+        // the passphrase once instead of opening a pinentry per entry.
+        for (entry, group) in groups {
+            let cmd = [self.bin, "show", entry.as_str()];
+            debug!("Lade run: {}", cmd.join(" "));
+            let child = run_cli(&cmd, extra_env, name, self.install_url, None).await?;
+            if !child.status.success() {
+                let stderr = String::from_utf8_lossy(&child.stderr);
+                let message = format!("{name} error for {entry}: {}", stderr.trim());
+                if stderr.contains("is not in the password store") || stderr.contains("not found") {
+                    return Err(Error::not_found(group[0].1, message).into());
+                }
+                bail!(message);
+            }
+            let body = String::from_utf8(child.stdout)
+                .map_err(|_| anyhow!("{name} entry {entry} is not UTF-8"))?;
+            for (url, value) in group {
+                hydration.insert(
+                    value.clone(),
+                    extract(&body, &selector(url)?, &entry, value)?,
+                );
+            }
+        }
+        debug!("hydration: {:?}", hydration);
+        Ok(hydration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    fn pass() -> PasswordStore {
+        PasswordStore::new("pass", "pass", "https://www.passwordstore.org/")
+    }
+
+    #[test]
+    fn test_add_routing() {
+        let mut p = pass();
+        assert!(p.add("pass://email/github".to_string()).is_ok());
+        assert!(p.add("gopass://email/github".to_string()).is_err());
+        assert!(p.add("plainvalue".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_entry_and_selector() {
+        let url = Url::parse("pass://email/work/github?field=login").unwrap();
+        assert_eq!(entry(&url).unwrap(), "email/work/github");
+        assert!(matches!(selector(&url).unwrap(), Selector::Field(f) if f == "login"));
+        let url = Url::parse("pass://github").unwrap();
+        assert!(matches!(selector(&url).unwrap(), Selector::Line(1)));
+        assert!(selector(&Url::parse("pass://a?line=0").unwrap()).is_err());
+        assert!(selector(&Url::parse("pass://a?line=2&field=x").unwrap()).is_err());
+        assert!(selector(&Url::parse("pass://a?column=2").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_extract() {
+        let body = "s3cret\nlogin: alice\nurl: https://example.com:443\n";
+        assert_eq!(
+            extract(body, &Selector::Line(1), "e", "pass://e").unwrap(),
+            "s3cret"
+        );
+        assert_eq!(
+            extract(body, &Selector::Line(2), "e", "pass://e").unwrap(),
+            "login: alice"
+        );
+        assert_eq!(
+            extract(body, &Selector::Field("url".into()), "e", "pass://e").unwrap(),
+            "https://example.com:443"
+        );
+        let error = extract(body, &Selector::Line(9), "e", "pass://e").unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "not_found");
+        assert!(extract(body, &Selector::Field("s3cret".into()), "e", "pass://e").is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_one_show_per_entry() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        fake_cli(
+            &fake_bin,
+            "gopass",
+            &format!(
+                r#"echo "$@" >> {}
+printf 's3cret\nlogin: alice\n'"#,
+                calls.display()
+            ),
+        );
+        let mut p = PasswordStore::new("gopass", "gopass", "https://www.gopass.pw/");
+        p.add("gopass://web/github".to_string()).unwrap();
+        p.add("gopass://web/github?field=login".to_string())
+            .unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get("gopass://web/github").unwrap(), "s3cret");
+        assert_eq!(
+            result.get("gopass://web/github?field=login").unwrap(),
+            "alice"
+        );
+        let calls = std::fs::read_to_string(calls).unwrap();
+        assert_eq!(calls.trim(), "show web/github");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_failure_reports_stderr() {
+        let fake_bin = tempdir().unwrap();
+        fake_cli(
+            &fake_bin,
+            "pass",
+            "echo 'Error: web/missing is not in the password store.' >&2\nexit 1",
+        );
+        let mut p = pass();
+        p.add("pass://web/missing".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("pass error for web/missing"));
+        assert!(err.to_string().contains("not in the password store"));
+        assert_eq!(err.downcast_ref::<Error>().unwrap().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_cli_not_found() {
+        let empty_bin = tempdir().unwrap();
+        let mut p = pass();
+        p.add("pass://web/github".to_string()).unwrap();
+        let result = p
+            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("pass CLI not found")
+        );
+    }
+}
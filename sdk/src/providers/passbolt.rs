@@ -8,9 +8,9 @@ use log::debug;
 use rustc_hash::FxHashMap;
 use url::Url;
 
-use crate::Hydration;
+use crate::{Error, Hydration};
 
-use super::{Provider, Warnings, add_url, deserialize_output, run_cli};
+use super::{Provider, Warnings, add_url, deserialize_output, require_parts, run_cli};
 
 #[derive(Default)]
 pub struct Passbolt {
@@ -47,20 +47,24 @@ impl Provider for Passbolt {
         extra_env: &HashMap<String, String>,
         _: &Warnings,
     ) -> Result<Hydration> {
+        require_parts(&self.urls, 2, "passbolt://HOST/RESOURCE_ID/FIELD")?;
         let extra_env = Arc::new(extra_env.clone());
         let name = self.name();
         let install_url = self.install_url();
         let fetches = self
             .urls
             .iter()
-            .into_group_map_by(|(url, _)| url.host().expect("Missing host"))
+            .into_group_map_by(|(url, _)| url.host().expect("host checked above"))
             .into_iter()
             .flat_map(|(host, group)| {
                 let extra_env = Arc::clone(&extra_env);
                 group
                     .into_iter()
                     .into_group_map_by(|(url, _)| {
-                        url.path().split('/').nth(1).expect("Missing resource id")
+                        url.path()
+                            .split('/')
+                            .nth(1)
+                            .expect("resource id checked above")
                     })
                     .into_iter()
                     .map(move |(resource_id, group)| {
@@ -83,21 +87,20 @@ impl Provider for Passbolt {
                             let hydration = group
                                 .into_iter()
                                 .map(|(url, value)| {
-                                    let var = url.path().split('/').nth(2).expect("Missing field");
-                                    (
-                                        value.clone(),
-                                        loaded
-                                            .get(var)
-                                            .unwrap_or_else(|| {
-                                                panic!(
-                                                    "Variable not found in Passbolt: {}",
-                                                    resource_id
-                                                )
-                                            })
-                                            .clone(),
-                                    )
+                                    let var =
+                                        url.path().split('/').nth(2).expect("field checked above");
+                                    let Some(loaded) = loaded.get(var) else {
+                                        return Err(Error::not_found(
+                                            value,
+                                            format!(
+                                                "Variable not found in Passbolt: {resource_id}"
+                                            ),
+                                        )
+                                        .into());
+                                    };
+                                    Ok((value.clone(), loaded.clone()))
                                 })
-                                .collect::<Hydration>();
+                                .collect::<Result<Hydration>>()?;
 
                             debug!("hydration: {:?}", hydration);
                             Ok(hydration)
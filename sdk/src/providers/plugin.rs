@@ -0,0 +1,294 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Ok, Result, anyhow, bail};
+use async_trait::async_trait;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::Hydration;
+
+use super::{Provider, Warnings, run_cli_with_stdin};
+
+/// Version of the JSON exchanged with `lade-provider-<scheme>` executables.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A `lade-provider-<scheme>` executable found on `PATH`, resolving every
+/// `<scheme>://` URI of the invocation in one call.
+pub struct Plugin {
+    bin: PathBuf,
+    name: String,
+    uris: Vec<String>,
+    masks: AtomicBool,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    version: u32,
+    uris: &'a [String],
+    cwd: &'a Path,
+    env: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    version: u32,
+    #[serde(default)]
+    values: HashMap<String, String>,
+    #[serde(default)]
+    errors: BTreeMap<String, String>,
+    #[serde(default)]
+    warnings: Vec<String>,
+    #[serde(default = "mask_by_default")]
+    mask: bool,
+}
+
+fn mask_by_default() -> bool {
+    true
+}
+
+impl Plugin {
+    pub fn new(scheme: &str, bin: PathBuf) -> Self {
+        Self {
+            bin,
+            name: format!("lade-provider-{scheme}"),
+            uris: Vec::new(),
+            masks: AtomicBool::new(true),
+        }
+    }
+
+    /// Looks for an executable `lade-provider-<scheme>` in `path` (a `PATH`
+    /// value).
+    pub fn find(scheme: &str, path: Option<&OsStr>) -> Option<Self> {
+        let valid = scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'));
+        if scheme.is_empty() || !valid {
+            return None;
+        }
+        let bin = format!("lade-provider-{scheme}");
+        std::env::split_paths(path?)
+            .map(|dir| dir.join(&bin))
+            .find(|candidate| is_executable(candidate))
+            .map(|bin| Self::new(scheme, bin))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[async_trait]
+impl Provider for Plugin {
+    fn add(&mut self, value: String) -> Result<()> {
+        self.uris.push(value);
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://github.com/zifeo/lade#provider-plugins"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.uris.is_empty()
+    }
+
+    fn masks_in_output(&self) -> bool {
+        self.masks.load(Ordering::Relaxed)
+    }
+
+    async fn resolve(
+        &self,
+        cwd: &Path,
+        extra_env: &HashMap<String, String>,
+        warnings: &Warnings,
+    ) -> Result<Hydration> {
+        let name = self.name();
+        let request = serde_json::to_string(&Request {
+            version: PROTOCOL_VERSION,
+            uris: &self.uris,
+            cwd,
+            env: extra_env
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+        })?;
+        let bin = self.bin.to_string_lossy();
+        debug!("Lade run: {bin}");
+        let child = run_cli_with_stdin(
+            &[bin.as_ref()],
+            &request,
+            extra_env,
+            name,
+            self.install_url(),
+        )
+        .await?;
+        if !child.status.success() {
+            bail!(
+                "{name} error: {}",
+                String::from_utf8_lossy(&child.stderr).trim()
+            );
+        }
+        let response: Response = serde_json::from_slice(&child.stdout).map_err(|e| {
+            anyhow!(
+                "{name} error: invalid response: {e} (stderr: {})",
+                String::from_utf8_lossy(&child.stderr).trim()
+            )
+        })?;
+        if response.version != PROTOCOL_VERSION {
+            bail!(
+                "{name} error: protocol version {} is not supported, expected {PROTOCOL_VERSION}",
+                response.version
+            );
+        }
+        if !response.errors.is_empty() {
+            bail!(
+                "{name} error: {}",
+                response
+                    .errors
+                    .iter()
+                    .map(|(uri, error)| format!("{uri}: {error}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        for warning in response.warnings {
+            warnings.push(format!("{name}: {warning}"));
+        }
+        self.masks.store(response.mask, Ordering::Relaxed);
+        let mut values = response.values;
+        let hydration = self
+            .uris
+            .iter()
+            .map(|uri| {
+                let value = values
+                    .remove(uri)
+                    .ok_or_else(|| anyhow!("{name} error: no value returned for {uri}"))?;
+                Ok((uri.clone(), value))
+            })
+            .collect::<Result<Hydration>>()?;
+        debug!("hydration: {:?}", hydration);
+        Ok(hydration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::fake_cli;
+    use tempfile::tempdir;
+
+    fn plugin(dir: &tempfile::TempDir, script: &str) -> Plugin {
+        fake_cli(dir, "lade-provider-acme", script);
+        Plugin::find("acme", Some(dir.path().as_os_str())).expect("plugin on PATH")
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find() {
+        let dir = tempdir().unwrap();
+        assert!(Plugin::find("acme", Some(dir.path().as_os_str())).is_none());
+        let found = plugin(&dir, "exit 0");
+        assert_eq!(found.name(), "lade-provider-acme");
+        assert!(Plugin::find("../acme", Some(dir.path().as_os_str())).is_none());
+        assert!(Plugin::find("acme", None).is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_batches_uris_in_one_request() {
+        let dir = tempdir().unwrap();
+        let request = dir.path().join("request");
+        let mut p = plugin(
+            &dir,
+            &format!(
+                r#"cat > {}
+echo '{{"version":1,"values":{{"acme://a/1":"one","acme://a/2":"two"}},"warnings":["cached"],"mask":false}}'"#,
+                request.display()
+            ),
+        );
+        p.add("acme://a/1".to_string()).unwrap();
+        p.add("acme://a/2".to_string()).unwrap();
+        let env = HashMap::from([("ACME_TOKEN".to_string(), "t".to_string())]);
+        let warnings = Warnings::default();
+        let result = p
+            .resolve(Path::new("/work"), &env, &warnings)
+            .await
+            .unwrap();
+        assert_eq!(result.get("acme://a/1").unwrap(), "one");
+        assert_eq!(result.get("acme://a/2").unwrap(), "two");
+        assert!(!p.masks_in_output());
+        assert_eq!(warnings.take(), vec!["lade-provider-acme: cached"]);
+        let request: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(request).unwrap()).unwrap();
+        assert_eq!(
+            request,
+            serde_json::json!({
+                "version": 1,
+                "uris": ["acme://a/1", "acme://a/2"],
+                "cwd": "/work",
+                "env": {"ACME_TOKEN": "t"},
+            })
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_reports_errors() {
+        let dir = tempdir().unwrap();
+        let mut p = plugin(
+            &dir,
+            r#"cat > /dev/null
+echo '{"version":1,"values":{"acme://a/1":"one"},"errors":{"acme://a/2":"not found"}}'"#,
+        );
+        p.add("acme://a/1".to_string()).unwrap();
+        p.add("acme://a/2".to_string()).unwrap();
+        let err = p
+            .resolve(Path::new("."), &HashMap::new(), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("lade-provider-acme error: acme://a/2: not found")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_resolve_rejects_bad_responses() {
+        for (script, expected) in [
+            (
+                r#"echo '{"version":2,"values":{}}'"#,
+                "protocol version 2 is not supported",
+            ),
+            (r#"echo '{"version":1,"values":{}}'"#, "no value returned"),
+            ("echo 'not json'", "invalid response"),
+            ("echo 'store offline' >&2; exit 1", "store offline"),
+        ] {
+            let dir = tempdir().unwrap();
+            let mut p = plugin(&dir, &format!("cat > /dev/null\n{script}"));
+            p.add("acme://a/1".to_string()).unwrap();
+            let err = p
+                .resolve(Path::new("."), &HashMap::new(), &Warnings::default())
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use anyhow::{Result, bail};
 use async_trait::async_trait;
@@ -73,14 +73,8 @@ impl Provider for Shell {
             let full_value_str = full_value.clone();
             let extra_env = Arc::clone(&extra_env);
             async move {
-                let output = tokio::time::timeout(
-                    Duration::from_secs(30),
-                    run_cli(&[bin, "-c", &cmd_str], &extra_env, name, install_url, None),
-                )
-                .await
-                .map_err(|_| {
-                    anyhow::anyhow!("{} command timed out after 30s: {}", name, cmd_str)
-                })??;
+                let output =
+                    run_cli(&[bin, "-c", &cmd_str], &extra_env, name, install_url, None).await?;
 
                 let stdout = String::from_utf8(output.stdout)
                     .map_err(|e| anyhow::anyhow!("{} output is not UTF-8: {}", name, e))?;
@@ -97,8 +91,14 @@ impl Provider for Shell {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::providers::fake_cli;
-    use std::path::Path;
+    use crate::providers::{
+        Providers, fake_cli,
+        limits::{Limits, ProviderLimits},
+    };
+    use std::{
+        path::Path,
+        time::{Duration, Instant},
+    };
     use tempfile::tempdir;
 
     fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
@@ -136,13 +136,21 @@ mod tests {
     #[tokio::test]
     #[cfg(unix)]
     async fn test_resolve_timeout() {
-        let fake_bin = tempdir().unwrap();
-        fake_cli(&fake_bin, "sh", "sleep 2");
-        let mut p = Shell::new("sh", "sh", "url");
-        p.add("sh://sleep 2".to_string()).unwrap();
-
-        // We can't easily test 30s timeout in unit tests without making it configurable,
-        // but we can verify the mechanism works if we were to use a shorter timeout.
-        // For now, we trust tokio::time::timeout.
+        let limits = ProviderLimits {
+            all: Limits {
+                timeout: Some(Duration::from_millis(100)),
+                ..Limits::default()
+            },
+            ..ProviderLimits::default()
+        };
+        let mut p = Providers::with_limits(limits);
+        p.add("sh://sleep 5".to_string()).unwrap();
+        let started = Instant::now();
+        let err = p
+            .resolve(Path::new("."), &HashMap::new(), &Warnings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "sh timed out after 100ms");
+        assert!(started.elapsed() < Duration::from_secs(2));
     }
 }
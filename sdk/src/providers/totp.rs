@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use hmac::{EagerHash, Hmac, KeyInit, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::form_urlencoded;
+
+use super::{Provider, Warnings};
+use crate::{Error, Hydration};
+
+const SHAPE: &str = "totp URI must be totp://?secret=BASE32[&digits=6&period=30&algorithm=SHA1]";
+
+/// `totp://?secret=${SEED}`: the current RFC 6238 code for a base32 seed (or
+/// an `otpauth://` URI) usually rendered from another binding.
+#[derive(Default)]
+pub struct Totp {
+    values: Vec<String>,
+}
+
+impl Totp {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, PartialEq)]
+struct Params {
+    key: Vec<u8>,
+    algorithm: Algorithm,
+    digits: u32,
+    period: u64,
+}
+
+fn parse(value: &str) -> Result<Params> {
+    let rest = value.strip_prefix("totp://").unwrap_or(value);
+    let query = rest.trim_start_matches('/').trim_start_matches('?');
+    // A seed rendered unencoded, `secret=otpauth://totp/L?secret=X&issuer=Y`,
+    // runs to the end: the `&` parameters after it are the otpauth URI's.
+    let (query, pasted) = match query
+        .match_indices("secret=otpauth://")
+        .find(|(at, _)| *at == 0 || query[..*at].ends_with('&'))
+    {
+        Some((at, _)) => (
+            &query[..at],
+            Some(query[at + "secret=".len()..].to_string()),
+        ),
+        None => (query, None),
+    };
+    let mut options = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect::<HashMap<_, _>>();
+    let mut secret = pasted
+        .or_else(|| options.remove("secret"))
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| Error::invalid_uri(value, SHAPE))?;
+    // An `otpauth://totp/LABEL?secret=...` URI, as exported by authenticator
+    // apps: its parameters apply unless the totp URI sets them.
+    if let Some((_, otpauth)) = secret
+        .strip_prefix("otpauth://")
+        .and_then(|rest| rest.split_once('?'))
+    {
+        let mut embedded = form_urlencoded::parse(otpauth.as_bytes())
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        for key in ["digits", "period", "algorithm"] {
+            if let Some(option) = embedded.remove(key) {
+                options.entry(key.to_string()).or_insert(option);
+            }
+        }
+        secret = embedded
+            .remove("secret")
+            .ok_or_else(|| Error::invalid_uri(value, "otpauth URI has no secret"))?;
+    }
+    let key = base32_decode(&secret)
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| Error::invalid_uri(value, "totp secret is not valid base32"))?;
+    let algorithm = match options
+        .remove("algorithm")
+        .map(|a| a.to_ascii_uppercase())
+        .as_deref()
+    {
+        None | Some("SHA1") => Algorithm::Sha1,
+        Some("SHA256") => Algorithm::Sha256,
+        Some("SHA512") => Algorithm::Sha512,
+        Some(other) => {
+            return Err(Error::invalid_uri(
+                value,
+                format!("unknown totp algorithm {other}, use SHA1, SHA256 or SHA512"),
+            )
+            .into());
+        }
+    };
+    let digits = match options.remove("digits").map(|d| d.parse::<u32>()) {
+        None => 6,
+        Some(Ok(digits)) if (6..=8).contains(&digits) => digits,
+        Some(_) => {
+            return Err(Error::invalid_uri(value, "totp ?digits= must be 6, 7 or 8").into());
+        }
+    };
+    let period = match options.remove("period").map(|p| p.parse::<u64>()) {
+        None => 30,
+        Some(Ok(period)) if period > 0 => period,
+        Some(_) => {
+            return Err(Error::invalid_uri(
+                value,
+                "totp ?period= must be a positive number of seconds",
+            )
+            .into());
+        }
+    };
+    if let Some(option) = options.keys().next() {
+        return Err(Error::invalid_uri(value, format!("unknown totp option ?{option}=")).into());
+    }
+    Ok(Params {
+        key,
+        algorithm,
+        digits,
+        period,
+    })
+}
+
+/// RFC 4648 base32, as authenticator seeds are written: case, spaces and
+/// padding are ignored.
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let index = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | index;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn hmac<D: EagerHash>(key: &[u8], message: &[u8]) -> Vec<u8>
+where
+    Hmac<D>: KeyInit + Mac,
+{
+    let mut mac = <Hmac<D> as KeyInit>::new_from_slice(key).expect("HMAC accepts any key size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The code of `params` at `unix_time` (RFC 6238, with RFC 4226 truncation).
+fn code(params: &Params, unix_time: u64) -> String {
+    let counter = (unix_time / params.period).to_be_bytes();
+    let digest = match params.algorithm {
+        Algorithm::Sha1 => hmac::<Sha1>(&params.key, &counter),
+        Algorithm::Sha256 => hmac::<Sha256>(&params.key, &counter),
+        Algorithm::Sha512 => hmac::<Sha512>(&params.key, &counter),
+    };
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        truncated % 10u32.pow(params.digits),
+        width = params.digits as usize
+    )
+}
+
+#[async_trait]
+impl Provider for Totp {
+    fn add(&mut self, value: String) -> Result<()> {
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "TOTP"
+    }
+
+    fn install_url(&self) -> &'static str {
+        "https://github.com/zifeo/lade#secrets"
+    }
+
+    fn has_work(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    async fn resolve(
+        &self,
+        _: &Path,
+        _: &HashMap<String, String>,
+        _: &Warnings,
+    ) -> Result<Hydration> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::provider("totp", format!("system clock is invalid: {e}")))?
+            .as_secs();
+        self.values
+            .iter()
+            .map(|value| Ok((value.clone(), code(&parse(value)?, now))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 appendix B seeds, base32 encoded.
+    const SHA1_SEED: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const SHA256_SEED: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+    const SHA512_SEED: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        for (uri, time, expected) in [
+            (
+                format!("totp://?secret={SHA1_SEED}&digits=8"),
+                59,
+                "94287082",
+            ),
+            (
+                format!("totp://?secret={SHA1_SEED}&digits=8"),
+                1111111109,
+                "07081804",
+            ),
+            (
+                format!("totp://?secret={SHA256_SEED}&digits=8&algorithm=SHA256"),
+                59,
+                "46119246",
+            ),
+            (
+                format!("totp://?secret={SHA512_SEED}&digits=8&algorithm=sha512"),
+                20000000000,
+                "47863826",
+            ),
+            (format!("totp://?secret={SHA1_SEED}"), 59, "287082"),
+        ] {
+            assert_eq!(code(&parse(&uri).unwrap(), time), expected, "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_parse_otpauth_and_lenient_seeds() {
+        let spaced =
+            parse("totp://?secret=gezd%20gnbv%20gy3t%20qojq%20gezd%20gnbv%20gy3t%20qojq").unwrap();
+        assert_eq!(spaced.key, b"12345678901234567890");
+        let embedded =
+            format!("otpauth://totp/Deploy:ci?secret={SHA1_SEED}&issuer=Deploy&digits=8&period=60");
+        let params = parse(&format!(
+            "totp://?secret={}&period=30",
+            urlencoding::encode(&embedded)
+        ))
+        .unwrap();
+        assert_eq!(params.key, b"12345678901234567890");
+        assert_eq!(params.digits, 8);
+        assert_eq!(params.period, 30);
+
+        let params = parse(&format!("totp://?period=30&secret={embedded}")).unwrap();
+        assert_eq!(params.key, b"12345678901234567890");
+        assert_eq!(params.digits, 8);
+        assert_eq!(params.period, 30);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_rejects_invalid_uris() {
+        for invalid in [
+            "totp://",
+            "totp://?secret=",
+            "totp://?secret=not-base32!",
+            "totp://?secret=GEZDGNBV&digits=4",
+            "totp://?secret=GEZDGNBV&period=0",
+            "totp://?secret=GEZDGNBV&algorithm=MD5",
+            "totp://?secret=GEZDGNBV&issuer=x",
+        ] {
+            let mut p = Totp::new();
+            p.add(invalid.to_string()).unwrap();
+            let error = p
+                .resolve(Path::new("."), &HashMap::new(), &Warnings::default())
+                .await
+                .unwrap_err();
+            let error = error.downcast_ref::<Error>().unwrap();
+            assert_eq!(error.kind(), "invalid_uri", "{invalid}");
+            assert!(!error.uri().unwrap().contains("GEZDGNBV"));
+        }
+    }
+}
@@ -1,20 +1,33 @@
-use anyhow::{Ok, Result};
+use anyhow::{Ok, Result, anyhow, bail};
 use async_trait::async_trait;
 use futures::future::try_join_all;
 use itertools::Itertools;
 use log::debug;
+use reqwest::{Client, Method, StatusCode};
 use rustc_hash::FxHashMap;
-use serde::Deserialize;
-use std::{collections::HashMap, path::Path, sync::Arc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Mutex,
+};
 use url::Url;
 
-use crate::Hydration;
+use crate::{Error, Hydration, error::redact};
 
-use super::{Provider, Warnings, add_url, deserialize_output, host_with_port, run_cli};
+use super::{
+    Provider, Warnings, add_url, deserialize_output, env_var, file::expand_path, host_with_port,
+    limits::throttle, run_cli, run_cli_with_stdin,
+};
+
+const K8S_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+const INSTALL_URL: &str = "https://developer.hashicorp.com/vault/docs/commands";
 
 #[derive(Default)]
 pub struct Vault {
     urls: FxHashMap<Url, String>,
+    leases: Mutex<Vec<Lease>>,
 }
 
 impl Vault {
@@ -23,14 +36,721 @@ impl Vault {
     }
 }
 
-#[derive(Deserialize)]
-struct VaultGetKVData {
-    data: HashMap<String, String>,
+/// How Lade logs in, from `?auth=`. Without it the `vault` CLI does; with
+/// it Lade calls the HTTP API and reads the credentials from the environment
+/// so they never appear in `lade.yml`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum Auth {
+    /// The `vault` CLI, with its token helper, `vault login` methods and TLS
+    /// settings (`VAULT_CACERT`, `VAULT_SKIP_VERIFY`).
+    Cli,
+    /// `VAULT_TOKEN`, or the `~/.vault-token` left by `vault login`.
+    Token,
+    /// `VAULT_ROLE_ID` and `VAULT_SECRET_ID`.
+    AppRole { mount: String },
+    /// The pod service-account token at `jwt_path`, exchanged for `role`.
+    Kubernetes {
+        mount: String,
+        role: String,
+        jwt_path: String,
+    },
+}
+
+/// Where and how to reach one Vault server, shared by every URI with the
+/// same host and connection query.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Server {
+    pub address: String,
+    pub namespace: Option<String>,
+    pub auth: Auth,
 }
 
-#[derive(Deserialize)]
-struct VaultExport {
-    data: VaultGetKVData,
+/// A dynamic secret lease. Callers keep it alive while the command runs and
+/// revoke it once the command exits.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    pub server: Server,
+    pub id: String,
+    /// Seconds the lease stays valid without renewal.
+    pub duration: u64,
+    pub renewable: bool,
+    /// The environment of the read that issued the lease, with the token it
+    /// used as `VAULT_TOKEN`. Never serialized: `lade unset` runs in another
+    /// process and logs in again from its own environment.
+    #[serde(skip)]
+    pub env: HashMap<String, String>,
+}
+
+// The environment holds the Vault token.
+impl std::fmt::Debug for Lease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lease")
+            .field("server", &self.server)
+            .field("id", &self.id)
+            .field("duration", &self.duration)
+            .field("renewable", &self.renewable)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Lease {
+    pub async fn renew(&self) -> Result<()> {
+        self.put("renew").await
+    }
+
+    pub async fn revoke(&self) -> Result<()> {
+        self.put("revoke").await
+    }
+
+    /// Reuses the token of the read when there is one, so renewals neither
+    /// spend AppRole secret-id uses nor need the credentials in lade's own
+    /// environment.
+    async fn put(&self, action: &str) -> Result<()> {
+        if self.server.auth == Auth::Cli {
+            let cli = Cli {
+                server: &self.server,
+                extra_env: &self.env,
+            };
+            let output = cli.run(&["lease", action], &[&self.id], None).await?;
+            if !output.status.success() {
+                bail!(
+                    "Vault error for lease {}: {}",
+                    self.id,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            return Ok(());
+        }
+        let server = match self.env.contains_key("VAULT_TOKEN") {
+            true => Server {
+                auth: Auth::Token,
+                ..self.server.clone()
+            },
+            false => self.server.clone(),
+        };
+        let session = Session::login(&server, Path::new("."), &self.env).await?;
+        let path = format!("sys/leases/{action}");
+        session
+            .send(
+                Method::PUT,
+                &path,
+                &[],
+                Some(json!({ "lease_id": self.id })),
+            )
+            .await
+            .map_err(|e| anyhow!("{e} (lease {})", self.id))?;
+        Ok(())
+    }
+}
+
+/// A malformed Vault URI, named in the message without its query values.
+fn invalid_uri(url: &Url, message: &str) -> anyhow::Error {
+    Error::invalid_uri(url.as_str(), format!("{message}: {}", redact(url.as_str()))).into()
+}
+
+impl Server {
+    fn from_url(url: &Url, extra_env: &HashMap<String, String>) -> Result<Self> {
+        let mut tls = true;
+        let mut namespace = env_var(extra_env, "VAULT_NAMESPACE");
+        let mut method = None;
+        let mut mount = None;
+        let mut role = None;
+        let mut jwt_path = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "tls" => match value.as_ref() {
+                    "true" => tls = true,
+                    "false" => tls = false,
+                    _ => return Err(invalid_uri(url, "Vault tls must be true or false")),
+                },
+                "namespace" => namespace = Some(value.into_owned()),
+                "auth" => method = Some(value.into_owned()),
+                "auth_mount" => mount = Some(value.into_owned()),
+                "role" => role = Some(value.into_owned()),
+                "jwt_path" => jwt_path = Some(value.into_owned()),
+                // Per secret, see `Read::from_url`.
+                "version" => {}
+                _ => return Err(invalid_uri(url, &format!("Unsupported Vault query {key}"))),
+            }
+        }
+        let auth = match method.as_deref() {
+            None => Auth::Cli,
+            Some("token") => Auth::Token,
+            Some("approle") => Auth::AppRole {
+                mount: mount.unwrap_or_else(|| "approle".to_string()),
+            },
+            Some("kubernetes") => Auth::Kubernetes {
+                mount: mount.unwrap_or_else(|| "kubernetes".to_string()),
+                role: role.ok_or_else(|| invalid_uri(url, "Vault kubernetes auth needs ?role="))?,
+                jwt_path: jwt_path.unwrap_or_else(|| K8S_TOKEN_PATH.to_string()),
+            },
+            Some(other) => {
+                return Err(invalid_uri(url, &format!("Unsupported Vault auth {other}")));
+            }
+        };
+        let scheme = if tls { "https" } else { "http" };
+        Ok(Self {
+            address: format!("{scheme}://{}", host_with_port(url)?),
+            namespace,
+            auth,
+        })
+    }
+}
+
+/// One Vault read. Fields of the same read share a single request, and for
+/// dynamic secrets a single lease, so a username stays paired with its
+/// password.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Read {
+    Kv {
+        mount: String,
+        key: String,
+        version: Option<u64>,
+    },
+    /// Paths longer than `MOUNT/KEY/FIELD`, which is how dynamic engines
+    /// issue credentials (`database/creds/ROLE/username`).
+    Dynamic { path: String },
+}
+
+impl Read {
+    fn from_url(url: &Url) -> Result<(Self, String)> {
+        let segments = url
+            .path()
+            .split('/')
+            .skip(1)
+            .map(|segment| {
+                urlencoding::decode(segment)
+                    .map(|segment| segment.into_owned())
+                    .map_err(|_| invalid_uri(url, "Vault URI is not valid UTF-8"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let Some((field, path)) = segments.split_last() else {
+            return Err(invalid_uri(
+                url,
+                "Vault URI must be vault://HOST/MOUNT/KEY/FIELD",
+            ));
+        };
+        if path.len() < 2 || segments.iter().any(String::is_empty) {
+            return Err(invalid_uri(
+                url,
+                "Vault URI must be vault://HOST/MOUNT/KEY/FIELD",
+            ));
+        }
+        let version = url
+            .query_pairs()
+            .find(|(key, _)| key == "version")
+            .map(|(_, value)| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| invalid_uri(url, "Vault version must be a number"))
+            })
+            .transpose()?;
+        let read = match path {
+            [mount, key] => Read::Kv {
+                mount: mount.clone(),
+                key: key.clone(),
+                version,
+            },
+            _ if version.is_some() => {
+                return Err(invalid_uri(
+                    url,
+                    "Vault ?version= only applies to KV secrets",
+                ));
+            }
+            _ => Read::Dynamic {
+                path: path.join("/"),
+            },
+        };
+        Ok((read, field.clone()))
+    }
+}
+
+/// The lease of a dynamic read, if the engine issued one.
+fn lease(server: &Server, response: &Value, env: HashMap<String, String>) -> Option<Lease> {
+    let id = response["lease_id"].as_str().filter(|id| !id.is_empty())?;
+    Some(Lease {
+        server: server.clone(),
+        id: id.to_string(),
+        duration: response["lease_duration"].as_u64().unwrap_or_default(),
+        renewable: response["renewable"].as_bool().unwrap_or_default(),
+        env,
+    })
+}
+
+fn errors(response: &Value) -> String {
+    response["errors"]
+        .as_array()
+        .map(|errors| errors.iter().filter_map(Value::as_str).join(", "))
+        .unwrap_or_default()
+}
+
+struct Session {
+    http: Client,
+    server: Server,
+    token: Option<String>,
+    extra_env: HashMap<String, String>,
+}
+
+impl Session {
+    async fn login(
+        server: &Server,
+        cwd: &Path,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut http = Client::builder();
+        if let Some(path) = env_var(extra_env, "VAULT_CACERT") {
+            let pem = std::fs::read(expand_path(&path, cwd))
+                .map_err(|e| anyhow!("Vault error reading VAULT_CACERT {path}: {e}"))?;
+            http = http.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+        let mut session = Self {
+            http: http.build()?,
+            server: server.clone(),
+            token: None,
+            extra_env: extra_env.clone(),
+        };
+        let token = match &server.auth {
+            Auth::Cli => bail!("Vault CLI logins are left to `vault login`"),
+            Auth::Token => match env_var(extra_env, "VAULT_TOKEN") {
+                Some(token) => token,
+                None => std::fs::read_to_string(expand_path("~/.vault-token", cwd))
+                    .map(|token| token.trim().to_string())
+                    .map_err(|_| {
+                        anyhow!("Vault token not found: set VAULT_TOKEN or run `vault login`")
+                    })?,
+            },
+            Auth::AppRole { mount } => {
+                let credential = |name| {
+                    env_var(extra_env, name)
+                        .ok_or_else(|| anyhow!("Vault AppRole login needs {name}"))
+                };
+                let body = json!({
+                    "role_id": credential("VAULT_ROLE_ID")?,
+                    "secret_id": credential("VAULT_SECRET_ID")?,
+                });
+                session.login_token(mount, body).await?
+            }
+            Auth::Kubernetes {
+                mount,
+                role,
+                jwt_path,
+            } => {
+                let jwt = std::fs::read_to_string(jwt_path).map_err(|e| {
+                    anyhow!("Vault error reading service-account token {jwt_path}: {e}")
+                })?;
+                let body = json!({ "role": role, "jwt": jwt.trim() });
+                session.login_token(mount, body).await?
+            }
+        };
+        session.token = Some(token);
+        Ok(session)
+    }
+
+    async fn login_token(&self, mount: &str, body: Value) -> Result<String> {
+        let response = self
+            .send(Method::PUT, &format!("auth/{mount}/login"), &[], Some(body))
+            .await?;
+        response["auth"]["client_token"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Vault error: no client token in auth/{mount}/login"))
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<Value>,
+    ) -> Result<Value> {
+        let (status, value) = self.send_raw(method, path, query, body).await?;
+        if !status.is_success() {
+            bail!("Vault error for {path}: {status} {}", errors(&value));
+        }
+        Ok(value)
+    }
+
+    /// A GET of a secret, whose absence is reported as not found for `uri`.
+    async fn get(&self, path: &str, query: &[(&str, String)], uri: &str) -> Result<Value> {
+        let (status, value) = self.send_raw(Method::GET, path, query, None).await?;
+        if status == StatusCode::NOT_FOUND {
+            return Err(Error::not_found(uri, format!("Vault secret {path} not found")).into());
+        }
+        if !status.is_success() {
+            bail!("Vault error for {path}: {status} {}", errors(&value));
+        }
+        Ok(value)
+    }
+
+    /// Like `send`, but leaves the status to the caller.
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<Value>,
+    ) -> Result<(StatusCode, Value)> {
+        let mut url = Url::parse(&self.server.address)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Vault address cannot be a base: {}", self.server.address))?
+            .push("v1")
+            .extend(path.split('/'));
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        debug!("Vault {method} {url}");
+        let mut request = self.http.request(method, url);
+        if let Some(token) = &self.token {
+            request = request.header("X-Vault-Token", token);
+        }
+        if let Some(namespace) = &self.server.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+        let _permit = throttle().await;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("Vault error for {path}: {e}"))?;
+        let status = response.status();
+        let bytes = response.bytes().await?;
+        let value = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice::<Value>(&bytes).map_err(|e| {
+                anyhow!(
+                    "Vault error for {path}: {e} (body: {})",
+                    String::from_utf8_lossy(&bytes)
+                )
+            })?
+        };
+        Ok((status, value))
+    }
+
+    /// KV v1 and v2 share the URI layout; like `vault kv get`, the mount
+    /// tells which API to call.
+    async fn kv_version(&self, mount: &str) -> Result<u8> {
+        let response = self
+            .send(
+                Method::GET,
+                &format!("sys/internal/ui/mounts/{mount}"),
+                &[],
+                None,
+            )
+            .await?;
+        Ok(match response["data"]["options"]["version"].as_str() {
+            Some("2") => 2,
+            _ => 1,
+        })
+    }
+
+    /// Reads `read`, on behalf of `uri`, one of the URIs it serves.
+    async fn read(
+        &self,
+        read: &Read,
+        uri: &str,
+        versions: &HashMap<String, u8>,
+        leases: &Mutex<Vec<Lease>>,
+    ) -> Result<(String, Value)> {
+        Ok(match read {
+            Read::Kv {
+                mount,
+                key,
+                version,
+            } => match (versions[mount], version) {
+                (2, version) => {
+                    let query = version
+                        .map(|version| vec![("version", version.to_string())])
+                        .unwrap_or_default();
+                    let response = self
+                        .get(&format!("{mount}/data/{key}"), &query, uri)
+                        .await?;
+                    (format!("{mount}/{key}"), response["data"]["data"].clone())
+                }
+                (_, Some(_)) => {
+                    return Err(Error::invalid_uri(
+                        uri,
+                        format!("Vault ?version= needs a KV v2 mount: {mount}"),
+                    )
+                    .into());
+                }
+                (_, None) => {
+                    let path = format!("{mount}/{key}");
+                    let response = self.get(&path, &[], uri).await?;
+                    (path, response["data"].clone())
+                }
+            },
+            Read::Dynamic { path } => {
+                let response = self.get(path, &[], uri).await?;
+                let mut env = self.extra_env.clone();
+                env.extend(
+                    self.token
+                        .clone()
+                        .map(|token| ("VAULT_TOKEN".to_string(), token)),
+                );
+                leases
+                    .lock()
+                    .unwrap()
+                    .extend(lease(&self.server, &response, env));
+                (path.clone(), response["data"].clone())
+            }
+        })
+    }
+}
+
+impl Session {
+    /// Sets `field` in a KV secret and keeps its other fields, like
+    /// `vault kv patch -method=rw`: KV v2 writes check-and-set against the
+    /// version that was read, so a concurrent change is not overwritten.
+    async fn write(&self, mount: &str, key: &str, field: &str, value: &str) -> Result<()> {
+        let kv2 = self.kv_version(mount).await? == 2;
+        let path = if kv2 {
+            format!("{mount}/data/{key}")
+        } else {
+            format!("{mount}/{key}")
+        };
+        let (status, current) = self.send_raw(Method::GET, &path, &[], None).await?;
+        // A missing or deleted secret still answers, with its metadata on v2.
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            bail!("Vault error for {path}: {status} {}", errors(&current));
+        }
+        let secret = if kv2 {
+            &current["data"]["data"]
+        } else {
+            &current["data"]
+        };
+        let mut data = secret.as_object().cloned().unwrap_or_default();
+        data.insert(field.to_string(), Value::String(value.to_string()));
+        let body = if kv2 {
+            let cas = current["data"]["metadata"]["version"].as_u64().unwrap_or(0);
+            json!({ "options": { "cas": cas }, "data": data })
+        } else {
+            Value::Object(data)
+        };
+        self.send(Method::POST, &path, &[], Some(body)).await?;
+        Ok(())
+    }
+}
+
+/// Runs the `vault` CLI for servers without `?auth=`.
+struct Cli<'a> {
+    server: &'a Server,
+    extra_env: &'a HashMap<String, String>,
+}
+
+impl Cli<'_> {
+    async fn run(
+        &self,
+        command: &[&str],
+        args: &[&str],
+        stdin: Option<&str>,
+    ) -> Result<std::process::Output> {
+        let address = format!("-address={}", self.server.address);
+        let namespace = self
+            .server
+            .namespace
+            .as_ref()
+            .map(|namespace| format!("-namespace={namespace}"));
+        let cmd = std::iter::once("vault")
+            .chain(command.iter().copied())
+            .chain([address.as_str()])
+            .chain(namespace.as_deref())
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>();
+        debug!("Lade run: {}", cmd.join(" "));
+        match stdin {
+            Some(stdin) => {
+                run_cli_with_stdin(&cmd, stdin, self.extra_env, "Vault", INSTALL_URL).await
+            }
+            None => run_cli(&cmd, self.extra_env, "Vault", INSTALL_URL, None).await,
+        }
+    }
+
+    /// Runs a read of `path` for `uri`, whose absence is reported as not found.
+    async fn json(&self, command: &[&str], args: &[&str], path: &str, uri: &str) -> Result<Value> {
+        let output = self.run(command, args, None).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("No value found") {
+                return Err(Error::not_found(uri, format!("Vault secret {path} not found")).into());
+            }
+            bail!("Vault error for {path}: {}", stderr.trim());
+        }
+        deserialize_output(&output, "Vault")
+    }
+
+    async fn read(
+        &self,
+        read: &Read,
+        uri: &str,
+        leases: &Mutex<Vec<Lease>>,
+    ) -> Result<(String, Value)> {
+        Ok(match read {
+            Read::Kv {
+                mount,
+                key,
+                version,
+            } => {
+                let path = format!("{mount}/{key}");
+                let mount = format!("-mount={mount}");
+                let version = version.map(|version| format!("-version={version}"));
+                let args = [mount.as_str(), "-format=json"]
+                    .into_iter()
+                    .chain(version.as_deref())
+                    .chain([key.as_str()])
+                    .collect::<Vec<_>>();
+                let response = self.json(&["kv", "get"], &args, &path, uri).await?;
+                (path, kv_data(&response).clone())
+            }
+            Read::Dynamic { path } => {
+                let response = self
+                    .json(&["read"], &["-format=json", path], path, uri)
+                    .await?;
+                leases.lock().unwrap().extend(lease(
+                    self.server,
+                    &response,
+                    self.extra_env.clone(),
+                ));
+                (path.clone(), response["data"].clone())
+            }
+        })
+    }
+
+    /// Same merge as `Session::write`, through `vault kv put` with the
+    /// fields on stdin so the value never shows in the process list.
+    async fn write(&self, mount: &str, key: &str, field: &str, value: &str) -> Result<()> {
+        let path = format!("{mount}/{key}");
+        let mount = format!("-mount={mount}");
+        let output = self
+            .run(&["kv", "get"], &[&mount, "-format=json", key], None)
+            .await?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let current = match output.status.success() {
+            true => deserialize_output(&output, "Vault")?,
+            false if stderr.contains("No value found") => Value::Null,
+            false => bail!("Vault error for {path}: {}", stderr.trim()),
+        };
+        let mut data = kv_data(&current).as_object().cloned().unwrap_or_default();
+        data.insert(field.to_string(), Value::String(value.to_string()));
+        let cas = current["data"]["metadata"]["version"]
+            .as_u64()
+            .map(|version| format!("-cas={version}"));
+        let args = [mount.as_str()]
+            .into_iter()
+            .chain(cas.as_deref())
+            .chain([key, "-"])
+            .collect::<Vec<_>>();
+        let output = self
+            .run(
+                &["kv", "put"],
+                &args,
+                Some(&Value::Object(data).to_string()),
+            )
+            .await?;
+        if !output.status.success() {
+            bail!(
+                "Vault error for {path}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `vault kv get` prints KV v2 secrets along with their metadata.
+fn kv_data(response: &Value) -> &Value {
+    match response["data"]["metadata"].is_object() {
+        true => &response["data"]["data"],
+        false => &response["data"],
+    }
+}
+
+impl Vault {
+    async fn resolve_server(
+        &self,
+        server: Server,
+        urls: Vec<(&Url, &String)>,
+        cwd: &Path,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<Hydration> {
+        let mut reads = BTreeMap::<Read, Vec<(String, &String)>>::new();
+        for (url, value) in urls {
+            let (read, field) = Read::from_url(url)?;
+            reads.entry(read).or_default().push((field, value));
+        }
+        let fetched =
+            match server.auth {
+                Auth::Cli => {
+                    let cli = Cli {
+                        server: &server,
+                        extra_env,
+                    };
+                    let cli = &cli;
+                    try_join_all(
+                        reads
+                            .iter()
+                            .map(|(read, fields)| cli.read(read, fields[0].1, &self.leases)),
+                    )
+                    .await?
+                }
+                _ => {
+                    let session = Session::login(&server, cwd, extra_env).await?;
+                    let session = &session;
+                    let mounts = reads
+                        .keys()
+                        .filter_map(|read| match read {
+                            Read::Kv { mount, .. } => Some(mount.as_str()),
+                            Read::Dynamic { .. } => None,
+                        })
+                        .unique()
+                        .map(|mount| async move {
+                            Ok((mount.to_string(), session.kv_version(mount).await?))
+                        });
+                    let versions = try_join_all(mounts)
+                        .await?
+                        .into_iter()
+                        .collect::<HashMap<_, _>>();
+                    let versions = &versions;
+                    try_join_all(reads.iter().map(|(read, fields)| {
+                        session.read(read, fields[0].1, versions, &self.leases)
+                    }))
+                    .await?
+                }
+            };
+        let hydration = reads
+            .into_values()
+            .zip(fetched)
+            .map(|(fields, (path, data))| {
+                let data = serde_json::from_value::<HashMap<String, Value>>(data)
+                    .map_err(|e| anyhow!("Vault error for {path}: no secret data ({e})"))?;
+                fields
+                    .into_iter()
+                    .map(|(field, value)| {
+                        let resolved = match data.get(&field) {
+                            Some(Value::String(s)) => s.clone(),
+                            Some(other) => other.to_string(),
+                            None => {
+                                return Err(Error::not_found(
+                                    value,
+                                    format!("Field {field} not found in Vault {path}"),
+                                )
+                                .into());
+                            }
+                        };
+                        Ok((value.clone(), resolved))
+                    })
+                    .collect::<Result<Hydration>>()
+            })
+            .flatten_ok()
+            .collect::<Result<Hydration>>()?;
+        debug!("hydration: {:?}", hydration);
+        Ok(hydration)
+    }
 }
 
 #[async_trait]
@@ -44,118 +764,103 @@ impl Provider for Vault {
     }
 
     fn install_url(&self) -> &'static str {
-        "https://developer.hashicorp.com/vault/docs/commands"
+        INSTALL_URL
     }
 
     fn has_work(&self) -> bool {
         !self.urls.is_empty()
     }
 
+    fn leases(&self) -> Vec<Lease> {
+        self.leases.lock().unwrap().clone()
+    }
+
     async fn resolve(
         &self,
-        _: &Path,
+        cwd: &Path,
         extra_env: &HashMap<String, String>,
         _: &Warnings,
     ) -> Result<Hydration> {
-        let extra_env = Arc::new(extra_env.clone());
-        let name = self.name();
-        let install_url = self.install_url();
-        let fetches = self
+        let servers = self
             .urls
             .iter()
-            .into_group_map_by(|(url, _)| host_with_port(url))
+            .map(|(url, value)| Ok((Server::from_url(url, extra_env)?, (url, value))))
+            .collect::<Result<Vec<_>>>()?
             .into_iter()
-            .flat_map(|(host, group)| {
-                group
-                    .into_iter()
-                    .into_group_map_by(|(url, _)| {
-                        url.path().split('/').nth(1).expect("Missing project")
-                    })
-                    .into_iter()
-                    .flat_map(|(mount, group)| {
-                        group
-                            .into_iter()
-                            .into_group_map_by(|(url, _)| {
-                                url.path().split('/').nth(2).expect("Missing env")
-                            })
-                            .into_iter()
-                            .map(|(key, group)| {
-                                let host = host.clone();
-                                let extra_env = Arc::clone(&extra_env);
-                                async move {
-                                    let scheme = if std::env::var("LADE_VAULT_HTTP").is_ok() {
-                                        "http"
-                                    } else {
-                                        "https"
-                                    };
-                                    let address_flag = format!("-address={}://{}", scheme, host);
-                                    let cmd = [
-                                        "vault",
-                                        "kv",
-                                        "get",
-                                        &address_flag,
-                                        &format!("-mount={}", mount),
-                                        "-format=json",
-                                        &urlencoding::decode(key)
-                                            .expect("Invalid URL key decoding"),
-                                    ];
-                                    debug!("Lade run: {}", cmd.join(" "));
-                                    let child =
-                                        run_cli(&cmd, &extra_env, name, install_url, None).await?;
-                                    let loaded: VaultExport = deserialize_output(&child, name)?;
-                                    let loaded = loaded.data.data;
-                                    let hydration = group
-                                        .into_iter()
-                                        .map(|(url, value)| {
-                                            let var = url
-                                                .path()
-                                                .split('/')
-                                                .nth(3)
-                                                .expect("Missing variable");
-                                            (
-                                                value.clone(),
-                                                loaded
-                                                    .get(
-                                                        urlencoding::decode(var)
-                                                            .expect("Invalid URL field decoding")
-                                                            .as_ref(),
-                                                    )
-                                                    .unwrap_or_else(|| {
-                                                        panic!(
-                                                            "Variable not found in Vault: {}",
-                                                            key
-                                                        )
-                                                    })
-                                                    .clone(),
-                                            )
-                                        })
-                                        .collect::<Hydration>();
-                                    debug!("hydration: {:?}", hydration);
-                                    Ok(hydration)
-                                }
-                            })
-                            .collect::<Vec<_>>()
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
+            .into_group_map();
+        let fetches = servers
+            .into_iter()
+            .map(|(server, urls)| self.resolve_server(server, urls, cwd, extra_env));
 
         Ok(try_join_all(fetches).await?.into_iter().flatten().collect())
     }
+
+    async fn write(
+        &self,
+        uri: &str,
+        value: &str,
+        cwd: &Path,
+        extra_env: &HashMap<String, String>,
+    ) -> Result<()> {
+        let url = Url::parse(uri).map_err(|e| Error::invalid_uri(uri, e.to_string()))?;
+        let server = Server::from_url(&url, extra_env)?;
+        let (
+            Read::Kv {
+                mount,
+                key,
+                version,
+            },
+            field,
+        ) = Read::from_url(&url)?
+        else {
+            return Err(Error::invalid_uri(
+                uri,
+                format!("Vault can only write KV secrets, not {}", url.path()),
+            )
+            .into());
+        };
+        if version.is_some() {
+            return Err(Error::invalid_uri(
+                uri,
+                format!("Vault cannot write an older ?version= of {mount}/{key}"),
+            )
+            .into());
+        }
+        if server.auth == Auth::Cli {
+            let cli = Cli {
+                server: &server,
+                extra_env,
+            };
+            return cli.write(&mount, &key, &field, value).await;
+        }
+        let session = Session::login(&server, cwd, extra_env).await?;
+        session.write(&mount, &key, &field, value).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::providers::fake_cli;
+    use crate::providers::{fake_cli, stand_in};
     use std::path::Path;
     use tempfile::tempdir;
 
-    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
-        HashMap::from([(
-            "PATH".to_string(),
-            dir.path().to_string_lossy().into_owned(),
-        )])
+    const KV2: (&str, u16, &str) = (
+        "GET /v1/sys/internal/ui/mounts/secret ",
+        200,
+        r#"{"data":{"type":"kv","options":{"version":"2"}}}"#,
+    );
+
+    fn token_env() -> HashMap<String, String> {
+        HashMap::from([("VAULT_TOKEN".to_string(), "root".to_string())])
+    }
+
+    async fn resolve(uris: &[String], env: &HashMap<String, String>) -> Result<Hydration> {
+        let mut p = Vault::new();
+        for uri in uris {
+            p.add(uri.clone()).unwrap();
+        }
+        p.resolve(Path::new("."), env, &Warnings::default()).await
     }
 
     #[test]
@@ -169,91 +874,590 @@ mod tests {
         assert!(p.add("plainvalue".to_string()).is_err());
     }
 
+    #[test]
+    fn test_server_from_url() {
+        let env = HashMap::new();
+        let url = Url::parse("vault://vault.local:8200/secret/app/key").unwrap();
+        let server = Server::from_url(&url, &env).unwrap();
+        assert_eq!(server.address, "https://vault.local:8200");
+        assert_eq!(server.auth, Auth::Cli);
+        let url = Url::parse("vault://vault.local:8200/secret/app/key?auth=token").unwrap();
+        assert_eq!(Server::from_url(&url, &env).unwrap().auth, Auth::Token);
+
+        let url = Url::parse(
+            "vault://localhost/secret/app/key?tls=false&namespace=team&auth=kubernetes&role=app",
+        )
+        .unwrap();
+        let server = Server::from_url(&url, &env).unwrap();
+        assert_eq!(server.address, "http://localhost");
+        assert_eq!(server.namespace.as_deref(), Some("team"));
+        assert_eq!(
+            server.auth,
+            Auth::Kubernetes {
+                mount: "kubernetes".into(),
+                role: "app".into(),
+                jwt_path: K8S_TOKEN_PATH.into(),
+            }
+        );
+
+        for uri in [
+            "vault://localhost/secret/app/key?tls=no",
+            "vault://localhost/secret/app/key?auth=kubernetes",
+            "vault://localhost/secret/app/key?auth=ldap",
+            "vault://localhost/secret/app/key?mount=x",
+        ] {
+            assert!(Server::from_url(&Url::parse(uri).unwrap(), &env).is_err());
+        }
+    }
+
+    #[test]
+    fn test_read_from_url() {
+        let url = Url::parse("vault://localhost/secret/org%2Fteam/value?version=3").unwrap();
+        let (read, field) = Read::from_url(&url).unwrap();
+        assert!(
+            read == Read::Kv {
+                mount: "secret".into(),
+                key: "org/team".into(),
+                version: Some(3),
+            }
+        );
+        assert_eq!(field, "value");
+
+        let url = Url::parse("vault://localhost/database/creds/app/username").unwrap();
+        let (read, field) = Read::from_url(&url).unwrap();
+        assert!(
+            read == Read::Dynamic {
+                path: "database/creds/app".into()
+            }
+        );
+        assert_eq!(field, "username");
+
+        for uri in [
+            "vault://localhost/secret/password",
+            "vault://localhost/database/creds/app/username?version=1",
+        ] {
+            assert!(Read::from_url(&Url::parse(uri).unwrap()).is_err());
+        }
+    }
+
     #[tokio::test]
-    #[cfg(unix)]
-    async fn test_resolve_single_field() {
-        let fake_bin = tempdir().unwrap();
-        fake_cli(
-            &fake_bin,
-            "vault",
-            r#"echo '{"data":{"data":{"password":"s3cret"}}}'"#,
+    async fn test_resolve_kv2_fields_share_one_request() {
+        let (address, requests) = stand_in(vec![
+            KV2,
+            (
+                "GET /v1/secret/data/myapp ",
+                200,
+                r#"{"data":{"data":{"password":"s3cret","api_key":"key123"}}}"#,
+            ),
+        ])
+        .await;
+        let password = format!("vault://{address}/secret/myapp/password?tls=false&auth=token");
+        let api_key = format!("vault://{address}/secret/myapp/api_key?tls=false&auth=token");
+        let result = resolve(&[password.clone(), api_key.clone()], &token_env())
+            .await
+            .unwrap();
+        assert_eq!(result.get(&password).unwrap(), "s3cret");
+        assert_eq!(result.get(&api_key).unwrap(), "key123");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.to_lowercase().contains("x-vault-token: root"))
         );
-        let mut p = Vault::new();
-        p.add("vault://localhost/secret/myapp/password".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_kv2_version_and_namespace() {
+        let (address, requests) = stand_in(vec![
+            KV2,
+            (
+                "GET /v1/secret/data/myapp?version=2 ",
+                200,
+                r#"{"data":{"data":{"password":"older"}}}"#,
+            ),
+        ])
+        .await;
+        let uri = format!(
+            "vault://{address}/secret/myapp/password?tls=false&auth=token&version=2&namespace=team"
+        );
+        let result = resolve(std::slice::from_ref(&uri), &token_env())
+            .await
             .unwrap();
+        assert_eq!(result.get(&uri).unwrap(), "older");
+        assert!(
+            requests
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|r| r.to_lowercase().contains("x-vault-namespace: team"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_kv1() {
+        let (address, _) = stand_in(vec![
+            (
+                "GET /v1/sys/internal/ui/mounts/kv ",
+                200,
+                r#"{"data":{"type":"kv","options":null}}"#,
+            ),
+            (
+                "GET /v1/kv/myapp ",
+                200,
+                r#"{"data":{"password":"v1-secret","port":5432}}"#,
+            ),
+        ])
+        .await;
+        let password = format!("vault://{address}/kv/myapp/password?tls=false&auth=token");
+        let port = format!("vault://{address}/kv/myapp/port?tls=false&auth=token");
+        let result = resolve(&[password.clone(), port.clone()], &token_env())
+            .await
+            .unwrap();
+        assert_eq!(result.get(&password).unwrap(), "v1-secret");
+        assert_eq!(result.get(&port).unwrap(), "5432");
+
+        let versioned =
+            format!("vault://{address}/kv/myapp/password?tls=false&auth=token&version=1");
+        let err = resolve(&[versioned], &token_env()).await.unwrap_err();
+        assert!(err.to_string().contains("needs a KV v2 mount"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_approle_login() {
+        let (address, requests) = stand_in(vec![
+            (
+                "PUT /v1/auth/approle/login ",
+                200,
+                r#"{"auth":{"client_token":"from-approle"}}"#,
+            ),
+            KV2,
+            (
+                "GET /v1/secret/data/myapp ",
+                200,
+                r#"{"data":{"data":{"password":"s3cret"}}}"#,
+            ),
+        ])
+        .await;
+        let uri = format!("vault://{address}/secret/myapp/password?tls=false&auth=approle");
+        let env = HashMap::from([
+            ("VAULT_ROLE_ID".to_string(), "role".to_string()),
+            ("VAULT_SECRET_ID".to_string(), "secret".to_string()),
+        ]);
+        let result = resolve(std::slice::from_ref(&uri), &env).await.unwrap();
+        assert_eq!(result.get(&uri).unwrap(), "s3cret");
+        {
+            let requests = requests.lock().unwrap();
+            assert!(requests[0].contains(r#""role_id":"role""#));
+            assert!(requests[0].contains(r#""secret_id":"secret""#));
+            assert!(
+                requests[1]
+                    .to_lowercase()
+                    .contains("x-vault-token: from-approle")
+            );
+        }
+
+        let err = resolve(std::slice::from_ref(&uri), &HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("needs VAULT_ROLE_ID"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_kubernetes_login() {
+        let (address, requests) = stand_in(vec![
+            (
+                "PUT /v1/auth/k8s-prod/login ",
+                200,
+                r#"{"auth":{"client_token":"from-k8s"}}"#,
+            ),
+            KV2,
+            (
+                "GET /v1/secret/data/myapp ",
+                200,
+                r#"{"data":{"data":{"password":"s3cret"}}}"#,
+            ),
+        ])
+        .await;
+        let dir = tempdir().unwrap();
+        let jwt = dir.path().join("token");
+        std::fs::write(&jwt, "eyJhbGciOi.jwt\n").unwrap();
+        let uri = format!(
+            "vault://{address}/secret/myapp/password?tls=false&auth=kubernetes&auth_mount=k8s-prod&role=app&jwt_path={}",
+            jwt.display()
+        );
+        let result = resolve(std::slice::from_ref(&uri), &HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(result.get(&uri).unwrap(), "s3cret");
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains(r#""jwt":"eyJhbGciOi.jwt""#));
+        assert!(requests[0].contains(r#""role":"app""#));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_dynamic_pairs_fields_from_one_lease() {
+        let (address, requests) = stand_in(vec![(
+            "GET /v1/database/creds/app ",
+            200,
+            r#"{"lease_id":"database/creds/app/abc","lease_duration":3600,"renewable":true,"data":{"username":"v-app-1","password":"p4ss"}}"#,
+        )])
+        .await;
+        let username =
+            format!("vault://{address}/database/creds/app/username?tls=false&auth=token");
+        let password =
+            format!("vault://{address}/database/creds/app/password?tls=false&auth=token");
+        let mut p = Vault::new();
+        p.add(username.clone()).unwrap();
+        p.add(password.clone()).unwrap();
         let result = p
-            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .resolve(Path::new("."), &token_env(), &Warnings::default())
             .await
             .unwrap();
+        assert_eq!(result.get(&username).unwrap(), "v-app-1");
+        assert_eq!(result.get(&password).unwrap(), "p4ss");
+        assert_eq!(requests.lock().unwrap().len(), 1);
         assert_eq!(
-            result
-                .get("vault://localhost/secret/myapp/password")
-                .unwrap(),
-            "s3cret"
+            p.leases(),
+            vec![Lease {
+                server: Server {
+                    address: format!("http://{address}"),
+                    namespace: None,
+                    auth: Auth::Token,
+                },
+                id: "database/creds/app/abc".to_string(),
+                duration: 3600,
+                renewable: true,
+                env: token_env(),
+            }]
         );
     }
 
+    #[tokio::test]
+    async fn test_resolve_reports_vault_errors() {
+        let (address, _) = stand_in(vec![
+            KV2,
+            (
+                "GET /v1/secret/data/denied ",
+                403,
+                r#"{"errors":["permission denied"]}"#,
+            ),
+            (
+                "GET /v1/secret/data/myapp ",
+                200,
+                r#"{"data":{"data":{"password":"s3cret"}}}"#,
+            ),
+        ])
+        .await;
+        let denied = format!("vault://{address}/secret/denied/password?tls=false&auth=token");
+        let err = resolve(&[denied], &token_env()).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Vault error for secret/data/denied")
+        );
+        assert!(err.to_string().contains("permission denied"));
+
+        let missing = format!("vault://{address}/secret/myapp/missing?tls=false&auth=token");
+        let err = resolve(&[missing], &token_env()).await.unwrap_err();
+        assert!(err.to_string().contains("Field missing not found"));
+    }
+
+    #[tokio::test]
+    async fn test_lease_revoke_reuses_the_token_of_the_read() {
+        let (address, requests) = stand_in(vec![("PUT /v1/sys/leases/revoke ", 204, "")]).await;
+        let lease = Lease {
+            server: Server {
+                address: format!("http://{address}"),
+                namespace: None,
+                auth: Auth::AppRole {
+                    mount: "approle".to_string(),
+                },
+            },
+            id: "database/creds/app/abc".to_string(),
+            duration: 3600,
+            renewable: true,
+            env: token_env(),
+        };
+        lease.revoke().await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains(r#""lease_id":"database/creds/app/abc""#));
+        assert!(!format!("{lease:?}").contains("root"));
+    }
+
+    fn body(request: &str) -> Value {
+        serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_write_kv2_keeps_other_fields_and_checks_the_version() {
+        let (address, requests) = stand_in(vec![
+            KV2,
+            (
+                "GET /v1/secret/data/myapp ",
+                200,
+                r#"{"data":{"data":{"password":"old","api_key":"key123"},"metadata":{"version":3}}}"#,
+            ),
+            ("POST /v1/secret/data/myapp ", 200, r#"{"data":{"version":4}}"#),
+            ("GET /v1/secret/data/fresh ", 404, r#"{"errors":[]}"#),
+            ("POST /v1/secret/data/fresh ", 200, r#"{"data":{"version":1}}"#),
+        ])
+        .await;
+        for key in ["myapp", "fresh"] {
+            Vault::new()
+                .write(
+                    &format!("vault://{address}/secret/{key}/password?tls=false&auth=token"),
+                    "n3w",
+                    Path::new("."),
+                    &token_env(),
+                )
+                .await
+                .unwrap();
+        }
+        let posts = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.starts_with("POST"))
+            .map(|r| body(r))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            posts,
+            [
+                json!({"data": {"api_key": "key123", "password": "n3w"}, "options": {"cas": 3}}),
+                json!({"data": {"password": "n3w"}, "options": {"cas": 0}}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_kv1_and_rejects_dynamic_paths() {
+        let (address, requests) = stand_in(vec![
+            (
+                "GET /v1/sys/internal/ui/mounts/kv ",
+                200,
+                r#"{"data":{"type":"kv","options":null}}"#,
+            ),
+            ("GET /v1/kv/myapp ", 200, r#"{"data":{"port":"5432"}}"#),
+            ("POST /v1/kv/myapp ", 204, ""),
+        ])
+        .await;
+        Vault::new()
+            .write(
+                &format!("vault://{address}/kv/myapp/password?tls=false&auth=token"),
+                "v1-secret",
+                Path::new("."),
+                &token_env(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            body(requests.lock().unwrap().last().unwrap()),
+            json!({"port": "5432", "password": "v1-secret"})
+        );
+
+        let err = Vault::new()
+            .write(
+                &format!("vault://{address}/database/creds/app/password?tls=false&auth=token"),
+                "x",
+                Path::new("."),
+                &token_env(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("can only write KV secrets"));
+    }
+
+    fn path_env(dir: &tempfile::TempDir) -> HashMap<String, String> {
+        HashMap::from([(
+            "PATH".to_string(),
+            dir.path().to_string_lossy().into_owned(),
+        )])
+    }
+
+    /// A fake `vault` that records its arguments and prints `output`.
+    fn recording_cli(dir: &tempfile::TempDir, output: &str) -> std::path::PathBuf {
+        let calls = dir.path().join("calls");
+        fake_cli(
+            dir,
+            "vault",
+            &format!("echo \"$@\" >> {}\necho '{output}'", calls.display()),
+        );
+        calls
+    }
+
     #[tokio::test]
     #[cfg(unix)]
-    async fn test_resolve_multiple_fields_same_key() {
+    async fn test_cli_resolve_kv_fields_share_one_call() {
         let fake_bin = tempdir().unwrap();
-        fake_cli(
+        let calls = recording_cli(
             &fake_bin,
-            "vault",
-            r#"echo '{"data":{"data":{"password":"s3cret","api_key":"key123"}}}'"#,
+            r#"{"data":{"data":{"password":"s3cret","api_key":"key123"},"metadata":{"version":3}}}"#,
         );
-        let mut p = Vault::new();
-        p.add("vault://localhost/secret/myapp/password".to_string())
-            .unwrap();
-        p.add("vault://localhost/secret/myapp/api_key".to_string())
-            .unwrap();
-        let result = p
-            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+        let password = "vault://localhost/secret/myapp/password".to_string();
+        let api_key = "vault://localhost/secret/myapp/api_key".to_string();
+        let result = resolve(&[password.clone(), api_key.clone()], &path_env(&fake_bin))
             .await
             .unwrap();
+        assert_eq!(result.get(&password).unwrap(), "s3cret");
+        assert_eq!(result.get(&api_key).unwrap(), "key123");
         assert_eq!(
-            result
-                .get("vault://localhost/secret/myapp/password")
-                .unwrap(),
-            "s3cret"
+            std::fs::read_to_string(calls).unwrap().trim(),
+            "kv get -address=https://localhost -mount=secret -format=json myapp"
         );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_cli_resolve_kv1_version_and_namespace() {
+        let fake_bin = tempdir().unwrap();
+        let calls = recording_cli(&fake_bin, r#"{"data":{"password":"v1-secret"}}"#);
+        let uri = "vault://localhost/kv/org%2Fteam/password?tls=false&version=2&namespace=team"
+            .to_string();
+        let result = resolve(std::slice::from_ref(&uri), &path_env(&fake_bin))
+            .await
+            .unwrap();
+        assert_eq!(result.get(&uri).unwrap(), "v1-secret");
         assert_eq!(
-            result
-                .get("vault://localhost/secret/myapp/api_key")
-                .unwrap(),
-            "key123"
+            std::fs::read_to_string(calls).unwrap().trim(),
+            "kv get -address=http://localhost -namespace=team -mount=kv -format=json -version=2 org/team"
         );
     }
 
     #[tokio::test]
     #[cfg(unix)]
-    async fn test_resolve_cli_not_found() {
-        let empty_bin = tempdir().unwrap();
+    async fn test_cli_dynamic_lease_is_revoked_through_the_cli() {
+        let fake_bin = tempdir().unwrap();
+        let calls = recording_cli(
+            &fake_bin,
+            r#"{"lease_id":"database/creds/app/abc","lease_duration":3600,"renewable":true,"data":{"username":"v-app-1","password":"p4ss"}}"#,
+        );
+        let username = "vault://localhost/database/creds/app/username".to_string();
+        let password = "vault://localhost/database/creds/app/password".to_string();
         let mut p = Vault::new();
-        p.add("vault://localhost/secret/myapp/password".to_string())
-            .unwrap();
+        p.add(username.clone()).unwrap();
+        p.add(password.clone()).unwrap();
         let result = p
-            .resolve(Path::new("."), &path_env(&empty_bin), &Warnings::default())
-            .await;
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("Vault CLI not found")
+            .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
+            .await
+            .unwrap();
+        assert_eq!(result.get(&username).unwrap(), "v-app-1");
+        assert_eq!(result.get(&password).unwrap(), "p4ss");
+        let leases = p.leases();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].server.auth, Auth::Cli);
+        leases[0].revoke().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(calls).unwrap(),
+            "read -address=https://localhost -format=json database/creds/app\n\
+             lease revoke -address=https://localhost database/creds/app/abc\n"
         );
     }
 
     #[tokio::test]
     #[cfg(unix)]
-    async fn test_resolve_malformed_json_error() {
+    async fn test_cli_failure_reports_stderr() {
         let fake_bin = tempdir().unwrap();
-        fake_cli(&fake_bin, "vault", "echo 'not valid json'");
+        fake_cli(
+            &fake_bin,
+            "vault",
+            "echo 'Error reading database/creds/app: permission denied' >&2\nexit 2",
+        );
         let mut p = Vault::new();
-        p.add("vault://localhost/secret/myapp/password".to_string())
+        p.add("vault://localhost/database/creds/app/username".to_string())
             .unwrap();
-        let result = p
+        let err = p
             .resolve(Path::new("."), &path_env(&fake_bin), &Warnings::default())
-            .await;
-        assert!(result.unwrap_err().to_string().contains("Vault error"));
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Vault error for database/creds/app"));
+        assert!(err.contains("permission denied"));
+        assert!(p.leases().is_empty());
+
+        let empty_bin = tempdir().unwrap();
+        let uri = "vault://localhost/secret/myapp/password".to_string();
+        let err = resolve(&[uri], &path_env(&empty_bin)).await.unwrap_err();
+        assert!(err.to_string().contains("Vault CLI not found"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_missing_fields_and_bad_uris_are_typed() {
+        let fake_bin = tempdir().unwrap();
+        recording_cli(&fake_bin, r#"{"data":{"data":{"password":"s3cret"}}}"#);
+        let uri = "vault://localhost/secret/myapp/api_key".to_string();
+        let error = resolve(std::slice::from_ref(&uri), &path_env(&fake_bin))
+            .await
+            .unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "not_found");
+
+        fake_cli(
+            &fake_bin,
+            "vault",
+            "echo 'No value found at secret/data/myapp' >&2\nexit 2",
+        );
+        let error = resolve(std::slice::from_ref(&uri), &path_env(&fake_bin))
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<Error>().unwrap();
+        assert_eq!(error.kind(), "not_found");
+        assert_eq!(error.uri(), Some(uri.as_str()));
+
+        let uri = Url::parse("vault://localhost/secret/myapp/password?tls=s3cret").unwrap();
+        let error = Server::from_url(&uri, &HashMap::new()).unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), "invalid_uri");
+        assert!(!error.to_string().contains("s3cret"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_cli_write_sends_merged_fields_on_stdin() {
+        let fake_bin = tempdir().unwrap();
+        let calls = fake_bin.path().join("calls");
+        let stdin = fake_bin.path().join("stdin");
+        fake_cli(
+            &fake_bin,
+            "vault",
+            &format!(
+                r#"echo "$@" >> {calls}
+case "$2" in
+  get) case "$6" in
+    myapp) echo '{{"data":{{"data":{{"password":"old","api_key":"key123"}},"metadata":{{"version":3}}}}}}' ;;
+    *) echo "No value found at secret/data/$6" >&2; exit 2 ;;
+  esac ;;
+  put) read -r body; echo "$body" >> {stdin} ;;
+esac"#,
+                calls = calls.display(),
+                stdin = stdin.display(),
+            ),
+        );
+        for key in ["myapp", "fresh"] {
+            Vault::new()
+                .write(
+                    &format!("vault://localhost/secret/{key}/password"),
+                    "n3w",
+                    Path::new("."),
+                    &path_env(&fake_bin),
+                )
+                .await
+                .unwrap();
+        }
+        let calls = std::fs::read_to_string(calls).unwrap();
+        assert!(calls.contains("kv put -address=https://localhost -mount=secret -cas=3 myapp -"));
+        assert!(calls.contains("kv put -address=https://localhost -mount=secret fresh -"));
+        assert!(!calls.contains("n3w"));
+        let bodies = std::fs::read_to_string(stdin)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bodies,
+            [
+                json!({"password": "n3w", "api_key": "key123"}),
+                json!({"password": "n3w"}),
+            ]
+        );
     }
 }
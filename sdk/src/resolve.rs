@@ -1,18 +1,31 @@
-use std::collections::{BTreeSet, HashMap, VecDeque};
-
-use anyhow::{Result, bail};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fmt,
+};
+
+use access_json::JSONQuery;
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use sha2::{Digest, Sha256};
 
 static VAR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$(?:\{(\w+)\}|(\w+))").unwrap());
 static TEMPLATE_VAR: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"\$(?:\{(\.?[A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))").unwrap()
+    Regex::new(r"\$(?:\{(\.?[A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\}|([A-Za-z_][A-Za-z0-9_]*))")
+        .unwrap()
+});
+static TRANSFORM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\s*\|\s*(base64decode|base64encode|trim|urlencode|sha256|jsonpath\(([^)]*)\)|lines\(([^)]*)\))\s*$")
+        .unwrap()
 });
 
 /// A source value compiled once for dependency discovery and interpolation.
 ///
 /// Only braced references form graph edges. This intentionally leaves `$NAME`
 /// intact for shell-backed providers, where the shell owns that syntax.
+/// `${NAME:-fallback}` renders `fallback` when `NAME` has no value.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Template {
     parts: Vec<TemplatePart>,
@@ -25,6 +38,7 @@ enum TemplatePart {
         name: String,
         braced: bool,
         private: bool,
+        default: Option<String>,
     },
 }
 
@@ -42,13 +56,14 @@ impl Template {
             let braced = captures.get(1).is_some();
             let raw_name = captures
                 .get(1)
-                .or_else(|| captures.get(2))
+                .or_else(|| captures.get(3))
                 .expect("reference name")
                 .as_str();
             parts.push(TemplatePart::Reference {
                 name: raw_name.trim_start_matches('.').to_string(),
                 braced,
                 private: raw_name.starts_with('.'),
+                default: captures.get(2).map(|default| default.as_str().to_string()),
             });
             offset = matched.end();
         }
@@ -58,6 +73,18 @@ impl Template {
         Self { parts }
     }
 
+    /// Planning template for a fallback chain: it depends on every
+    /// alternative, since any of them may end up being resolved. Render the
+    /// alternatives themselves, not this.
+    pub fn chain(alternatives: &[Template]) -> Self {
+        Self {
+            parts: alternatives
+                .iter()
+                .flat_map(|template| template.parts.iter().cloned())
+                .collect(),
+        }
+    }
+
     pub fn dependencies(&self) -> impl Iterator<Item = &str> {
         self.parts.iter().filter_map(|part| match part {
             TemplatePart::Reference { name, .. } => Some(name.as_str()),
@@ -65,13 +92,31 @@ impl Template {
         })
     }
 
+    /// References without a `:-` default, which cannot render without a
+    /// value.
+    pub fn required_dependencies(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            TemplatePart::Reference {
+                name,
+                default: None,
+                ..
+            } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
     pub fn render(&self, values: &HashMap<String, String>) -> Result<String> {
         let mut output = String::new();
         for part in &self.parts {
             match part {
                 TemplatePart::Literal(value) => output.push_str(value),
-                TemplatePart::Reference { name, braced, .. } => {
-                    if let Some(value) = values.get(name) {
+                TemplatePart::Reference {
+                    name,
+                    braced,
+                    default,
+                    ..
+                } => {
+                    if let Some(value) = values.get(name).or(default.as_ref()) {
                         output.push_str(value);
                     } else if *braced {
                         bail!("missing dependency '{name}'");
@@ -94,10 +139,15 @@ impl Template {
                     name,
                     braced,
                     private,
+                    default,
                 } => {
                     if *braced || *private {
                         output.push_str("${");
                         output.push_str(name);
+                        if let Some(default) = default {
+                            output.push_str(":-");
+                            output.push_str(default);
+                        }
                         output.push('}');
                     } else {
                         output.push('$');
@@ -126,11 +176,17 @@ impl Dag {
         for (name, template) in &templates {
             let mut dependencies = BTreeSet::new();
             for part in &template.parts {
-                let TemplatePart::Reference { name, braced, .. } = part else {
+                let TemplatePart::Reference {
+                    name,
+                    braced,
+                    default,
+                    ..
+                } = part
+                else {
                     continue;
                 };
                 if !templates.contains_key(name) {
-                    if !braced {
+                    if !braced || default.is_some() {
                         continue;
                     }
                     bail!("binding '{name}' references missing dependency '{name}'");
@@ -207,6 +263,132 @@ impl Dag {
     }
 }
 
+/// A step applied to a resolved value, chained onto its source with ` | `,
+/// e.g. `op://vault/item/cert | base64decode | trim`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transform {
+    Base64Decode,
+    Base64Encode,
+    Trim,
+    UrlEncode,
+    Sha256,
+    JsonPath(String),
+    /// 1-based line number.
+    Lines(usize),
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::Base64Decode => write!(f, "base64decode"),
+            Transform::Base64Encode => write!(f, "base64encode"),
+            Transform::Trim => write!(f, "trim"),
+            Transform::UrlEncode => write!(f, "urlencode"),
+            Transform::Sha256 => write!(f, "sha256"),
+            Transform::JsonPath(query) => write!(f, "jsonpath({query})"),
+            Transform::Lines(n) => write!(f, "lines({n})"),
+        }
+    }
+}
+
+impl Transform {
+    pub fn apply(&self, value: String) -> Result<String> {
+        Ok(match self {
+            Transform::Base64Decode => {
+                String::from_utf8(STANDARD.decode(value.trim())?).context("not UTF-8")?
+            }
+            Transform::Base64Encode => STANDARD.encode(value),
+            Transform::Trim => value.trim().to_string(),
+            Transform::UrlEncode => urlencoding::encode(&value).into_owned(),
+            Transform::Sha256 => Sha256::digest(value.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            Transform::JsonPath(query) => {
+                let json = serde_json::from_str::<serde_json::Value>(&value)?;
+                let compiled =
+                    JSONQuery::parse(query).map_err(|e| anyhow!("invalid query {query}: {e:?}"))?;
+                match compiled
+                    .execute(&json)
+                    .map_err(|e| anyhow!("query {query} failed: {e:?}"))?
+                {
+                    Some(serde_json::Value::String(value)) => value,
+                    Some(value) => value.to_string(),
+                    None => bail!("no result for {query}"),
+                }
+            }
+            Transform::Lines(n) => value
+                .lines()
+                .nth(n - 1)
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("line {n} not found"))?,
+        })
+    }
+}
+
+/// Splits trailing ` | transform` stages off `source`, returning the part a
+/// provider resolves and the transforms in application order. Only known
+/// transform names are split, so pipes inside `sh://` commands stay intact,
+/// and only off provider URIs: literals, `!`-escaped or not, are kept as is.
+pub fn split_transforms(source: &str) -> Result<(&str, Vec<Transform>)> {
+    let is_uri = source.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if !is_uri {
+        return Ok((source, Vec::new()));
+    }
+    split_stages(source)
+}
+
+/// Splits the stages off a template that renders to a literal, such as
+/// `${TOKEN} | base64decode`, before it is rendered so that a referenced
+/// value is never read as a stage. Other values are left to
+/// [`split_transforms`] and come back whole.
+pub fn split_template_transforms(template: &str) -> Result<(&str, Vec<Transform>)> {
+    match template.contains("://") || template.starts_with('!') || !VAR.is_match(template) {
+        true => Ok((template, Vec::new())),
+        false => split_stages(template),
+    }
+}
+
+fn split_stages(source: &str) -> Result<(&str, Vec<Transform>)> {
+    let mut rest = source;
+    let mut transforms = Vec::new();
+    while let Some(captures) = TRANSFORM.captures(rest) {
+        let stage = captures.get(1).expect("transform name").as_str();
+        let transform = match stage {
+            "base64decode" => Transform::Base64Decode,
+            "base64encode" => Transform::Base64Encode,
+            "trim" => Transform::Trim,
+            "urlencode" => Transform::UrlEncode,
+            "sha256" => Transform::Sha256,
+            _ if stage.starts_with("jsonpath") => {
+                Transform::JsonPath(captures[2].trim().to_string())
+            }
+            _ => match captures[3].trim().parse::<usize>() {
+                Ok(n) if n > 0 => Transform::Lines(n),
+                _ => bail!("{stage}: line must be a positive number"),
+            },
+        };
+        transforms.push(transform);
+        rest = &rest[..captures.get(0).expect("transform match").start()];
+    }
+    transforms.reverse();
+    Ok((rest, transforms))
+}
+
+/// Applies `transforms` in order, naming the failing stage and `source`.
+pub fn apply_transforms(source: &str, value: String, transforms: &[Transform]) -> Result<String> {
+    transforms.iter().try_fold(value, |value, transform| {
+        transform
+            .apply(value)
+            .with_context(|| format!("{transform} failed for {source}"))
+    })
+}
+
 pub fn resolve(
     kvs: &HashMap<String, String>,
     existing_vars: &HashMap<String, String>,
@@ -356,6 +538,39 @@ mod tests {
         assert_eq!(template.shell_source(), "sh://echo ${TOKEN}");
     }
 
+    #[test]
+    fn template_renders_default_for_missing_value() {
+        let template = Template::parse("Bearer ${TOKEN:-anonymous} ${.SUFFIX:-}");
+        assert_eq!(
+            template.dependencies().collect::<Vec<_>>(),
+            vec!["TOKEN", "SUFFIX"]
+        );
+        assert_eq!(template.required_dependencies().count(), 0);
+        assert_eq!(
+            template.render(&HashMap::new()).unwrap(),
+            "Bearer anonymous "
+        );
+        assert_eq!(
+            template
+                .render(&HashMap::from([("TOKEN".into(), "value".into())]))
+                .unwrap(),
+            "Bearer value "
+        );
+        assert_eq!(
+            template.shell_source(),
+            "Bearer ${TOKEN:-anonymous} ${SUFFIX:-}"
+        );
+    }
+
+    #[test]
+    fn dag_allows_missing_dependency_with_default() {
+        Dag::new(HashMap::from([(
+            "HEADER".into(),
+            Template::parse("Bearer ${TOKEN:-anonymous}"),
+        )]))
+        .unwrap();
+    }
+
     #[test]
     fn dag_rejects_missing_dependency() {
         let err = Dag::new(HashMap::from([(
@@ -375,4 +590,68 @@ mod tests {
         .unwrap_err();
         assert!(err.to_string().contains("cyclic binding dependencies"));
     }
+
+    #[test]
+    fn split_transforms_keeps_shell_pipes() {
+        let (source, transforms) =
+            split_transforms("sh://cat cert | tr -d x | base64decode | trim").unwrap();
+        assert_eq!(source, "sh://cat cert | tr -d x");
+        assert_eq!(transforms, vec![Transform::Base64Decode, Transform::Trim]);
+
+        let (source, transforms) =
+            split_transforms("op://v/i/f|jsonpath(.data.token) | lines(2)").unwrap();
+        assert_eq!(source, "op://v/i/f");
+        assert_eq!(
+            transforms,
+            vec![
+                Transform::JsonPath(".data.token".to_string()),
+                Transform::Lines(2)
+            ]
+        );
+
+        assert_eq!(split_transforms("plain").unwrap(), ("plain", vec![]));
+        assert!(split_transforms("op://v/i/f | lines(0)").is_err());
+    }
+
+    #[test]
+    fn split_transforms_keeps_literals() {
+        for literal in ["a | trim", "!op://v/i/f | base64decode", "${A} | trim"] {
+            assert_eq!(split_transforms(literal).unwrap(), (literal, vec![]));
+        }
+        assert_eq!(
+            split_template_transforms("${A} | trim").unwrap(),
+            ("${A}", vec![Transform::Trim])
+        );
+        for kept in ["a | trim", "!${A} | trim", "op://v/${A} | trim"] {
+            assert_eq!(split_template_transforms(kept).unwrap(), (kept, vec![]));
+        }
+    }
+
+    #[test]
+    fn transforms_apply_in_order() {
+        let apply = |source: &str, value: &str| {
+            let (source, transforms) = split_stages(source).unwrap();
+            apply_transforms(source, value.to_string(), &transforms)
+        };
+        assert_eq!(apply("x | base64decode | trim", "IGhpIAo=").unwrap(), "hi");
+        assert_eq!(apply("x | base64encode", "hi").unwrap(), "aGk=");
+        assert_eq!(apply("x | urlencode", "a b&c").unwrap(), "a%20b%26c");
+        assert_eq!(
+            apply("x | sha256", "abc").unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            apply("x | jsonpath(.data.token)", r#"{"data":{"token":"t"}}"#).unwrap(),
+            "t"
+        );
+        assert_eq!(apply("x | jsonpath(.n)", r#"{"n":1}"#).unwrap(), "1");
+        assert_eq!(apply("x | lines(2)", "a\nb\nc").unwrap(), "b");
+        let err = apply("x | lines(4)", "a\nb").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "lines(4) failed for x: line 4 not found"
+        );
+        assert!(apply("x | jsonpath(.missing)", "{}").is_err());
+        assert!(apply("x | base64decode", "!!").is_err());
+    }
 }
@@ -5,18 +5,21 @@ use anyhow::Result;
 use crate::{
     config::{Config, LadeRule, saved_user},
     files::{remove_files, split_env_files, write_files},
+    leases::LeaseGuard,
     network::{self, AcquiredNetwork},
     provider_progress::{
         ProviderProgressRenderer, start_provider_progress, stop_provider_progress,
     },
 };
 
-/// Command-scoped access state. Network guards and temporary files are owned
-/// here so every caller gets the same cleanup behavior.
+/// Command-scoped access state. Network guards, dynamic secret leases and
+/// temporary files are owned here so every caller gets the same cleanup
+/// behavior.
 pub struct AttachedAccess {
     pub env: HashMap<String, String>,
     pub warnings: Vec<String>,
     files: HashMap<PathBuf, HashMap<String, String>>,
+    _leases: LeaseGuard,
     _network: AcquiredNetwork,
 }
 
@@ -41,7 +44,10 @@ pub async fn acquire_attached(
 ) -> Result<AttachedAccess> {
     let saved_user = saved_user().await?;
     let network_bindings = Config::network_bindings_from_rules(rules, &saved_user);
-    let (vars, _sources, _maskable, warnings) = config.hydrate_rules(rules, &saved_user).await?;
+    let (vars, _sources, _maskable, warnings, leases) = config
+        .hydrate_rules(rules, &saved_user, &HashMap::new())
+        .await?;
+    let leases = LeaseGuard::new(leases);
     let mut progress: Option<ProviderProgressRenderer> =
         Some(start_provider_progress(rich_progress));
     let network_sink = progress.as_ref().expect("progress renderer").sink();
@@ -70,6 +76,7 @@ pub async fn acquire_attached(
         env,
         warnings,
         files,
+        _leases: leases,
         _network: network,
     })
 }
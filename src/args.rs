@@ -57,6 +57,12 @@ pub struct StatusCommand {
     pub json: bool,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Remove every cached value and the key encrypting them.
+    Clear,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Upgrade lade.
@@ -84,6 +90,11 @@ pub enum Command {
         /// The secret URI to resolve (e.g., op://vault/item/field)
         uri: String,
     },
+    /// Store a secret at a URI, reading the value from stdin.
+    Put {
+        /// The secret URI to write (e.g., vault://host/secret/app/password)
+        uri: String,
+    },
     /// Handle preToolUse for Cursor and Claude Code.
     Hook,
     /// Approve a pending disclaimer and run the command, using the code shown in
@@ -100,6 +111,11 @@ pub enum Command {
         #[arg(long)]
         reset: bool,
     },
+    /// Manage the encrypted resolution cache.
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
     /// Shortcut for `lade inject <command...>`.
     #[command(external_subcommand)]
     InjectAlias(Vec<String>),
@@ -0,0 +1,318 @@
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use age::{secrecy::ExposeSecret, x25519::Identity};
+use anyhow::{Context, Result, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Opt-in resolution cache (`.: { cache: 5m }`). Values are age-encrypted on
+/// disk with a key kept in the runtime dir, so they become unreadable once
+/// the login session ends, even if the cache dir itself survives.
+pub struct ResolutionCache {
+    entries: PathBuf,
+    identity: Identity,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CachedValue {
+    pub value: String,
+    pub masked: bool,
+    stored_at: u64,
+}
+
+/// What a cached value depends on besides the source itself: the cwd for
+/// relative paths and shells, the user, and the env given to providers.
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn new(
+        source: &str,
+        cwd: &Path,
+        user: &Option<String>,
+        extra_env: &[(String, String)],
+    ) -> Self {
+        let mut hasher = Sha256::new();
+        for part in [
+            source,
+            &cwd.to_string_lossy(),
+            user.as_deref().unwrap_or_default(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        // `extra_env` is sorted by the caller, so equal envs hash equally.
+        for (key, value) in extra_env {
+            hasher.update(key.as_bytes());
+            hasher.update([0]);
+            hasher.update(value.as_bytes());
+            hasher.update([0]);
+        }
+        Self(hex::encode(hasher.finalize()))
+    }
+}
+
+fn entries_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("LADE_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    directories::ProjectDirs::from("com", "zifeo", "lade")
+        .expect("cannot get directory for project")
+        .cache_dir()
+        .join("resolutions")
+}
+
+fn key_path() -> PathBuf {
+    let runtime = directories::BaseDirs::new()
+        .and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+        .map(|dir| dir.join("lade"))
+        .unwrap_or_else(|| {
+            let user = std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default();
+            std::env::temp_dir().join(format!("lade-{user}"))
+        });
+    runtime.join("cache.key")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn create_private_dir(dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        builder.mode(0o700);
+        builder.create(dir)?;
+        // The temp dir fallback is shared: never trust a key dir other users
+        // can read or plant files in.
+        if fs::metadata(dir)?.permissions().mode() & 0o077 != 0 {
+            bail!("{} is accessible to other users", dir.display());
+        }
+    }
+    #[cfg(not(unix))]
+    builder.create(dir)?;
+    Ok(())
+}
+
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&tmp)?.write_all(content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+impl ResolutionCache {
+    pub fn open() -> Result<Self> {
+        Self::at(entries_dir(), key_path())
+    }
+
+    fn at(entries: PathBuf, key: PathBuf) -> Result<Self> {
+        let key_dir = key.parent().context("cache key path has no parent")?;
+        create_private_dir(key_dir)?;
+        create_private_dir(&entries)?;
+        let identity = match fs::read_to_string(&key) {
+            Ok(content) => Identity::from_str(content.trim())
+                .map_err(|e| anyhow::anyhow!("invalid cache key {}: {e}", key.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let identity = Identity::generate();
+                write_private(&key, identity.to_string().expose_secret().as_bytes())?;
+                identity
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { entries, identity })
+    }
+
+    fn entry(&self, key: &CacheKey) -> PathBuf {
+        self.entries.join(&key.0)
+    }
+
+    /// The cached value if it is younger than `ttl`. Expired entries and
+    /// entries from an older key are dropped.
+    pub fn get(&self, key: &CacheKey, ttl: Duration) -> Option<CachedValue> {
+        let path = self.entry(key);
+        let encrypted = fs::read(&path).ok()?;
+        let cached = age::decrypt(&self.identity, &encrypted)
+            .ok()
+            .and_then(|plain| serde_json::from_slice::<CachedValue>(&plain).ok())
+            .filter(|cached| now().saturating_sub(cached.stored_at) < ttl.as_secs());
+        if cached.is_none() {
+            debug!("cache miss: {}", path.display());
+            let _ = fs::remove_file(&path);
+        }
+        cached
+    }
+
+    pub fn put(&self, key: &CacheKey, value: String, masked: bool) -> Result<()> {
+        let plain = serde_json::to_vec(&CachedValue {
+            value,
+            masked,
+            stored_at: now(),
+        })?;
+        let encrypted = age::encrypt(&self.identity.to_public(), &plain)?;
+        write_private(&self.entry(key), &encrypted)
+    }
+}
+
+/// Removes every cached value and the key that encrypts them.
+pub fn clear() -> Result<usize> {
+    let entries = entries_dir();
+    let removed = match fs::read_dir(&entries) {
+        Ok(dir) => dir.filter_map(|entry| entry.ok()).count(),
+        Err(e) if e.kind() == ErrorKind::NotFound => 0,
+        Err(e) => return Err(e.into()),
+    };
+    for path in [entries, key_path()] {
+        let removal = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        if let Err(e) = removal
+            && e.kind() != ErrorKind::NotFound
+        {
+            return Err(e.into());
+        }
+    }
+    Ok(removed)
+}
+
+/// Parses a duration such as `30s`, `5m`, `1h30m` or `1d`, as used for cache
+/// TTLs and provider timeouts.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for ch in value.trim().chars() {
+        if ch.is_ascii_digit() {
+            digits.push(ch);
+            continue;
+        }
+        let unit = match ch {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => bail!("invalid duration '{value}': unknown unit '{ch}'"),
+        };
+        let amount = digits
+            .parse::<u64>()
+            .with_context(|| format!("invalid duration '{value}'"))?;
+        total = amount
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .with_context(|| format!("invalid duration '{value}': too long"))?;
+        digits.clear();
+    }
+    if !digits.is_empty() || total == 0 {
+        bail!("invalid duration '{value}': use a unit, e.g. 30s, 5m, 1h");
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn cache(dir: &tempfile::TempDir) -> ResolutionCache {
+        ResolutionCache::at(dir.path().join("entries"), dir.path().join("run/cache.key")).unwrap()
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("18446744073709551615d").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_is_encrypted() {
+        let dir = tempdir().unwrap();
+        let cache = cache(&dir);
+        let key = CacheKey::new("op://vault/item/field", Path::new("/w"), &None, &[]);
+        cache.put(&key, "s3cret".to_string(), true).unwrap();
+        let on_disk = fs::read(cache.entry(&key)).unwrap();
+        assert!(!String::from_utf8_lossy(&on_disk).contains("s3cret"));
+        let cached = cache.get(&key, Duration::from_secs(60)).unwrap();
+        assert_eq!(cached.value, "s3cret");
+        assert!(cached.masked);
+    }
+
+    #[test]
+    fn test_key_depends_on_user_cwd_and_env() {
+        let base = CacheKey::new("op://v/i/f", Path::new("/w"), &None, &[]).0;
+        let user = Some("alice".to_string());
+        let env = [("OP_SERVICE_ACCOUNT_TOKEN".to_string(), "t".to_string())];
+        assert_ne!(
+            base,
+            CacheKey::new("op://v/i/f", Path::new("/w"), &user, &[]).0
+        );
+        assert_ne!(
+            base,
+            CacheKey::new("op://v/i/f", Path::new("/x"), &None, &[]).0
+        );
+        assert_ne!(
+            base,
+            CacheKey::new("op://v/i/f", Path::new("/w"), &None, &env).0
+        );
+        assert_eq!(
+            base,
+            CacheKey::new("op://v/i/f", Path::new("/w"), &None, &[]).0
+        );
+    }
+
+    #[test]
+    fn test_expired_and_foreign_entries_miss() {
+        let dir = tempdir().unwrap();
+        let cache = cache(&dir);
+        let key = CacheKey::new("op://v/i/f", Path::new("/w"), &None, &[]);
+        cache.put(&key, "s3cret".to_string(), true).unwrap();
+        assert!(cache.get(&key, Duration::ZERO).is_none());
+        assert!(!cache.entry(&key).exists());
+
+        cache.put(&key, "s3cret".to_string(), true).unwrap();
+        fs::remove_file(dir.path().join("run/cache.key")).unwrap();
+        let rekeyed = self::cache(&dir);
+        assert!(rekeyed.get(&key, Duration::from_secs(60)).is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_key_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        cache(&dir);
+        let mode = fs::metadata(dir.path().join("run/cache.key"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o077, 0);
+    }
+}
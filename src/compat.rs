@@ -158,12 +158,16 @@ mod tests {
             "op://my.1password.com/v/i/f",
             "op://my.1password.com/v/i/g",
             "vault://localhost/secret/app/pass",
+            "doppler://api.doppler.com/p/e/K",
             "plainvalue",
             "unknown://host/path",
         ];
         let mut schemes = known_schemes(uris.iter().copied());
         schemes.sort();
-        assert_eq!(schemes, vec!["op".to_string(), "vault".to_string()]);
+        assert_eq!(
+            schemes,
+            vec!["doppler".to_string(), "op".to_string(), "vault".to_string()]
+        );
     }
 
     #[test]
@@ -14,13 +14,13 @@ use super::{Config, secret::LadeRule};
 #[serde(untagged)]
 enum RuleBodies {
     Many(Vec<LadeRule>),
-    One(LadeRule),
+    One(Box<LadeRule>),
 }
 
 impl RuleBodies {
     fn into_rules(self, pattern: &str) -> Result<Vec<LadeRule>> {
         match self {
-            RuleBodies::One(rule) => Ok(vec![rule]),
+            RuleBodies::One(rule) => Ok(vec![*rule]),
             RuleBodies::Many(rules) => {
                 if rules.is_empty() {
                     bail!("pattern '{pattern}' has an empty rule list");
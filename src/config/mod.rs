@@ -4,30 +4,50 @@ mod secret;
 mod tests;
 
 pub use loader::LadeFile;
-use secret::resolve_lade_secret;
 pub use secret::*;
+use secret::{fallbacks, resolve_lade_secret};
 
+use crate::cache::{CacheKey, ResolutionCache};
 use crate::global_config::GlobalConfig;
+use crate::leases::revoke_all;
 use crate::provider_registry::is_network_scheme;
 use anyhow::{Result, bail};
-use futures::stream::{FuturesUnordered, StreamExt};
-use lade_sdk::{Dag, Template, hydrate_one, hydrate_with_maskable};
+use futures::{
+    FutureExt,
+    stream::{FuturesUnordered, StreamExt},
+};
+use lade_sdk::{
+    Dag, Hydrated, Limits, ProviderLimits, Template, apply_transforms, hydrate_one,
+    hydrate_with_limits, keepass, split_template_transforms, split_transforms, vault::Lease,
+};
 use regex::RegexSet;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct SecretSources {
     pub sources: HashMap<String, String>,
+    /// Alternatives tried after `sources` when a chain's first one fails.
+    pub fallbacks: HashMap<String, Vec<String>>,
     pub overridden: HashSet<String>,
     pub cancelled: HashMap<String, String>,
     pub silent: HashSet<String>,
 }
 
+impl SecretSources {
+    /// Every source that may be resolved, chain alternatives included.
+    pub fn all(&self) -> impl Iterator<Item = &String> {
+        self.sources
+            .values()
+            .chain(self.fallbacks.values().flatten())
+    }
+}
+
 pub type Output = Option<PathBuf>;
 
 /// Who secrets are for. Produced by [`crate::audience::detect`], never picked
@@ -55,9 +75,13 @@ fn rule_applies_to(rule: &LadeRule, audience: Audience) -> bool {
 struct Binding {
     private: bool,
     source: String,
+    fallbacks: Vec<String>,
     cwd: PathBuf,
     output: Output,
     extra_env: HashMap<String, String>,
+    cache: Option<Duration>,
+    optional: bool,
+    limits: Limits,
 }
 
 fn binding_name(key: &str) -> Result<(String, bool)> {
@@ -77,6 +101,29 @@ fn is_shell_source(source: &str) -> bool {
     matches!(split_scheme(source), Some("sh" | "bash" | "zsh" | "fish"))
 }
 
+/// Sources that get their `${NAME}` dependencies as env instead of rendered
+/// into the value: shells own that syntax, and `keepass://` reads its
+/// `?password=${NAME}` this way so the password never ends up in the URI.
+fn forwards_dependencies(source: &str) -> bool {
+    is_shell_source(source) || split_scheme(source) == Some("keepass")
+}
+
+/// Sources of codes that change every few seconds: `totp://` and 1Password
+/// `?attribute=otp` references.
+fn is_one_time_code(source: &str) -> bool {
+    let uri = split_transforms(source).map_or(source, |(uri, _)| uri);
+    let Ok(url) = url::Url::parse(uri) else {
+        return false;
+    };
+    match url.scheme() {
+        "totp" => true,
+        "op" => url
+            .query_pairs()
+            .any(|(key, value)| key == "attribute" && value == "otp"),
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NetworkBinding {
     pub key: String,
@@ -93,6 +140,8 @@ enum ResolvedEntry {
     Secret {
         key: String,
         value: String,
+        /// Tried in order when `value` fails to resolve.
+        fallbacks: Vec<String>,
     },
     Network {
         key: String,
@@ -135,6 +184,7 @@ fn resolve_entry(
     Some(ResolvedEntry::Secret {
         key: key.to_string(),
         value,
+        fallbacks: fallbacks(secret).to_vec(),
     })
 }
 
@@ -147,12 +197,19 @@ fn split_scheme(value: &str) -> Option<&str> {
 /// outputs alike — unlike [`Config::keys_from_rules`], it does not require
 /// keys to look like valid env var names (a file-routed secret can use any
 /// key as its JSON/YAML field name).
-fn rule_sources(rule: &LadeRule, saved_user: &Option<String>) -> Result<HashMap<String, String>> {
+fn rule_sources(
+    rule: &LadeRule,
+    saved_user: &Option<String>,
+) -> Result<HashMap<String, Vec<String>>> {
     let mut out = HashMap::new();
     for (key, secret) in &rule.secrets {
         match resolve_entry(key, secret, saved_user) {
-            Some(ResolvedEntry::Secret { key, value }) => {
-                out.insert(key, value);
+            Some(ResolvedEntry::Secret {
+                key,
+                value,
+                fallbacks,
+            }) => {
+                out.insert(key, std::iter::once(value).chain(fallbacks).collect());
             }
             Some(ResolvedEntry::Network { .. }) | Some(ResolvedEntry::Unset { .. }) | None => {}
             Some(ResolvedEntry::InvalidNumericSecret { key }) => bail!(
@@ -164,26 +221,49 @@ fn rule_sources(rule: &LadeRule, saved_user: &Option<String>) -> Result<HashMap<
     Ok(out)
 }
 
+/// Resolves the provider credentials declared on `rule` into the environment
+/// variables their CLIs read, adding the leases issued for them to `leases`.
+async fn credentials_env(
+    rule: &LadeRule,
+    cwd: &Path,
+    saved_user: &Option<String>,
+    leases: &mut Vec<Lease>,
+) -> Result<HashMap<String, String>> {
+    let mut extra_env = HashMap::new();
+    for (var, secret) in rule.config.iter().flat_map(|config| config.credentials()) {
+        if let Some(uri) = resolve_lade_secret(secret, saved_user) {
+            let mut resolved = hydrate_one(uri, cwd, &HashMap::new()).await;
+            for fallback in fallbacks(secret) {
+                if resolved.is_ok() {
+                    break;
+                }
+                resolved = hydrate_one(fallback.clone(), cwd, &HashMap::new()).await;
+            }
+            let (value, issued) = resolved?;
+            leases.extend(issued);
+            extra_env.insert(var.to_string(), value);
+        }
+    }
+    Ok(extra_env)
+}
+
 async fn bindings_from_rules(
     rules: &[(PathBuf, LadeRule)],
     saved_user: &Option<String>,
+    unlocked: &HashMap<String, String>,
+    leases: &mut Vec<Lease>,
 ) -> Result<HashMap<String, Binding>> {
     let mut bindings = HashMap::<String, Binding>::new();
     for (cwd, rule) in rules {
         let output = rule.config.as_ref().and_then(|config| config.file.clone());
-        let extra_env = if let Some(uri) = rule
+        let cache = rule.config.as_ref().and_then(|config| config.cache);
+        let optional = rule.config.as_ref().is_some_and(|config| config.optional);
+        let limits = rule
             .config
             .as_ref()
-            .and_then(|config| config.onepassword_service_account.as_ref())
-            .and_then(|secret| resolve_lade_secret(secret, saved_user))
-        {
-            HashMap::from([(
-                "OP_SERVICE_ACCOUNT_TOKEN".to_string(),
-                hydrate_one(uri, cwd, &HashMap::new()).await?,
-            )])
-        } else {
-            HashMap::new()
-        };
+            .map(RuleConfig::limits)
+            .unwrap_or_default();
+        let extra_env = credentials_env(rule, cwd, saved_user, leases).await?;
         for (key, secret) in &rule.secrets {
             match resolve_entry(key, secret, saved_user) {
                 Some(ResolvedEntry::Unset { key }) | Some(ResolvedEntry::Network { key, .. }) => {
@@ -195,14 +275,30 @@ async fn bindings_from_rules(
                     key
                 ),
                 None => {}
-                Some(ResolvedEntry::Secret { key, value }) => {
+                Some(ResolvedEntry::Secret {
+                    key,
+                    value,
+                    fallbacks,
+                }) => {
                     let (name, private) = binding_name(&key)?;
+                    let mut extra_env = extra_env.clone();
+                    if let Some(password) = std::iter::once(&value)
+                        .chain(&fallbacks)
+                        .filter_map(|source| keepass::locked_database(source))
+                        .find_map(|db| unlocked.get(&db))
+                    {
+                        extra_env.insert(keepass::PASSWORD_ENV.to_string(), password.clone());
+                    }
                     let binding = Binding {
                         private,
                         source: value,
+                        fallbacks,
                         cwd: cwd.clone(),
                         output: output.as_ref().map(|path| cwd.join(path)),
-                        extra_env: extra_env.clone(),
+                        extra_env,
+                        cache,
+                        optional,
+                        limits: limits.clone(),
                     };
                     if let Some(existing) = bindings.get(&name)
                         && existing.private != binding.private
@@ -217,6 +313,154 @@ async fn bindings_from_rules(
     Ok(bindings)
 }
 
+/// One source of a binding, rendered against the values resolved so far.
+struct Attempt {
+    /// As written in `lade.yml`, so warnings never show rendered secrets.
+    configured: String,
+    rendered: String,
+    extra_env: Vec<(String, String)>,
+    forwarded: bool,
+}
+
+impl Attempt {
+    fn prepare(
+        configured: &str,
+        template: &Template,
+        binding: &Binding,
+        values: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let forwarded = forwards_dependencies(configured);
+        let (source, transforms) = split_template_transforms(configured)?;
+        let rendered = if forwarded {
+            template.shell_source()
+        } else if transforms.is_empty() {
+            template.render(values)?
+        } else {
+            // The SDK only applies stages to provider URIs: hand it the
+            // transformed value as an escaped literal.
+            let value = Template::parse(source).render(values)?;
+            format!("!{}", apply_transforms(source, value, &transforms)?)
+        };
+        let mut extra_env = binding
+            .extra_env
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        if forwarded {
+            extra_env.extend(template.dependencies().filter_map(|dependency| {
+                values
+                    .get(dependency)
+                    .map(|value| (dependency.to_string(), value.clone()))
+            }));
+        }
+        extra_env.sort();
+        Ok(Self {
+            configured: configured.to_string(),
+            rendered,
+            extra_env,
+            forwarded,
+        })
+    }
+
+    /// A chain is cached as a whole: the value may come from any alternative.
+    fn cache_key(attempts: &[Self], cwd: &Path, saved_user: &Option<String>) -> CacheKey {
+        let source = attempts
+            .iter()
+            .map(|attempt| attempt.rendered.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let extra_env = attempts
+            .iter()
+            .flat_map(|attempt| attempt.extra_env.iter().cloned())
+            .collect::<Vec<_>>();
+        CacheKey::new(&source, cwd, saved_user, &extra_env)
+    }
+}
+
+type Resolved = (HashMap<String, String>, Vec<(String, CacheKey)>, Hydrated);
+
+/// Tries each alternative of a fallback chain in order, warning about the
+/// ones skipped before the first that resolves.
+async fn resolve_chain(
+    name: String,
+    attempts: Vec<Attempt>,
+    cwd: PathBuf,
+    to_cache: Vec<(String, CacheKey)>,
+    optional: bool,
+    limits: ProviderLimits,
+) -> Result<Resolved> {
+    let source = attempts[0].rendered.clone();
+    let mut skipped = Vec::new();
+    let mut last_error = None;
+    for attempt in attempts {
+        let env = HashMap::from([(name.clone(), attempt.rendered.clone())]);
+        let extra_env = attempt.extra_env.into_iter().collect();
+        match hydrate_with_limits(env, cwd.clone(), extra_env, &limits).await {
+            Ok((resolved, maskable, warnings, leases)) => {
+                skipped.extend(warnings);
+                let configured = HashMap::from([(name, attempt.rendered)]);
+                return Ok((configured, to_cache, (resolved, maskable, skipped, leases)));
+            }
+            Err(e) => {
+                skipped.push(format!("{name}: skipped {}: {e}", attempt.configured));
+                last_error = Some(e);
+            }
+        }
+    }
+    if optional {
+        skipped.push(format!(
+            "{name}: skipped optional binding, no fallback resolved"
+        ));
+        let configured = HashMap::from([(name, source)]);
+        let hydrated = (HashMap::new(), FxHashSet::default(), skipped, Vec::new());
+        return Ok((configured, to_cache, hydrated));
+    }
+    let message = format!("{name}: no fallback resolved: {}", skipped.join("; "));
+    // The last attempt keeps its kind, scheme and binding for the error box.
+    Err(match last_error.map(|e| e.downcast::<lade_sdk::Error>()) {
+        Some(Ok(error)) => error.with_message(message).into(),
+        _ => anyhow::anyhow!(message),
+    })
+}
+
+/// Resolves optional bindings together, then one by one when that fails, so
+/// a broken source only skips its own binding.
+async fn resolve_optional(
+    sources: HashMap<String, String>,
+    cwd: PathBuf,
+    extra_env: HashMap<String, String>,
+    to_cache: Vec<(String, CacheKey)>,
+    limits: ProviderLimits,
+) -> Result<Resolved> {
+    let configured = sources.clone();
+    let error = match hydrate_with_limits(sources, cwd.clone(), extra_env.clone(), &limits).await {
+        Ok(result) => return Ok((configured, to_cache, result)),
+        Err(e) => e,
+    };
+    let mut result = Hydrated::default();
+    if let [(name, _)] = Vec::from_iter(&configured).as_slice() {
+        result
+            .2
+            .push(format!("{name}: skipped optional binding: {error}"));
+        return Ok((configured, to_cache, result));
+    }
+    for (name, source) in &configured {
+        let single = HashMap::from([(name.clone(), source.clone())]);
+        match hydrate_with_limits(single, cwd.clone(), extra_env.clone(), &limits).await {
+            Ok((values, maskable, warnings, leases)) => {
+                result.0.extend(values);
+                result.1.extend(maskable);
+                result.2.extend(warnings);
+                result.3.extend(leases);
+            }
+            Err(e) => result
+                .2
+                .push(format!("{name}: skipped optional binding: {e}")),
+        }
+    }
+    Ok((configured, to_cache, result))
+}
+
 /// The configured user (global config override, falling back to the OS
 /// user), used to resolve per-user secret/network maps. Reads
 /// [`GlobalConfig`] from disk, so callers on the hot path (one shell command
@@ -271,22 +515,52 @@ impl Config {
     /// this directly with the single `collect`+`saved_user` resolved at the
     /// top of the invocation, instead of [`Config::collect_hydrate`] which
     /// re-resolves both.
+    /// `unlocked` maps KeePassXC databases to the passwords typed at the
+    /// interactive prompt (see [`crate::prompt::unlock_keepass`]).
     pub async fn hydrate_rules(
         &self,
         rules: &[(PathBuf, LadeRule)],
         saved_user: &Option<String>,
+        unlocked: &HashMap<String, String>,
     ) -> Result<(
         HashMap<Output, HashMap<String, String>>,
         HashMap<String, String>,
         FxHashSet<String>,
         Vec<String>,
+        Vec<Lease>,
     )> {
-        let bindings = bindings_from_rules(rules, saved_user).await?;
-        let templates = bindings
+        let by_scheme = GlobalConfig::load().await?.provider_limits()?;
+        // Leases issued for provider credentials live as long as the ones
+        // issued for the bindings they unlock.
+        let mut leases = Vec::new();
+        let bindings = match bindings_from_rules(rules, saved_user, unlocked, &mut leases).await {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                revoke_all(&leases).await;
+                return Err(e);
+            }
+        };
+        let alternatives = bindings
             .iter()
-            .map(|(name, binding)| (name.clone(), Template::parse(&binding.source)))
+            .map(|(name, binding)| {
+                let templates = std::iter::once(&binding.source)
+                    .chain(&binding.fallbacks)
+                    .map(|source| Template::parse(source))
+                    .collect::<Vec<_>>();
+                (name.clone(), templates)
+            })
+            .collect::<HashMap<_, _>>();
+        let templates = alternatives
+            .iter()
+            .map(|(name, templates)| (name.clone(), Template::chain(templates)))
             .collect::<HashMap<_, _>>();
-        let dag = Dag::new(templates)?;
+        let dag = match Dag::new(templates) {
+            Ok(dag) => dag,
+            Err(e) => {
+                revoke_all(&leases).await;
+                return Err(e);
+            }
+        };
         let mut degrees = dag.indegrees();
         let mut ready = dag.initial_ready();
         let mut values = HashMap::<String, String>::new();
@@ -294,50 +568,179 @@ impl Config {
         let mut maskable = FxHashSet::default();
         let mut warnings = Vec::new();
 
+        let cache = bindings
+            .values()
+            .any(|binding| binding.cache.is_some())
+            .then(|| {
+                ResolutionCache::open()
+                    .inspect_err(|e| log::warn!("resolution cache disabled: {e}"))
+                    .ok()
+            })
+            .flatten();
+        let cache = cache.as_ref();
+        let provider_limits = |rule: &Limits| ProviderLimits {
+            all: rule.clone(),
+            by_scheme: by_scheme.clone(),
+        };
+
+        // Optional bindings that failed, and bindings that could not render
+        // without one of them.
+        let mut skipped = HashSet::<String>::new();
+
         let mut running = FuturesUnordered::new();
-        while !ready.is_empty() || !running.is_empty() {
+        let mut failure = None;
+        'dag: while !ready.is_empty() || !running.is_empty() {
             let batch = std::mem::take(&mut ready);
-            let mut groups =
-                BTreeMap::<(PathBuf, Vec<(String, String)>, bool), HashMap<String, String>>::new();
+            let mut groups = BTreeMap::<
+                (PathBuf, Vec<(String, String)>, bool, bool, Limits),
+                (HashMap<String, String>, Vec<(String, CacheKey)>),
+            >::new();
+            let mut hits = HashMap::<String, String>::new();
+            let mut hit_values = HashMap::<String, String>::new();
+            let mut hit_maskable = FxHashSet::default();
+            let mut hit_warnings = Vec::new();
             for name in &batch {
                 let binding = bindings.get(name).expect("planned binding");
-                let template = dag.template(name).expect("planned template");
-                let shell_source = is_shell_source(&binding.source);
-                let rendered = if shell_source {
-                    template.shell_source()
-                } else {
-                    template.render(&values)?
+                let attempts = std::iter::once(&binding.source)
+                    .chain(&binding.fallbacks)
+                    .zip(&alternatives[name])
+                    .filter(|(_, template)| {
+                        !template
+                            .required_dependencies()
+                            .any(|dependency| skipped.contains(dependency))
+                    })
+                    .map(|(configured, template)| {
+                        Attempt::prepare(configured, template, binding, &values)
+                    })
+                    .collect::<Result<Vec<_>>>();
+                let mut attempts = match attempts {
+                    Ok(attempts) => attempts,
+                    Err(e) => {
+                        failure = Some(e);
+                        break 'dag;
+                    }
                 };
-                let mut extra_env = binding
-                    .extra_env
-                    .iter()
-                    .map(|(key, value)| (key.clone(), value.clone()))
-                    .collect::<Vec<_>>();
-                if shell_source {
-                    extra_env.extend(template.dependencies().filter_map(|dependency| {
-                        values
-                            .get(dependency)
-                            .map(|value| (dependency.to_string(), value.clone()))
-                    }));
+                if attempts.is_empty() {
+                    let missing = dag
+                        .template(name)
+                        .expect("planned template")
+                        .required_dependencies()
+                        .filter(|dependency| skipped.contains(*dependency))
+                        .collect::<BTreeSet<_>>();
+                    hit_warnings.push(format!(
+                        "{name}: skipped, {} could not be resolved",
+                        missing.into_iter().collect::<Vec<_>>().join(", ")
+                    ));
+                    hits.insert(name.clone(), binding.source.clone());
+                    continue;
                 }
-                extra_env.sort();
-                groups
-                    .entry((binding.cwd.clone(), extra_env, shell_source))
-                    .or_default()
-                    .insert(name.clone(), rendered);
-            }
-            for ((cwd, extra_env, _), sources_for_group) in groups {
-                running.push(async move {
-                    let extra_env = extra_env.into_iter().collect::<HashMap<_, _>>();
-                    let configured = sources_for_group.clone();
-                    let result = hydrate_with_maskable(sources_for_group, cwd, extra_env).await?;
-                    Ok::<_, anyhow::Error>((configured, result))
+                let cached = binding.cache.zip(cache).map(|(ttl, cache)| {
+                    let key = Attempt::cache_key(&attempts, &binding.cwd, saved_user);
+                    let hit = cache.get(&key, ttl);
+                    (key, hit)
                 });
+                let (to_cache, hit) = match cached {
+                    Some((key, None)) => (Some((name.clone(), key)), None),
+                    Some((_, hit)) => (None, hit),
+                    None => (None, None),
+                };
+                if let Some(hit) = hit {
+                    let source = attempts.swap_remove(0).rendered;
+                    if hit.masked {
+                        hit_maskable.insert(source.clone());
+                    }
+                    hit_values.insert(name.clone(), hit.value);
+                    hits.insert(name.clone(), source);
+                    continue;
+                }
+                if attempts.len() > 1 {
+                    running.push(
+                        resolve_chain(
+                            name.clone(),
+                            attempts,
+                            binding.cwd.clone(),
+                            to_cache.into_iter().collect(),
+                            binding.optional,
+                            provider_limits(&binding.limits),
+                        )
+                        .boxed(),
+                    );
+                    continue;
+                }
+                let attempt = attempts.swap_remove(0);
+                let (sources_for_group, group_cache) = groups
+                    .entry((
+                        binding.cwd.clone(),
+                        attempt.extra_env,
+                        attempt.forwarded,
+                        binding.optional,
+                        binding.limits.clone(),
+                    ))
+                    .or_default();
+                sources_for_group.insert(name.clone(), attempt.rendered);
+                group_cache.extend(to_cache);
+            }
+            if !hits.is_empty() {
+                let hydrated = (hit_values, hit_maskable, hit_warnings, Vec::new());
+                running.push(async move { Ok((hits, Vec::new(), hydrated)) }.boxed());
+            }
+            for ((cwd, extra_env, _, optional, limits), (sources_for_group, to_cache)) in groups {
+                let extra_env = extra_env.into_iter().collect::<HashMap<_, _>>();
+                let limits = provider_limits(&limits);
+                if optional {
+                    running.push(
+                        resolve_optional(sources_for_group, cwd, extra_env, to_cache, limits)
+                            .boxed(),
+                    );
+                    continue;
+                }
+                running.push(
+                    async move {
+                        let configured = sources_for_group.clone();
+                        let result =
+                            hydrate_with_limits(sources_for_group, cwd, extra_env, &limits).await?;
+                        Ok((configured, to_cache, result))
+                    }
+                    .boxed(),
+                );
             }
-            let (configured, (resolved, group_maskable, group_warnings)) = running
-                .next()
-                .await
-                .expect("a planned DAG must have an active group")?;
+            let (configured, to_cache, (resolved, group_maskable, group_warnings, group_leases)) =
+                match running
+                    .next()
+                    .await
+                    .expect("a planned DAG must have an active group")
+                {
+                    Ok(group) => group,
+                    Err(e) => {
+                        failure = Some(e);
+                        break 'dag;
+                    }
+                };
+            // Values issued with a lease are revoked when the command exits,
+            // so caching them would hand out dead credentials.
+            if let Some(cache) = cache
+                && group_leases.is_empty()
+            {
+                for (name, key) in to_cache {
+                    let Some(value) = resolved.get(&name) else {
+                        continue;
+                    };
+                    // One-time codes expire within seconds: a cached one is stale.
+                    if is_one_time_code(&configured[&name]) {
+                        continue;
+                    }
+                    let masked = group_maskable.contains(&configured[&name]);
+                    if let Err(e) = cache.put(&key, value.clone(), masked) {
+                        log::warn!("could not cache {name}: {e}");
+                    }
+                }
+            }
+            skipped.extend(
+                configured
+                    .keys()
+                    .filter(|name| !resolved.contains_key(*name))
+                    .cloned(),
+            );
             for (name, value) in resolved {
                 let source = configured.get(&name).expect("configured source").clone();
                 if group_maskable.contains(&source)
@@ -356,6 +759,7 @@ impl Config {
                 sources.insert(name, source);
             }
             warnings.extend(group_warnings);
+            leases.extend(group_leases);
             let mut newly_ready = BTreeSet::new();
             for name in configured.keys() {
                 for dependent in dag.dependents(name) {
@@ -368,10 +772,21 @@ impl Config {
             }
             ready.extend(newly_ready);
         }
+        if let Some(error) = failure {
+            // Nothing will use the credentials issued so far, including by
+            // groups still running: do not leave them valid until their TTL.
+            while let Some(group) = running.next().await {
+                if let Ok((_, _, (_, _, _, group_leases))) = group {
+                    leases.extend(group_leases);
+                }
+            }
+            revoke_all(&leases).await;
+            return Err(error);
+        }
 
         let mut vars = FxHashMap::<Output, HashMap<String, String>>::default();
         for (name, binding) in bindings {
-            if binding.private {
+            if binding.private || skipped.contains(&name) {
                 continue;
             }
             vars.entry(binding.output).or_default().insert(
@@ -379,7 +794,13 @@ impl Config {
                 values.remove(&name).expect("resolved binding"),
             );
         }
-        Ok((vars.into_iter().collect(), sources, maskable, warnings))
+        Ok((
+            vars.into_iter().collect(),
+            sources,
+            maskable,
+            warnings,
+            leases,
+        ))
     }
 
     #[cfg(test)]
@@ -391,9 +812,10 @@ impl Config {
         HashMap<String, String>,
         FxHashSet<String>,
         Vec<String>,
+        Vec<Lease>,
     )> {
         let saved_user = saved_user().await?;
-        self.hydrate_rules(&self.collect(command), &saved_user)
+        self.hydrate_rules(&self.collect(command), &saved_user, &HashMap::new())
             .await
     }
 
@@ -408,21 +830,28 @@ impl Config {
             let silent = rule.config.as_ref().is_some_and(|config| config.silence);
             for (key, secret) in &rule.secrets {
                 match resolve_entry(key, secret, saved_user) {
-                    Some(ResolvedEntry::Secret { key, value }) => {
+                    Some(ResolvedEntry::Secret {
+                        key,
+                        value,
+                        fallbacks,
+                    }) => {
                         if plan.sources.contains_key(&key) || plan.cancelled.contains_key(&key) {
                             plan.overridden.insert(key.clone());
                         }
                         plan.cancelled.remove(&key);
                         mark_silent(&mut plan.silent, &key, silent);
+                        plan.fallbacks.insert(key.clone(), fallbacks);
                         plan.sources.insert(key, value);
                     }
                     Some(ResolvedEntry::Unset { key }) => {
+                        plan.fallbacks.remove(&key);
                         plan.overridden.remove(&key);
                         let previous = plan.sources.remove(&key).unwrap_or_default();
                         mark_silent(&mut plan.silent, &key, silent);
                         plan.cancelled.insert(key, previous);
                     }
                     Some(ResolvedEntry::Network { key, .. }) => {
+                        plan.fallbacks.remove(&key);
                         plan.overridden.remove(&key);
                         plan.cancelled.remove(&key);
                         plan.silent.remove(&key);
@@ -514,10 +943,29 @@ impl Config {
         self.rules
             .iter()
             .filter_map(|(_, rule)| rule_sources(rule, saved_user).ok())
-            .flat_map(|sources| sources.into_values())
+            .flat_map(|sources| sources.into_values().flatten())
             .collect()
     }
 
+    /// Provider credentials of the last rule declaring `uri` as a source, so
+    /// `lade put` authenticates the way hydrating that secret would. Empty when
+    /// no rule declares it. The leases issued for them are added to `leases`.
+    pub async fn credentials_for(
+        &self,
+        uri: &str,
+        saved_user: &Option<String>,
+        leases: &mut Vec<Lease>,
+    ) -> Result<HashMap<String, String>> {
+        let declaring = self.rules.iter().rev().find(|(_, rule)| {
+            rule_sources(rule, saved_user)
+                .is_ok_and(|sources| sources.values().flatten().any(|source| source == uri))
+        });
+        match declaring {
+            Some((cwd, rule)) => credentials_env(rule, cwd, saved_user, leases).await,
+            None => Ok(HashMap::new()),
+        }
+    }
+
     pub fn all_network_sources(&self, saved_user: &Option<String>) -> Vec<String> {
         self.rules
             .iter()
@@ -1,13 +1,19 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use lade_sdk::Limits;
 use serde::Deserialize;
 use serde::de;
 
+use crate::provider_registry::is_network_scheme;
+
 #[derive(Debug, Clone)]
 pub enum LadeSecret {
     Secret(String),
     User(HashMap<String, Option<String>>),
+    /// Alternatives tried in order until one resolves.
+    Chain(Vec<String>),
     Unset,
 }
 
@@ -25,10 +31,26 @@ impl<'de> Deserialize<'de> for LadeSecret {
         enum Repr {
             Secret(String),
             User(HashMap<String, Option<String>>),
+            Chain(Vec<String>),
         }
         match Repr::deserialize(value) {
             Ok(Repr::Secret(value)) => Ok(LadeSecret::Secret(value)),
             Ok(Repr::User(map)) => Ok(LadeSecret::User(map)),
+            Ok(Repr::Chain(values)) if values.is_empty() => Err(de::Error::custom(
+                "fallback chain must list at least one source",
+            )),
+            Ok(Repr::Chain(values)) => {
+                if let Some(network) = values.iter().find(|value| {
+                    value
+                        .split_once("://")
+                        .is_some_and(|(scheme, _)| is_network_scheme(scheme))
+                }) {
+                    return Err(de::Error::custom(format!(
+                        "fallback chain cannot contain network URI '{network}'"
+                    )));
+                }
+                Ok(LadeSecret::Chain(values))
+            }
             Err(error) => Err(de::Error::custom(error)),
         }
     }
@@ -48,11 +70,61 @@ pub struct RuleConfig {
     pub file: Option<PathBuf>,
     #[serde(rename = "1password_service_account")]
     pub onepassword_service_account: Option<LadeSecret>,
+    pub bitwarden_access_token: Option<LadeSecret>,
+    pub bitwarden_session: Option<LadeSecret>,
     pub disclaimer: Option<String>,
     #[serde(default)]
     pub when: RuleWhen,
     #[serde(default)]
     pub silence: bool,
+    /// Skip bindings whose provider fails instead of failing the command.
+    #[serde(default)]
+    pub optional: bool,
+    /// How long resolved values of the rule stay in the encrypted cache.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub cache: Option<Duration>,
+    /// Time allowed to each provider call of the rule.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// Retries of a provider call failing on the backend side.
+    pub retries: Option<u32>,
+    /// Backend calls in flight at once, per provider.
+    pub concurrency: Option<usize>,
+}
+
+pub(crate) fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| crate::cache::parse_duration(&value).map_err(de::Error::custom))
+        .transpose()
+}
+
+impl RuleConfig {
+    /// Provider limits set on the rule, over the global ones.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            timeout: self.timeout,
+            retries: self.retries,
+            concurrency: self.concurrency,
+        }
+    }
+
+    /// Provider credentials declared on the rule, paired with the environment
+    /// variable their CLI reads.
+    pub fn credentials(&self) -> impl Iterator<Item = (&'static str, &LadeSecret)> {
+        [
+            (
+                "OP_SERVICE_ACCOUNT_TOKEN",
+                self.onepassword_service_account.as_ref(),
+            ),
+            ("BWS_ACCESS_TOKEN", self.bitwarden_access_token.as_ref()),
+            ("BW_SESSION", self.bitwarden_session.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(var, secret)| secret.map(|secret| (var, secret)))
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -86,9 +158,18 @@ where
         .collect()
 }
 
+/// Alternatives after the one [`resolve_lade_secret`] returns.
+pub(super) fn fallbacks(secret: &LadeSecret) -> &[String] {
+    match secret {
+        LadeSecret::Chain(values) => values.get(1..).unwrap_or_default(),
+        _ => &[],
+    }
+}
+
 pub(super) fn resolve_lade_secret(secret: &LadeSecret, user: &Option<String>) -> Option<String> {
     match secret {
         LadeSecret::Secret(value) => Some(value.clone()),
+        LadeSecret::Chain(values) => values.first().cloned(),
         LadeSecret::User(map) => user
             .as_ref()
             .and_then(|u| map.get(u))
@@ -213,6 +294,50 @@ mod tests {
         assert!(config.silence);
     }
 
+    #[test]
+    fn test_rule_config_cache() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("lade.yml");
+        std::fs::write(
+            &file_path,
+            "\"cmd\":\n  \".\":\n    cache: 5m\n  KEY: val\n",
+        )
+        .unwrap();
+        let lade_file = LadeFile::from_path(&file_path).unwrap();
+        let config = lade_file.commands.get("cmd").unwrap()[0]
+            .config
+            .as_ref()
+            .unwrap();
+        assert_eq!(config.cache, Some(std::time::Duration::from_secs(300)));
+
+        std::fs::write(&file_path, "\"cmd\":\n  \".\":\n    cache: 5\n  KEY: val\n").unwrap();
+        assert!(LadeFile::from_path(&file_path).is_err());
+    }
+
+    #[test]
+    fn test_rule_config_limits() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("lade.yml");
+        std::fs::write(
+            &file_path,
+            "\"cmd\":\n  \".\":\n    timeout: 10s\n    retries: 2\n    concurrency: 4\n  KEY: val\n",
+        )
+        .unwrap();
+        let lade_file = LadeFile::from_path(&file_path).unwrap();
+        let config = lade_file.commands.get("cmd").unwrap()[0]
+            .config
+            .as_ref()
+            .unwrap();
+        assert_eq!(
+            config.limits(),
+            Limits {
+                timeout: Some(std::time::Duration::from_secs(10)),
+                retries: Some(2),
+                concurrency: Some(4),
+            }
+        );
+    }
+
     #[test]
     fn test_rule_config_when() {
         let dir = tempdir().unwrap();
@@ -324,4 +449,19 @@ mod tests {
             panic!("expected LadeSecret::User");
         }
     }
+
+    #[test]
+    fn test_rule_config_bitwarden_credentials() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("lade.yml");
+        std::fs::write(
+            &file_path,
+            b"\"cmd\":\n  \".\":\n    bitwarden_access_token: \"file://token?query=.bws\"\n    bitwarden_session: \"raw\"\n  KEY: bws://id\n",
+        ).unwrap();
+        let lade_file = LadeFile::from_path(&file_path).unwrap();
+        let rule = &lade_file.commands.get("cmd").unwrap()[0];
+        let config = rule.config.as_ref().unwrap();
+        let vars = config.credentials().map(|(var, _)| var).collect::<Vec<_>>();
+        assert_eq!(vars, vec!["BWS_ACCESS_TOKEN", "BW_SESSION"]);
+    }
 }
@@ -11,6 +11,8 @@ use std::{
 };
 use tokio::{signal, time};
 
+use lade_sdk::vault::Lease;
+
 use crate::config::{Config, LadeRule, Output, SecretSources};
 use crate::network::{ProviderProgressEvent, ProviderProgressKind, format_timing};
 use crate::provider_progress::ProviderProgressSink;
@@ -32,6 +34,8 @@ pub struct LoadedSecrets {
     pub maskable: FxHashSet<String>,
     /// Warnings collected during resolution (e.g. provider fallbacks).
     pub warnings: Vec<String>,
+    /// Dynamic secret leases to revoke once the command exits.
+    pub leases: Vec<Lease>,
 }
 
 /// Hydrates already-collected `rules` against an already-resolved
@@ -42,6 +46,7 @@ pub async fn hydrate_secrets_with_progress(
     config: &Config,
     rules: &[(PathBuf, LadeRule)],
     saved_user: &Option<String>,
+    unlocked: &HashMap<String, String>,
     progress: ProviderProgressSink,
 ) -> Result<LoadedSecrets> {
     let started = Instant::now();
@@ -54,8 +59,8 @@ pub async fn hydrate_secrets_with_progress(
             kind: ProviderProgressKind::Connecting,
         });
     }
-    let hydrated = config.hydrate_rules(rules, saved_user).await;
-    if let Err(e) = &hydrated {
+    let hydrated = config.hydrate_rules(rules, saved_user, unlocked).await;
+    if hydrated.is_err() {
         for (id, display) in &progress_groups {
             progress.send(ProviderProgressEvent {
                 id: id.clone(),
@@ -63,9 +68,8 @@ pub async fn hydrate_secrets_with_progress(
                 kind: ProviderProgressKind::Failed,
             });
         }
-        return Err(anyhow::anyhow!(e.to_string()));
     }
-    let (vars, sources, maskable, warnings) = hydrated?;
+    let (vars, sources, maskable, warnings, leases) = hydrated?;
     for (id, display) in &progress_groups {
         progress.send(ProviderProgressEvent {
             id: id.clone(),
@@ -78,6 +82,7 @@ pub async fn hydrate_secrets_with_progress(
         sources,
         maskable,
         warnings,
+        leases,
     })
 }
 
@@ -92,6 +97,11 @@ fn provider_label(source: &str) -> String {
                 "vault" => format!("Vault {provider}"),
                 "passbolt" => format!("Passbolt {provider}"),
                 "file" => "File".to_string(),
+                "sops" => "SOPS".to_string(),
+                "age" => "age".to_string(),
+                "env" => "Environment".to_string(),
+                "gen" => "Generator".to_string(),
+                "totp" => "TOTP".to_string(),
                 other => format!("{other} {provider}"),
             }
         }
@@ -1,11 +1,14 @@
-use anyhow::{Ok, Result};
+use anyhow::{Context, Ok, Result};
 use chrono::{DateTime, Utc};
+use lade_sdk::Limits;
 use log::debug;
 
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
+use crate::cache::parse_duration;
+
 use tokio::fs;
 
 #[derive(Deserialize, Serialize)]
@@ -17,6 +20,19 @@ pub struct GlobalConfig {
     pub user: Option<String>,
     #[serde(default)]
     pub cli_check: BTreeMap<String, DateTime<Utc>>,
+    /// Limits per provider scheme, e.g. `{"op": {"timeout": "10s"}}`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub providers: BTreeMap<String, ProviderSettings>,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct ProviderSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
 }
 
 impl GlobalConfig {
@@ -43,10 +59,32 @@ impl GlobalConfig {
                 update_check: None,
                 user: None,
                 cli_check: BTreeMap::new(),
+                providers: BTreeMap::new(),
             })
         }
     }
 
+    /// The `providers` settings as limits per scheme.
+    pub fn provider_limits(&self) -> Result<HashMap<String, Limits>> {
+        self.providers
+            .iter()
+            .map(|(scheme, settings)| {
+                let timeout = settings
+                    .timeout
+                    .as_deref()
+                    .map(parse_duration)
+                    .transpose()
+                    .with_context(|| format!("invalid timeout for {scheme} providers"))?;
+                let limits = Limits {
+                    timeout,
+                    retries: settings.retries,
+                    concurrency: settings.concurrency,
+                };
+                Ok((scheme.clone(), limits))
+            })
+            .collect()
+    }
+
     pub async fn update<F: FnOnce(&mut GlobalConfig)>(f: F) -> Result<()> {
         let mut config = Self::load().await?;
         f(&mut config);
@@ -110,4 +148,23 @@ mod tests {
                 });
         });
     }
+
+    #[test]
+    fn provider_limits_parse_timeouts() {
+        let config: GlobalConfig = serde_json::from_str(
+            r#"{"user":null,"providers":{"op":{"timeout":"10s"},"vault":{"retries":2,"concurrency":4}}}"#,
+        )
+        .unwrap();
+        let limits = config.provider_limits().unwrap();
+        assert_eq!(
+            limits["op"].timeout,
+            Some(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(limits["vault"].retries, Some(2));
+        assert_eq!(limits["vault"].concurrency, Some(4));
+
+        let config: GlobalConfig =
+            serde_json::from_str(r#"{"user":null,"providers":{"op":{"timeout":"10"}}}"#).unwrap();
+        assert!(config.provider_limits().is_err());
+    }
 }
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use lade_sdk::vault::Lease;
 use rustc_hash::FxHashSet;
 use std::future::Future;
 use std::path::Path;
@@ -12,6 +13,7 @@ use crate::files::{
     LoadedSecrets, hydrate_secrets_with_progress, remove_files, sleep_or_cancel, split_env_files,
     write_files,
 };
+use crate::leases::{LeaseGuard, revoke_all, revoke_leases};
 use crate::message_box;
 use crate::network::{self, stop_network_pids};
 use crate::prompt;
@@ -23,11 +25,26 @@ use crate::shell::Shell;
 use crate::{compat, masking};
 
 fn loader_error_box(e: &anyhow::Error) -> message_box::MessageBox {
-    message_box::MessageBox::new()
-        .error()
-        .line("Lade could not get secrets from one loader:")
-        .line("")
-        .paragraph(e.to_string())
+    let mb = message_box::MessageBox::new().error();
+    let Some(error) = e.downcast_ref::<lade_sdk::Error>() else {
+        return mb
+            .line("Lade could not get secrets from one loader:")
+            .line("")
+            .paragraph(e.to_string());
+    };
+    let mb = match error.binding() {
+        Some(binding) => mb.line(format!("Lade could not get {binding}:")),
+        None => mb.line("Lade could not get secrets from one loader:"),
+    }
+    .line("")
+    .paragraph(error.to_string())
+    .line("");
+    let mb = match error.uri() {
+        Some(uri) => mb.line(format!("Source: {uri}")),
+        None => mb.line(format!("Provider: {}", error.scheme())),
+    };
+    // A stable kind lets agents react without parsing the message.
+    mb.line(format!("Error kind: {}", error.kind()))
 }
 
 type SecretBundle = (
@@ -36,12 +53,17 @@ type SecretBundle = (
     HashMap<String, String>,
     FxHashSet<String>,
     Vec<String>,
+    Vec<Lease>,
 );
 
 enum Acquisition<N> {
     Ready(SecretBundle, N),
     Failed(anyhow::Error),
-    FailedWithFiles(anyhow::Error, HashMap<PathBuf, HashMap<String, String>>),
+    FailedWithFiles(
+        anyhow::Error,
+        HashMap<PathBuf, HashMap<String, String>>,
+        Vec<Lease>,
+    ),
     FailedWithNetwork(anyhow::Error, N),
 }
 
@@ -63,15 +85,17 @@ pub async fn run_inject(
 
     let saved_user = crate::config::saved_user().await?;
     let network_bindings = Config::network_bindings_from_rules(&rules, &saved_user);
-    let ((mut env, files, sources, maskable, warnings), network) = acquire_secrets_and_network(
-        ctx,
-        config,
-        &rules,
-        &saved_user,
-        network_bindings,
-        network::start_attached_network_session,
-    )
-    .await;
+    let ((mut env, files, sources, maskable, warnings, leases), network) =
+        acquire_secrets_and_network(
+            ctx,
+            config,
+            &rules,
+            &saved_user,
+            network_bindings,
+            network::start_attached_network_session,
+        )
+        .await;
+    let leases = LeaseGuard::new(leases);
     show_loader_warnings(ctx, &warnings).await;
     if let Err(error) = merge_env_with_conflicts(&mut env, network.env.clone()) {
         let _ = remove_files(&mut files.keys());
@@ -104,6 +128,7 @@ pub async fn run_inject(
         }
         Err(e) => {
             let _ = remove_files(&mut files.keys());
+            drop(leases);
             drop(network);
             return Err(e);
         }
@@ -153,13 +178,21 @@ async fn acquire_secrets_and_network<N: Send + 'static>(
     network_bindings: Vec<NetworkBinding>,
     start_network: impl FnOnce(&[NetworkBinding], ProviderProgressSink) -> Result<N> + Send + 'static,
 ) -> (SecretBundle, N) {
+    // Prompts must finish before the progress renderer takes over stderr.
+    let unlocked = match prompt::unlock_keepass(ctx, rules, saved_user).await {
+        Ok(unlocked) => unlocked,
+        Err(e) => {
+            handle_provider_failure(ctx, &e).await;
+            std::process::exit(crate::exit_codes::FAILURE);
+        }
+    };
     let provider_progress = start_provider_progress(ctx.stderr_is_terminal);
     let secret_sink = provider_progress.sink();
     let network_sink = provider_progress.sink();
     let mut provider_progress = Some(provider_progress);
 
     let acquisition = {
-        let secret_task = prepare_secrets(config, rules, saved_user, secret_sink);
+        let secret_task = prepare_secrets(config, rules, saved_user, &unlocked, secret_sink);
         let network_task = async {
             let result =
                 tokio::task::spawn_blocking(move || start_network(&network_bindings, network_sink))
@@ -177,8 +210,9 @@ async fn acquire_secrets_and_network<N: Send + 'static>(
             handle_provider_failure(ctx, &e).await;
             std::process::exit(crate::exit_codes::FAILURE);
         }
-        Acquisition::FailedWithFiles(e, files) => {
+        Acquisition::FailedWithFiles(e, files, leases) => {
             let _ = remove_files(&mut files.keys());
+            revoke_leases(&leases);
             handle_provider_failure(ctx, &e).await;
             std::process::exit(crate::exit_codes::FAILURE);
         }
@@ -204,8 +238,8 @@ where
                 Ok(secret_result) => match network_task.await {
                     Ok(network_result) => Acquisition::Ready(secret_result, network_result),
                     Err(e) => {
-                        let (_, files, ..) = secret_result;
-                        Acquisition::FailedWithFiles(e, files)
+                        let (_, files, _, _, _, leases) = secret_result;
+                        Acquisition::FailedWithFiles(e, files, leases)
                     }
                 },
                 Err(e) => {
@@ -233,6 +267,7 @@ async fn prepare_secrets(
     config: &Config,
     rules: &[(PathBuf, LadeRule)],
     saved_user: &Option<String>,
+    unlocked: &HashMap<String, String>,
     progress: ProviderProgressSink,
 ) -> Result<SecretBundle> {
     let LoadedSecrets {
@@ -240,11 +275,15 @@ async fn prepare_secrets(
         sources,
         maskable,
         warnings,
-    } = hydrate_secrets_with_progress(config, rules, saved_user, progress).await?;
+        leases,
+    } = hydrate_secrets_with_progress(config, rules, saved_user, unlocked, progress).await?;
 
     let (env, files) = split_env_files(vars);
-    write_files(&files)?;
-    Ok((env, files, sources, maskable, warnings))
+    if let Err(e) = write_files(&files) {
+        revoke_all(&leases).await;
+        return Err(e);
+    }
+    Ok((env, files, sources, maskable, warnings, leases))
 }
 
 pub async fn handle_set(
@@ -259,6 +298,7 @@ pub async fn handle_set(
         shell.unset(vec![
             crate::shell::LADE_PENDING.to_string(),
             crate::shell::LADE_NETWORK_PIDS.to_string(),
+            crate::shell::LADE_LEASES.to_string(),
             crate::shell::LADE_RESTORE.to_string(),
         ])
     );
@@ -289,17 +329,21 @@ pub async fn handle_set(
     }
     let saved_user = crate::config::saved_user().await?;
     let network_bindings = Config::network_bindings_from_rules(&rules, &saved_user);
-    let ((mut env, _files, _sources, _maskable, warnings), detached) = acquire_secrets_and_network(
-        ctx,
-        config,
-        &rules,
-        &saved_user,
-        network_bindings,
-        network::start_detached_network_session,
-    )
-    .await;
+    let ((mut env, _files, _sources, _maskable, warnings, leases), detached) =
+        acquire_secrets_and_network(
+            ctx,
+            config,
+            &rules,
+            &saved_user,
+            network_bindings,
+            network::start_detached_network_session,
+        )
+        .await;
     show_loader_warnings(ctx, &warnings).await;
-    merge_env_with_conflicts(&mut env, detached.env)?;
+    if let Err(e) = merge_env_with_conflicts(&mut env, detached.env) {
+        revoke_all(&leases).await;
+        return Err(e);
+    }
     if !detached.pids.is_empty() {
         let raw = detached
             .pids
@@ -309,6 +353,13 @@ pub async fn handle_set(
             .join(",");
         env.insert(crate::shell::LADE_NETWORK_PIDS.to_string(), raw);
     }
+    // The shell keeps the leases until `lade unset` runs after the command.
+    if !leases.is_empty() {
+        env.insert(
+            crate::shell::LADE_LEASES.to_string(),
+            crate::shell::LeasesPayload { leases }.encode()?,
+        );
+    }
     println!("{}", stamp_preexec(shell, env)?);
     Ok(())
 }
@@ -320,7 +371,7 @@ fn stamp_preexec(shell: &Shell, mut env: HashMap<String, String>) -> Result<Stri
     );
     let previous = env
         .keys()
-        .filter(|key| *key != crate::shell::LADE_NETWORK_PIDS)
+        .filter(|key| *key != crate::shell::LADE_NETWORK_PIDS && *key != crate::shell::LADE_LEASES)
         .map(|key| (key.clone(), std::env::var(key).ok()))
         .collect::<HashMap<_, _>>();
     env.insert(
@@ -418,6 +469,15 @@ pub async fn handle_unset(
     if let Ok(raw) = std::env::var(crate::shell::LADE_NETWORK_PIDS) {
         stop_network_pids(&raw);
     }
+    if let Ok(raw) = std::env::var(crate::shell::LADE_LEASES) {
+        match crate::shell::LeasesPayload::decode(&raw) {
+            Ok(payload) => revoke_leases(&payload.leases),
+            Err(_) => message_box::MessageBox::new()
+                .warning()
+                .line("The Vault lease list is corrupted; leases expire at the end of their TTL.")
+                .print_stderr(),
+        }
+    }
     let command = commands.join(" ");
     let rules = config.collect_for(&command, ctx.audience);
     let keys = if rules.is_empty() {
@@ -447,6 +507,7 @@ pub async fn handle_unset(
     let meta = shell.unset(vec![
         crate::shell::LADE_RESTORE.to_string(),
         crate::shell::LADE_NETWORK_PIDS.to_string(),
+        crate::shell::LADE_LEASES.to_string(),
     ]);
     let line = [env_line, meta]
         .into_iter()
@@ -527,6 +588,22 @@ mod tests {
     use super::*;
     use std::time::{Duration, Instant};
 
+    #[test]
+    fn loader_error_box_names_binding_source_and_kind() {
+        let e = anyhow::Error::from(
+            lade_sdk::Error::not_found("file://prod.json?query=.db", "cannot read file prod.json")
+                .with_binding("DB_PASSWORD"),
+        );
+        let rendered = format!("{:?}", loader_error_box(&e));
+        assert!(rendered.contains("Lade could not get DB_PASSWORD:"));
+        assert!(rendered.contains("Source: file://prod.json?query=***"));
+        assert!(rendered.contains("Error kind: not_found"));
+
+        let rendered = format!("{:?}", loader_error_box(&anyhow::anyhow!("boom")));
+        assert!(rendered.contains("from one loader"));
+        assert!(!rendered.contains("Error kind"));
+    }
+
     #[tokio::test]
     async fn provider_race_fails_fast_and_does_not_deadlock_progress_renderer() {
         let mut provider_progress = Some(start_provider_progress(false));
@@ -543,6 +620,7 @@ mod tests {
                     HashMap::new(),
                     FxHashSet::default(),
                     Vec::new(),
+                    Vec::new(),
                 ))
             };
             let network_task =
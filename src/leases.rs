@@ -0,0 +1,112 @@
+use std::{
+    future::Future,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use futures::future::join_all;
+use lade_sdk::vault::Lease;
+
+use crate::message_box::MessageBox;
+
+/// Command-scoped dynamic secret leases: renewed in the background while the
+/// command runs, revoked when the guard is dropped.
+pub struct LeaseGuard {
+    leases: Vec<Lease>,
+    stop: Option<Sender<()>>,
+    renewer: Option<JoinHandle<()>>,
+}
+
+impl LeaseGuard {
+    pub fn new(leases: Vec<Lease>) -> Self {
+        let renewable = leases
+            .iter()
+            .filter(|lease| lease.renewable && lease.duration > 0)
+            .cloned()
+            .collect::<Vec<_>>();
+        let Some(shortest) = renewable.iter().map(|lease| lease.duration).min() else {
+            return Self {
+                leases,
+                stop: None,
+                renewer: None,
+            };
+        };
+        // Renew at half the shortest TTL so a slow renewal still
+        // lands before the lease expires.
+        let interval = Duration::from_secs((shortest / 2).max(1));
+        let (stop, stopped) = mpsc::channel::<()>();
+        let renewer = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                for lease in &renewable {
+                    if let Err(e) = block_on(lease.renew()) {
+                        log::warn!("{e}");
+                    }
+                }
+            }
+        });
+        Self {
+            leases,
+            stop: Some(stop),
+            renewer: Some(renewer),
+        }
+    }
+
+    pub fn revoke(&mut self) {
+        drop(self.stop.take());
+        if let Some(renewer) = self.renewer.take() {
+            let _ = renewer.join();
+        }
+        revoke_leases(&std::mem::take(&mut self.leases));
+    }
+}
+
+impl Drop for LeaseGuard {
+    fn drop(&mut self) {
+        self.revoke();
+    }
+}
+
+/// Revokes every lease, reporting the ones Vault refused so users know which
+/// credentials outlive the command until their TTL.
+pub fn revoke_leases(leases: &[Lease]) {
+    block_on(revoke_all(leases));
+}
+
+/// [`revoke_leases`] from async code.
+pub async fn revoke_all(leases: &[Lease]) {
+    let failures = join_all(leases.iter().map(Lease::revoke))
+        .await
+        .into_iter()
+        .filter_map(Result::err)
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    if !failures.is_empty() {
+        MessageBox::new()
+            .warning()
+            .line("Lade could not revoke some Vault leases:")
+            .line("")
+            .paragraphs(failures.iter().map(String::as_str))
+            .print_stderr();
+    }
+}
+
+/// Runs a Vault call to completion from synchronous code, including `Drop`
+/// while the main runtime is busy, on a dedicated thread and runtime.
+fn block_on<F: Future + Send>(future: F) -> F::Output
+where
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("lease runtime")
+                    .block_on(future)
+            })
+            .join()
+            .expect("lease thread")
+    })
+}
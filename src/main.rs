@@ -4,6 +4,7 @@ use std::{env, io::Read, time::Duration};
 mod access;
 mod args;
 mod audience;
+mod cache;
 mod compat;
 mod config;
 mod context;
@@ -12,6 +13,7 @@ mod exit_codes;
 mod files;
 mod global_config;
 mod inject;
+mod leases;
 mod masking;
 mod mcp;
 mod message_box;
@@ -25,9 +27,9 @@ mod shell;
 mod status;
 mod upgrade;
 
-use args::{Args, Command, DEFAULT_MASK_FORMAT, EvalCommand, InjectCommand};
+use args::{Args, CacheCommand, Command, DEFAULT_MASK_FORMAT, EvalCommand, InjectCommand};
 use clap::{CommandFactory, Parser};
-use config::LadeFile;
+use config::{LadeFile, saved_user};
 use context::InvocationContext;
 use global_config::GlobalConfig;
 use inject::{handle_approve, handle_set, handle_unset, run_inject};
@@ -136,6 +138,16 @@ async fn run() -> Result<()> {
         }
         Command::Upgrade(opts) => return upgrade::perform(opts).await,
         Command::Status(opts) => return status::run(opts).await,
+        Command::Cache {
+            action: CacheCommand::Clear,
+        } => {
+            let removed = cache::clear()?;
+            message_box::MessageBox::new()
+                .info()
+                .line(format!("Cleared {removed} cached value(s)"))
+                .print_plain_stderr();
+            return Ok(());
+        }
         Command::User { username, reset } => {
             if reset {
                 GlobalConfig::update(|c| c.user = None).await?;
@@ -177,7 +189,9 @@ async fn run() -> Result<()> {
     let current_dir = env::current_dir()?;
 
     if let Command::Eval { uri } = command {
-        let value =
+        // The printed value is used after lade exits, so its leases are left
+        // to expire with their TTL.
+        let (value, _leases) =
             hydrate_one(uri.clone(), &current_dir, &std::collections::HashMap::new()).await?;
         if ctx.is_interactive() {
             compat::warn_outdated(&ctx, compat::known_schemes(std::iter::once(uri.as_str()))).await;
@@ -201,6 +215,34 @@ async fn run() -> Result<()> {
         }
     };
 
+    if let Command::Put { uri } = command {
+        // The value never comes from an argument, so it stays out of shell
+        // history and the process list.
+        if ctx.stdin_is_terminal {
+            message_box::MessageBox::new()
+                .error()
+                .line("`lade put` reads the secret value from stdin.")
+                .line("")
+                .line("Pipe it in, e.g. `pbpaste | lade put URI` or `lade put URI < file`.")
+                .print_stderr();
+            std::process::exit(exit_codes::FAILURE);
+        }
+        let mut value = String::new();
+        std::io::stdin().read_to_string(&mut value)?;
+        let value = value
+            .strip_suffix('\n')
+            .map(|v| v.strip_suffix('\r').unwrap_or(v))
+            .unwrap_or(&value);
+        let saved_user = saved_user().await?;
+        let mut leases = Vec::new();
+        let written = match config.credentials_for(&uri, &saved_user, &mut leases).await {
+            Ok(extra_env) => lade_sdk::write(&uri, value, &current_dir, &extra_env).await,
+            Err(e) => Err(e),
+        };
+        leases::revoke_all(&leases).await;
+        return written;
+    }
+
     let mut inject_exit_code: Option<i32> = None;
 
     match command {
@@ -1,8 +1,8 @@
 use anyhow::{Result, bail};
 use std::process::Command;
 
-use crate::network::kubeconfig::resolve_kube_context;
 use crate::network::types::ProviderSpec;
+use lade_sdk::network::kubeconfig::resolve_kube_context;
 
 pub(crate) fn build_command(
     spec: &ProviderSpec,
@@ -1,6 +1,5 @@
 mod acquire;
 mod command;
-mod kubeconfig;
 mod parse;
 mod process;
 mod progress;
@@ -1,9 +1,12 @@
+use std::collections::{BTreeSet, HashMap};
 use std::io::Write;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use sha2::{Digest, Sha256};
 use tokio::{io::AsyncBufReadExt, select, signal};
 
+use crate::config::{Config, LadeRule};
 use crate::context::InvocationContext;
 use crate::message_box::MessageBox;
 use crate::shell::{LADE_APPROVE, LADE_DISCLAIMER_APPROVED};
@@ -139,6 +142,89 @@ pub async fn confirm_disclaimers(disclaimers: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Asks once per KeePassXC database that no `?password=${NAME}` binding
+/// unlocks. Non-interactive runs skip the prompt and let the provider report
+/// the locked database instead.
+pub async fn unlock_keepass(
+    ctx: &InvocationContext,
+    rules: &[(PathBuf, LadeRule)],
+    saved_user: &Option<String>,
+) -> Result<HashMap<String, String>> {
+    if !ctx.is_interactive() {
+        return Ok(HashMap::new());
+    }
+    let databases = Config::secret_sources_from_rules(rules, saved_user)?
+        .all()
+        .filter_map(|source| lade_sdk::keepass::locked_database(source))
+        .collect::<BTreeSet<_>>();
+    let mut unlocked = HashMap::new();
+    for database in databases {
+        eprint!("Password to unlock {database}: ");
+        std::io::stderr().flush()?;
+        let password = read_hidden_line().await?;
+        unlocked.insert(database, password);
+    }
+    Ok(unlocked)
+}
+
+/// Reads one line from the terminal with echo turned off. Ctrl-C exits like
+/// at the other prompts, with echo back on.
+async fn read_hidden_line() -> Result<String> {
+    let hidden = HiddenInput::new()?;
+    let read = select! {
+        read = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        }) => read,
+        _ = signal::ctrl_c() => {
+            drop(hidden);
+            eprintln!();
+            std::process::exit(crate::exit_codes::INTERRUPTED)
+        }
+    };
+    drop(hidden);
+    eprintln!();
+    let line = read??;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Terminal echo turned off until dropped.
+struct HiddenInput {
+    #[cfg(unix)]
+    original: Option<nix::sys::termios::Termios>,
+}
+
+impl HiddenInput {
+    fn new() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use nix::sys::termios::{LocalFlags, SetArg, tcgetattr, tcsetattr};
+            let original = tcgetattr(std::io::stdin()).ok();
+            if let Some(original) = &original {
+                let mut hidden = original.clone();
+                hidden.local_flags.remove(LocalFlags::ECHO);
+                tcsetattr(std::io::stdin(), SetArg::TCSANOW, &hidden)?;
+            }
+            Ok(Self { original })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+}
+
+impl Drop for HiddenInput {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(original) = &self.original {
+            let _ = nix::sys::termios::tcsetattr(
+                std::io::stdin(),
+                nix::sys::termios::SetArg::TCSANOW,
+                original,
+            );
+        }
+    }
+}
+
 async fn read_stdin() -> Option<String> {
     let mut line = String::new();
     let mut reader = tokio::io::BufReader::new(tokio::io::stdin());
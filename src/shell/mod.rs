@@ -5,6 +5,7 @@ pub use preexec::preexec_installed;
 use anyhow::{Context, Result, bail};
 use base64::Engine;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use lade_sdk::vault::Lease;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use std::{collections::HashMap, path::PathBuf, str::FromStr};
 use sysinfo::{ProcessesToUpdate, System, get_current_pid};
@@ -13,6 +14,7 @@ pub const LADE_PENDING: &str = "LADE_PENDING";
 pub const LADE_DISCLAIMER_APPROVED: &str = "LADE_DISCLAIMER_APPROVED";
 pub const LADE_APPROVE: &str = "LADE_APPROVE";
 pub const LADE_NETWORK_PIDS: &str = "LADE_NETWORK_PIDS";
+pub const LADE_LEASES: &str = "LADE_LEASES";
 pub const LADE_RESTORE: &str = "LADE_RESTORE";
 pub const LADE_VIA: &str = "LADE_VIA";
 pub const LADE_VIA_PREEXEC: &str = "preexec";
@@ -49,6 +51,22 @@ impl RestorePayload {
     }
 }
 
+/// Dynamic secret leases issued by `lade set`, revoked by `lade unset`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LeasesPayload {
+    pub leases: Vec<Lease>,
+}
+
+impl LeasesPayload {
+    pub fn encode(&self) -> Result<String> {
+        encode_v1("LADE_LEASES", self)
+    }
+
+    pub fn decode(value: &str) -> Result<Self> {
+        decode_v1("LADE_LEASES", value)
+    }
+}
+
 fn encode_v1<T: Serialize>(label: &str, value: &T) -> Result<String> {
     let json = serde_json::to_string(value).with_context(|| format!("failed to encode {label}"))?;
     Ok(format!("v1:{}", URL_SAFE_NO_PAD.encode(json)))
@@ -283,6 +301,26 @@ mod tests {
         assert!(RestorePayload::decode("v1:!!!").is_err());
     }
 
+    #[test]
+    fn test_leases_payload_roundtrip() {
+        let payload = LeasesPayload {
+            leases: vec![Lease {
+                server: lade_sdk::vault::Server {
+                    address: "https://vault.example.com".to_string(),
+                    namespace: None,
+                    auth: lade_sdk::vault::Auth::Token,
+                },
+                id: "database/creds/app/abc".to_string(),
+                duration: 3600,
+                renewable: true,
+                env: HashMap::new(),
+            }],
+        };
+        let decoded = LeasesPayload::decode(&payload.encode().unwrap()).unwrap();
+        assert_eq!(payload, decoded);
+        assert!(LeasesPayload::decode("not-v1").is_err());
+    }
+
     #[test]
     fn test_restore_fish_syntax() {
         let previous = HashMap::from([
@@ -66,7 +66,6 @@ fn vault_shell_scripts_run_from_cargo_test_workspace() {
             "-i",
             &format!("PATH={path}"),
             "VAULT_TOKEN=token",
-            "LADE_VAULT_HTTP=1",
             "bash",
             "tests/test_vault.bash",
         ],
@@ -77,7 +76,6 @@ fn vault_shell_scripts_run_from_cargo_test_workspace() {
             "-i",
             &format!("PATH={path}"),
             "VAULT_TOKEN=token",
-            "LADE_VAULT_HTTP=1",
             "zsh",
             "tests/test_vault.zsh",
         ],
@@ -88,7 +86,6 @@ fn vault_shell_scripts_run_from_cargo_test_workspace() {
             "-i",
             &format!("PATH={path}"),
             "VAULT_TOKEN=token",
-            "LADE_VAULT_HTTP=1",
             "fish",
             "tests/test_vault.fish",
         ],
@@ -399,3 +399,65 @@ fn test_inject_strips_via_stamp_from_child() {
         .success()
         .stdout(predicates::str::contains("via=pretool").not());
 }
+
+#[test]
+#[cfg(unix)]
+fn test_inject_reuses_cached_value_until_cleared() {
+    let dir = tempdir().unwrap();
+    let home = tempdir().unwrap();
+    let calls = dir.path().join("calls");
+    fs::write(
+        dir.path().join("lade.yml"),
+        format!(
+            "\"echo.*\":\n  .:\n    cache: 5m\n  SECRET: \"sh://echo call >> {}; echo cached_secret\"\n",
+            calls.display()
+        ),
+    )
+    .unwrap();
+    let run = || {
+        common::lade(home.path())
+            .current_dir(dir.path())
+            .env("LADE_CACHE_DIR", home.path().join("cache"))
+            .env("XDG_RUNTIME_DIR", home.path().join("run"))
+            .args(["inject", "--no-mask", "echo", "$SECRET"])
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("cached_secret"));
+    };
+    run();
+    run();
+    assert_eq!(fs::read_to_string(&calls).unwrap().lines().count(), 1);
+
+    common::lade(home.path())
+        .env("LADE_CACHE_DIR", home.path().join("cache"))
+        .env("XDG_RUNTIME_DIR", home.path().join("run"))
+        .args(["cache", "clear"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Cleared 1 cached value(s)"));
+    run();
+    assert_eq!(fs::read_to_string(&calls).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn test_inject_failure_names_binding_and_source() {
+    let dir = tempdir().unwrap();
+    let home = tempdir().unwrap();
+    fs::write(
+        dir.path().join("lade.yml"),
+        "\"echo.*\":\n  DB_PASSWORD: \"file://missing.json?query=.db\"\n",
+    )
+    .unwrap();
+    common::lade(home.path())
+        .current_dir(dir.path())
+        .args(["inject", "echo", "$DB_PASSWORD"])
+        .assert()
+        .failure()
+        .stderr(
+            predicates::str::contains("Lade could not get DB_PASSWORD:")
+                .and(predicates::str::contains(
+                    "Source: file://missing.json?query=***",
+                ))
+                .and(predicates::str::contains("Error kind: not_found")),
+        );
+}
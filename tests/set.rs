@@ -271,30 +271,48 @@ fn test_set_without_silence_shows_hydration_progress() {
         .stderr(predicates::str::contains("Raw: KEY"));
 }
 
+/// Stand-in Vault answering the KV v2 mount lookup and any secret read.
+fn stand_in_vault() -> String {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut buffer = [0; 4096];
+            let read = stream.read(&mut buffer).unwrap();
+            let request = String::from_utf8_lossy(&buffer[..read]);
+            let body = if request.starts_with("GET /v1/sys/internal/ui/mounts/secret ") {
+                r#"{"data":{"options":{"version":"2"}}}"#
+            } else {
+                r#"{"data":{"data":{"password":"vault_injected"}}}"#
+            };
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    });
+    address
+}
+
 #[test]
-#[cfg(unix)]
-fn test_inject_with_fake_vault_cli() {
+fn test_set_with_stand_in_vault() {
     let dir = tempdir().unwrap();
     let home = tempdir().unwrap();
-    let fake_bin = tempdir().unwrap();
-    common::fake_cli(
-        &fake_bin,
-        "vault",
-        r#"echo '{"data":{"data":{"password":"vault_injected"}}}'"#,
-    );
+    let address = stand_in_vault();
     fs::write(
         dir.path().join("lade.yml"),
-        "\"vault.*\":\n  PASSWORD: \"vault://localhost/secret/myapp/password\"\n",
+        format!(
+            "\"vault.*\":\n  PASSWORD: \"vault://{address}/secret/myapp/password?tls=false&auth=token\"\n"
+        ),
     )
     .unwrap();
-    let new_path = format!(
-        "{}:{}",
-        fake_bin.path().display(),
-        std::env::var("PATH").unwrap_or_default()
-    );
     common::lade(home.path())
         .current_dir(dir.path())
-        .env("PATH", &new_path)
+        .env("VAULT_TOKEN", "token")
         .args(["set", "vault cmd"])
         .assert()
         .success()
@@ -1,4 +1,4 @@
-# env -i PATH="$PATH" VAULT_TOKEN="token" LADE_VAULT_HTTP=1 bash tests/test_vault.bash
+# env -i PATH="$PATH" VAULT_TOKEN="token" bash tests/test_vault.bash
 set -e
 
 source tests/test_vault_setup.bash
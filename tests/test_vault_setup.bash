@@ -1,7 +1,5 @@
 set -e
 
-export LADE_VAULT_HTTP=1
-
 if ! curl -s http://127.0.0.1:8200/v1/sys/health > /dev/null; then
   docker compose up -d vault
 fi